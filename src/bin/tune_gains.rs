@@ -0,0 +1,44 @@
+//! Prints recommended driver gains for the default craft class without starting the game.
+//! Run with `cargo run --bin tune_gains`.
+
+#[cfg(feature = "dylink")]
+#[allow(unused_imports)]
+use dylink;
+
+use isis::{
+    craft::{self, engine::tuning},
+    math::*,
+};
+
+fn main() {
+    let engine_config = craft::engine::EngineConfig::default();
+    let dimensions: craft::CraftDimensions = (TVec3::ONE * 8.).into();
+    let profile = engine_config.gain_profile(dimensions);
+    let tuned = tuning::autotune_craft(&engine_config, dimensions, &Default::default());
+    for (name, plant, vel_limit, profile, tuned) in [
+        (
+            "linear",
+            tuning::PlantModel::linear(&engine_config),
+            engine_config.linvel_limit,
+            profile.linear,
+            tuned.linear,
+        ),
+        (
+            "angular",
+            tuning::PlantModel::angular(&engine_config, dimensions),
+            engine_config.angvel_limit,
+            profile.angular,
+            tuned.angular,
+        ),
+    ] {
+        println!("{name} profile: {profile:#?}");
+        println!("{name} recommended: {tuned:#?}");
+        for fraction in [0.05, 0.25, 1.] {
+            let response = tuning::step_response(&plant, &tuned, vel_limit * fraction, 10.);
+            println!(
+                "{name} step of {fraction} the limit: overshoot {:?}, settling time {:?}",
+                response.overshoot, response.settling_time
+            );
+        }
+    }
+}
@@ -8,6 +8,9 @@ use bevy_rapier3d::prelude::*;
 pub mod arms;
 pub mod attire;
 pub mod engine;
+pub mod repair;
+pub mod signature;
+pub mod trajectory;
 
 pub struct CraftsPlugin;
 
@@ -19,9 +22,17 @@ impl Plugin for CraftsPlugin {
             .add_system(engine::apply_flames_simple_accel)
             .add_plugin(attire::AttirePlugin)
             .add_plugin(arms::ArmsPlugin)
+            .add_plugin(repair::RepairPlugin)
+            .add_plugin(signature::SignaturePlugin)
+            .add_plugin(trajectory::TrajectoryPlugin)
             .register_inspectable::<engine::LinearEngineState>()
             .register_inspectable::<engine::AngularEngineState>()
-            .register_inspectable::<engine::EngineConfig>();
+            .register_inspectable::<engine::EngineConfig>()
+            .register_inspectable::<attire::CraftIntegrity>()
+            .register_inspectable::<repair::RepairAura>()
+            .register_inspectable::<repair::ResupplyStation>()
+            .register_inspectable::<signature::CraftSignature>()
+            .register_inspectable::<signature::SignatureProfile>();
     }
 }
 
@@ -35,6 +46,9 @@ pub struct CraftBundle {
 
     pub rigid_body_sync: RigidBodyPositionSync,
     pub collision_damage_tag: attire::CollisionDamageEnabledRb,
+    pub integrity: attire::CraftIntegrity,
+    pub signature: signature::CraftSignature,
+    pub signature_profile: signature::SignatureProfile,
 
     #[bundle]
     pub collider: attire::CollisionDamageEnabledColliderBundle,
@@ -46,6 +60,7 @@ pub struct CraftBundle {
     pub angular_state: engine::AngularEngineState,
     pub linear_pid: engine::LinearDriverPid,
     pub angular_pid: engine::AngularDriverPid,
+    pub predicted_trajectory: trajectory::PredictedTrajectory,
 
     pub name: Name,
 }
@@ -64,6 +79,7 @@ impl CraftBundle {
     }
     pub fn new(engine_config: engine::EngineConfig, dimensions: CraftDimensions) -> Self {
         let derived_config = engine_config.derive_items(dimensions);
+        let gains = engine_config.gain_profile(dimensions);
         Self {
             xfrom: Transform::default(),
             global_xform: GlobalTransform::default(),
@@ -72,23 +88,15 @@ impl CraftBundle {
             dimensions,
             linear_state: Default::default(),
             angular_state: Default::default(),
-            linear_pid: engine::LinearDriverPid(crate::utils::PIDControllerVec3::new(
-                TVec3::ONE * 30.,
-                TVec3::ZERO,
-                TVec3::ZERO,
-                TVec3::ZERO,
-                TVec3::ZERO,
-            )),
-            angular_pid: engine::AngularDriverPid(crate::utils::PIDControllerVec3::new(
-                TVec3::ONE * 10_000.0,
-                TVec3::ONE * 0.0,
-                TVec3::ONE,
-                TVec3::ONE,
-                TVec3::ONE * -0.,
-            )),
+            linear_pid: engine::LinearDriverPid(gains.linear.controller()),
+            angular_pid: engine::AngularDriverPid(gains.angular.controller()),
+            predicted_trajectory: Default::default(),
             rigid_body: Self::default_rb_bundle(),
             rigid_body_sync: RigidBodyPositionSync::Discrete,
             collision_damage_tag: attire::CollisionDamageEnabledRb,
+            integrity: Default::default(),
+            signature: Default::default(),
+            signature_profile: Default::default(),
             collider: Default::default(),
             name: Self::DEFAULT_NAME.into(),
         }
@@ -116,6 +116,40 @@ impl WeaponActivationState {
     }
 }
 
+/// Optional ammunition store for a weapon. Weapons without one have endless ammo.
+#[derive(Debug, Clone, Component)]
+pub struct Ammunition {
+    pub remaining: u32,
+    pub capacity: u32,
+}
+
+impl Ammunition {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            remaining: capacity,
+            capacity,
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.remaining >= self.capacity
+    }
+
+    /// Adds the given rounds and returns how many didn't fit.
+    pub fn resupply(&mut self, rounds: u32) -> u32 {
+        let space = self.capacity - self.remaining.min(self.capacity);
+        let added = rounds.min(space);
+        self.remaining += added;
+        rounds - added
+    }
+}
+
 #[derive(Component)]
 pub struct ProjectileWeapon {
     pub proj_damage: Damage,
@@ -132,6 +166,8 @@ pub struct ProjectileWeapon {
 pub struct Projectile {
     pub damage: Damage,
     pub source_wpn: Entity,
+    /// The craft the source weapon's attached to.
+    pub source_craft: Entity,
     pub emit_instant_secs: f64,
     pub lifespan_secs: f64,
 }
@@ -141,8 +177,10 @@ fn handle_activate_weapon_events_projectile(
     mut commands: Commands,
     mut weapons: Query<(
         &ProjectileWeapon,
+        &CraftWeapon,
         &mut WeaponActivationState,
         &GlobalTransform,
+        Option<&mut Ammunition>,
     )>,
     mut fire_events: EventReader<ActivateWeaponEvent>,
     //mut lines: ResMut<bevy_prototype_debug_lines::DebugLines>,
@@ -150,7 +188,7 @@ fn handle_activate_weapon_events_projectile(
 ) {
     for event in fire_events.iter() {
         match weapons.get_mut(event.weapon_id) {
-            Ok((proj_wpn, mut firing_state, xform)) => {
+            Ok((proj_wpn, wpn, mut firing_state, xform, ammo)) => {
                 /* tracing::info!(
                     "\n{:?}\n{:?}",
                     xform.forward(),
@@ -159,6 +197,12 @@ fn handle_activate_weapon_events_projectile(
                 if !firing_state.can_activate(&time) {
                     continue;
                 }
+                if let Some(mut ammo) = ammo {
+                    if ammo.is_empty() {
+                        continue;
+                    }
+                    ammo.remaining -= 1;
+                }
                 match firing_state.as_mut() {
                     WeaponActivationState::Discrete {
                         last_firing_time, ..
@@ -170,6 +214,7 @@ fn handle_activate_weapon_events_projectile(
                         damage: proj_wpn.proj_damage,
                         lifespan_secs: proj_wpn.proj_lifespan_secs,
                         source_wpn: event.weapon_id,
+                        source_craft: wpn.boid_entt(),
                         emit_instant_secs: time.seconds_since_startup(),
                     })
                     .insert_bundle(PbrBundle {
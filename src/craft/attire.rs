@@ -3,12 +3,14 @@ use std::sync::Arc;
 use deps::*;
 
 use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
 use bevy_rapier3d::prelude::*;
 use bitflags::bitflags;
 use deps::bevy::utils::HashMap;
 use once_cell::sync::Lazy;
 
 use crate::math::*;
+use crate::mind::tribe::Factions;
 
 pub struct AttirePlugin;
 impl Plugin for AttirePlugin {
@@ -17,6 +19,7 @@ impl Plugin for AttirePlugin {
             .add_system(handle_collision_damage_events)
             .add_system(handle_projectile_xin_evenns)
             .add_system(log_damage_events)
+            .add_system(update_craft_integrity)
             .add_event::<BetterContactEvent>()
             .add_event::<CollisionDamageEvent>()
             .add_event::<ProjectileDamageEvent>();
@@ -60,7 +63,10 @@ impl Default for AttireType {
 pub struct Attire {
     pub remaining_integrity: f32,
 
-    //pub recovery_rate: Real,
+    /// Integrity regained per second through self repair.
+    pub repair_rate: f32,
+    /// Fraction of the `factory_integrity` that self repair is able to restore.
+    pub repair_limit: f32,
     pub attire_type: AttireType,
     pub factory_integrity: f32,
     pub damage_multiplier: smallvec::SmallVec<[f32; 6]>,
@@ -88,6 +94,23 @@ impl Attire {
             })
         }
     }
+
+    /// This restores integrity up to the given fraction of the `factory_integrity` and
+    /// returns any amount that went unused.
+    pub fn repair(&mut self, amount: TReal, limit: TReal) -> TReal {
+        let cap = self.factory_integrity * limit;
+        if self.remaining_integrity >= cap {
+            return amount;
+        }
+        let new_integrity = self.remaining_integrity + amount;
+        if new_integrity <= cap {
+            self.remaining_integrity = new_integrity;
+            0.
+        } else {
+            self.remaining_integrity = cap;
+            new_integrity - cap
+        }
+    }
 }
 
 /// Mostly for UX purposes.
@@ -124,6 +147,26 @@ impl AttireProfile {
         }
         remaining_damage
     }
+
+    /// Restores integrity to the members in order until the amount's used up. `limit`
+    /// is the fraction of each member's `factory_integrity` to stop at.
+    pub fn repair(&mut self, amount: TReal, limit: TReal) -> TReal {
+        let mut remaining_amount = amount;
+        for attire in self.members.iter_mut() {
+            remaining_amount = attire.repair(remaining_amount, limit);
+            if remaining_amount <= 0. {
+                break;
+            }
+        }
+        remaining_amount
+    }
+
+    #[inline]
+    pub fn is_intact(&self) -> bool {
+        self.members
+            .iter()
+            .all(|attire| attire.remaining_integrity >= attire.factory_integrity)
+    }
 }
 
 impl Default for AttireProfile {
@@ -134,12 +177,48 @@ impl Default for AttireProfile {
                 attire_type: AttireType::Hull,
                 factory_integrity: 1_000.,
                 remaining_integrity: 1_000.,
+                repair_rate: 5.,
+                repair_limit: 0.5,
                 damage_multiplier: smallvec::smallvec![1.0; 6],
             }],
         }
     }
 }
 
+/// The sum of all the attires on a craft.
+/// Craft component maintained by [`update_craft_integrity`].
+#[derive(Debug, Clone, Copy, Component, Reflect, Inspectable)]
+pub struct CraftIntegrity {
+    pub remaining: TReal,
+    pub factory: TReal,
+}
+
+impl Default for CraftIntegrity {
+    fn default() -> Self {
+        Self {
+            remaining: 1.,
+            factory: 1.,
+        }
+    }
+}
+
+impl CraftIntegrity {
+    /// The remaining integrity in fraction of the factory integrity.
+    #[inline]
+    pub fn fraction(&self) -> TReal {
+        if self.factory > TReal::EPSILON {
+            self.remaining / self.factory
+        } else {
+            0.
+        }
+    }
+
+    #[inline]
+    pub fn is_below(&self, threshold: TReal) -> bool {
+        self.fraction() < threshold
+    }
+}
+
 bitflags! {
     pub struct ColliderGroups: u32 {
         const SOLID = 1 << 1;
@@ -357,9 +436,17 @@ pub(super) fn handle_collision_damage_events(
     mut contact_events: EventReader<BetterContactEvent>,
     mut cd_events: EventWriter<CollisionDamageEvent>,
     mut generated_events: Local<Vec<CollisionDamageEvent>>,
+    factions: Factions,
 ) {
     for event in contact_events.iter() {
         let (manifold, contact) = event.contact_pair.find_deepest_contact().unwrap_or_log();
+
+        // allies bumping into each other
+        if let (Some(rb1), Some(rb2)) = (manifold.data.rigid_body1, manifold.data.rigid_body2) {
+            if !factions.allows_collision_damage(rb1.entity(), rb2.entity()) {
+                continue;
+            }
+        }
         let damage = {
             // calculate the force from the impulse
             // J = F Δt
@@ -539,9 +626,15 @@ fn handle_projectile_xin_evenns(
     mut attires: Query<(Entity, &mut AttireProfile, &ColliderParentComponent)>,
     mut proj_ixn_events: EventReader<ProjectileIxnEvent>,
     mut pd_events: EventWriter<ProjectileDamageEvent>,
+    factions: Factions,
 ) {
     for event in proj_ixn_events.iter() {
         if let Ok((attire_entt, mut attire, parent)) = attires.get_mut(event.collider.entity()) {
+            if !factions
+                .allows_projectile_damage(event.projectile.source_craft, parent.handle.entity())
+            {
+                continue;
+            }
             if attire.damage(event.projectile.damage).is_some() {
                 tracing::info!(
                     "Craft {:?} destroyed by Projectile damage",
@@ -562,6 +655,31 @@ fn handle_projectile_xin_evenns(
     }
 }
 
+/// Sums up the [`AttireProfile`]s of each craft into its [`CraftIntegrity`].
+fn update_craft_integrity(
+    mut crafts: Query<&mut CraftIntegrity>,
+    attires: Query<(&AttireProfile, &ColliderParentComponent)>,
+    mut sums: Local<HashMap<Entity, (TReal, TReal)>>,
+) {
+    for (profile, parent) in attires.iter() {
+        let sum = sums.entry(parent.handle.entity()).or_insert((0., 0.));
+        for attire in profile.members.iter() {
+            sum.0 += attire.remaining_integrity;
+            sum.1 += attire.factory_integrity;
+        }
+    }
+    for (craft_entt, (remaining, factory)) in sums.drain() {
+        if let Ok(mut integrity) = crafts.get_mut(craft_entt) {
+            // avoid triggering change detection every frame
+            if (integrity.remaining - remaining).abs() > TReal::EPSILON
+                || (integrity.factory - factory).abs() > TReal::EPSILON
+            {
+                *integrity = CraftIntegrity { remaining, factory };
+            }
+        }
+    }
+}
+
 fn log_damage_events(
     mut coll_dmg_events: EventReader<CollisionDamageEvent>,
     mut proj_dmg_events: EventReader<ProjectileDamageEvent>,
@@ -7,6 +7,8 @@ use bevy_rapier3d::prelude::*;
 use crate::craft::CraftDimensions;
 use crate::math::*;
 
+pub mod tuning;
+
 #[derive(Debug, Default, Clone, Component, Reflect, Inspectable)]
 pub struct LinearEngineState {
     /// Linear velocity in local-space
@@ -130,6 +132,130 @@ impl EngineConfig {
         let max_force = self.linear_thruster_force * self.thruster_force_multiplier;
         max_force / self.mass
     }
+
+    /// What the linear driver will actually get out of the thrusters, respecting
+    /// [`limit_acceleration`].
+    #[inline]
+    pub fn effective_lin_accel(&self) -> TVec3 {
+        let avail = self.avail_lin_accel();
+        if self.limit_acceleration {
+            avail.min(self.actual_acceleration_limit())
+        } else {
+            avail
+        }
+    }
+
+    /// Principal moments of inertia, approximating the craft as a solid box of
+    /// its dimensions.
+    /// In kg*m*m.
+    pub fn approx_principal_inertia(&self, dimensions: CraftDimensions) -> TVec3 {
+        let sq = *dimensions * *dimensions;
+        TVec3::new(sq.y + sq.z, sq.x + sq.z, sq.x + sq.y) * (self.mass / 12.)
+    }
+
+    /// Counterpart to rapier's `inv_principal_inertia_sqrt` for use before the
+    /// craft's got a rigid body.
+    pub fn approx_inv_principal_inertia_sqrt(&self, dimensions: CraftDimensions) -> TVec3 {
+        self.approx_principal_inertia(dimensions)
+            .max(TVec3::splat(TReal::EPSILON))
+            .powf(-0.5)
+    }
+
+    /// PID gains for the drivers scaled to the craft's thrust and mass properties.
+    /// See [`tuning`] to refine them further.
+    pub fn gain_profile(&self, dimensions: CraftDimensions) -> GainProfile {
+        let linear = PidGains::for_plant(
+            TVec3::ONE,
+            self.effective_lin_accel(),
+            self.linvel_limit.abs(),
+        );
+
+        // the angular driver's flames are scaled by the inverse sqrt of the inertia,
+        // see [`apply_flames_simple_accel`]
+        let inv_inertia_sqrt = self.approx_inv_principal_inertia_sqrt(dimensions);
+        let max_torque =
+            self.derive_items(dimensions).thruster_torque * self.thruster_force_multiplier;
+        let angular = PidGains::for_plant(
+            inv_inertia_sqrt,
+            max_torque * inv_inertia_sqrt * inv_inertia_sqrt,
+            self.angvel_limit.abs(),
+        );
+        GainProfile { linear, angular }
+    }
+}
+
+/// The timestep the gain profiles are designed around.
+/// In seconds.
+pub const NOMINAL_TIMESTEP: TReal = 1. / 60.;
+
+/// Portion of the velocity limit the velocity error needs to be under before the
+/// drivers come out of saturation.
+const SATURATION_ZONE: TReal = 0.02;
+
+/// Upper bound for the closed loop bandwidth as a fraction of the update rate. With
+/// the frame of delay before the flames take effect, the loop's critically damped here.
+const MAX_BANDWIDTH_RATIO: TReal = 0.25;
+
+/// Portion of the flame limit the integral term's allowed to command. It's only there
+/// for persistent disturbances and winds up when the driver saturates otherwise.
+const INTEGRAL_AUTHORITY: TReal = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidGains {
+    pub proportional: TVec3,
+    pub integral: TVec3,
+    /// Bound on the magnitude of the accumulated integral error.
+    pub integral_limit: TVec3,
+    pub derivative: TVec3,
+}
+
+impl PidGains {
+    /// Gains for a velocity loop on a plant that accelerates at `plant_gain * flame`.
+    ///
+    /// The bandwidth is picked such that the driver only saturates at velocity errors
+    /// over the [`SATURATION_ZONE`]. There's no integral term, nothing's there to
+    /// bleed velocity in space and it only adds a slow tail to the response.
+    pub fn for_plant(plant_gain: TVec3, accel_limit: TVec3, vel_limit: TVec3) -> Self {
+        let plant_gain = plant_gain.max(TVec3::splat(TReal::EPSILON));
+        let bandwidth = (accel_limit
+            / (vel_limit * SATURATION_ZONE).max(TVec3::splat(TReal::EPSILON)))
+        .min(TVec3::splat(MAX_BANDWIDTH_RATIO / NOMINAL_TIMESTEP));
+        Self {
+            proportional: bandwidth / plant_gain,
+            integral: TVec3::ZERO,
+            integral_limit: TVec3::ZERO,
+            derivative: TVec3::ZERO,
+        }
+    }
+
+    /// Sets the integral gain, limiting the accumulated error such that the term
+    /// never commands more than [`INTEGRAL_AUTHORITY`] of the `flame_limit`.
+    pub fn with_integral(mut self, integral: TVec3, flame_limit: TVec3) -> Self {
+        self.integral = integral.max(TVec3::ZERO);
+        self.integral_limit = TVec3::select(
+            self.integral.cmpgt(TVec3::splat(TReal::EPSILON)),
+            flame_limit * INTEGRAL_AUTHORITY / self.integral,
+            TVec3::ZERO,
+        );
+        self
+    }
+
+    pub fn controller(&self) -> crate::utils::PIDControllerVec3 {
+        crate::utils::PIDControllerVec3::new(
+            self.proportional,
+            self.integral,
+            self.integral_limit,
+            -self.integral_limit,
+            self.derivative,
+        )
+    }
+}
+
+/// PID gains for both drivers of a craft.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GainProfile {
+    pub linear: PidGains,
+    pub angular: PidGains,
 }
 
 #[derive(Debug, Clone, Component, Reflect, Inspectable)]
@@ -178,7 +304,7 @@ pub fn sync_craft_state_velocities(
 
 pub fn linear_pid_driver(
     mut crafts: Query<(&mut LinearEngineState, &EngineConfig, &mut LinearDriverPid)>,
-    //time: Time,
+    time: Res<Time>,
 ) {
     for (mut state, config, mut pid) in crafts.iter_mut() {
         let mut linear_input = state.input;
@@ -218,9 +344,11 @@ pub fn linear_pid_driver(
             }
         };
 
-        let linear_flame = pid
-            .0
-            .update(state.velocity, linear_input - state.velocity, 1.);
+        let linear_flame = pid.0.update(
+            state.velocity,
+            linear_input - state.velocity,
+            time.delta_seconds(),
+        );
 
         state.flame = linear_flame.clamp(-acceleration_limit, acceleration_limit);
     }
@@ -259,7 +387,10 @@ pub fn angular_pid_driver(
                 .into();
                 if config.limit_acceleration {
                     let artificial_accel_limit = derived_config.angular_acceleration_limit;
-                    pid.0.integrat_max = acceleration_limit.min(artificial_accel_limit);
+                    // bound the integral term's share of the limit
+                    pid.0.integrat_max = acceleration_limit.min(artificial_accel_limit)
+                        * INTEGRAL_AUTHORITY
+                        / pid.0.integrat_gain.max(TVec3::splat(TReal::EPSILON));
                     pid.0.integrat_min = -pid.0.integrat_max;
 
                     // clamp the actual limit to the artifical limit
@@ -0,0 +1,301 @@
+//! Offline tuning of the driver PID gains against a simulated craft class.
+//!
+//! Each axis is modeled as a velocity loop on a saturating double integrator
+//! with a frame of actuation delay, which is what the drivers see between
+//! [`super::sync_craft_state_velocities`] and the physics step. Gains are refined
+//! with a pattern search over the proportional and integral terms, starting
+//! from the [`super::GainProfile`], minimizing the time weighted absolute error
+//! with a penalty on overshoot.
+
+use deps::*;
+
+use super::{EngineConfig, GainProfile, PidGains, NOMINAL_TIMESTEP};
+use crate::craft::CraftDimensions;
+use crate::math::*;
+
+/// Per-axis model of what a driver's flames do to the craft.
+#[derive(Debug, Clone, Copy)]
+pub struct PlantModel {
+    /// Acceleration per unit of flame.
+    pub gain: TVec3,
+    /// Flames are clamped to this.
+    pub flame_limit: TVec3,
+    /// In seconds.
+    pub timestep: TReal,
+    /// Frames between the driver's output and it taking effect.
+    pub delay_steps: usize,
+}
+
+impl PlantModel {
+    pub fn linear(config: &EngineConfig) -> Self {
+        Self {
+            gain: TVec3::ONE,
+            flame_limit: config.effective_lin_accel(),
+            timestep: NOMINAL_TIMESTEP,
+            delay_steps: 1,
+        }
+    }
+
+    pub fn angular(config: &EngineConfig, dimensions: CraftDimensions) -> Self {
+        let gain = config.approx_inv_principal_inertia_sqrt(dimensions);
+        let max_torque =
+            config.derive_items(dimensions).thruster_torque * config.thruster_force_multiplier;
+        Self {
+            gain,
+            flame_limit: max_torque * gain,
+            timestep: NOMINAL_TIMESTEP,
+            delay_steps: 1,
+        }
+    }
+}
+
+/// Per-axis characteristics of a response to a velocity step from rest.
+#[derive(Debug, Clone, Copy)]
+pub struct StepResponse {
+    /// Peak overshoot as a fraction of the step.
+    pub overshoot: TVec3,
+    /// Time after which the velocity stays within [`SETTLING_BAND`] of the setpoint.
+    /// Infinite if it never does within the horizon.
+    /// In seconds.
+    pub settling_time: TVec3,
+    /// Integral of time weighted absolute error, normalized by the step.
+    pub itae: TVec3,
+}
+
+/// Fraction of the step the response has to stay within to count as settled.
+pub const SETTLING_BAND: TReal = 0.02;
+
+/// Runs the driver with the given gains against the plant for `horizon` seconds.
+pub fn step_response(
+    plant: &PlantModel,
+    gains: &PidGains,
+    setpoint: TVec3,
+    horizon: TReal,
+) -> StepResponse {
+    let mut pid = gains.controller();
+    let mut pending = std::collections::VecDeque::with_capacity(plant.delay_steps + 1);
+    pending.extend(std::iter::repeat(TVec3::ZERO).take(plant.delay_steps));
+
+    let step = setpoint.abs().max(TVec3::splat(TReal::EPSILON));
+    let mut velocity = TVec3::ZERO;
+    let mut peak = TVec3::ZERO;
+    let mut settled_at = TVec3::ZERO;
+    let mut itae = TVec3::ZERO;
+
+    let steps = (horizon / plant.timestep).ceil() as usize;
+    for ii in 0..steps {
+        let flame = pid
+            .update(velocity, setpoint - velocity, plant.timestep)
+            .clamp(-plant.flame_limit, plant.flame_limit);
+        pending.push_back(flame);
+        let flame = pending.pop_front().unwrap_or_log();
+        velocity += flame * plant.gain * plant.timestep;
+
+        let time = (ii + 1) as TReal * plant.timestep;
+        // progress along the step, 1 being the setpoint
+        let progress = velocity * setpoint.signum() / step;
+        peak = peak.max(progress);
+        let err = (TVec3::ONE - progress).abs();
+        itae += err * time * plant.timestep;
+        for axis in 0..3 {
+            if err[axis] > SETTLING_BAND {
+                settled_at[axis] = time;
+            }
+        }
+    }
+    let horizon = steps as TReal * plant.timestep;
+    let mut settling_time = settled_at;
+    for axis in 0..3 {
+        // still out of the band at the end
+        if settled_at[axis] >= horizon {
+            settling_time[axis] = TReal::INFINITY;
+        }
+    }
+    StepResponse {
+        overshoot: (peak - TVec3::ONE).max(TVec3::ZERO),
+        settling_time,
+        itae,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TuningConfig {
+    /// The sizes of the steps to tune against as fractions of the velocity limit.
+    /// Include small ones, large ones only show how the driver saturates.
+    pub step_fractions: [TReal; 3],
+    /// In seconds.
+    pub horizon: TReal,
+    /// Cost added per unit of overshoot squared.
+    pub overshoot_penalty: TReal,
+    pub max_iterations: usize,
+}
+
+impl Default for TuningConfig {
+    fn default() -> Self {
+        Self {
+            step_fractions: [0.05, 0.25, 1.],
+            horizon: 5.,
+            overshoot_penalty: 100.,
+            max_iterations: 200,
+        }
+    }
+}
+
+/// Refines `initial` for the plant using a pattern search.
+pub fn autotune(
+    plant: &PlantModel,
+    initial: PidGains,
+    vel_limit: TVec3,
+    config: &TuningConfig,
+) -> PidGains {
+    let cost = |gains: &PidGains| {
+        let mut cost = TVec3::ZERO;
+        for fraction in config.step_fractions {
+            let setpoint = vel_limit.abs() * fraction;
+            let response = step_response(plant, gains, setpoint, config.horizon);
+            cost +=
+                response.itae + response.overshoot * response.overshoot * config.overshoot_penalty;
+        }
+        cost
+    };
+    // the integral gain is searched as a multiple of the square of the
+    // loop bandwidth, it starts from zero so steps on it are additive
+    let with_params = |kp_scale: TVec3, ki_ratio: TVec3| {
+        let proportional = initial.proportional * kp_scale;
+        let bandwidth = proportional * plant.gain;
+        PidGains {
+            proportional,
+            ..initial
+        }
+        .with_integral(
+            ki_ratio * bandwidth * bandwidth / plant.gain,
+            plant.flame_limit,
+        )
+    };
+
+    // the axes don't interact so they're searched simultaneously
+    let mut kp_scale = TVec3::ONE;
+    let mut ki_ratio = initial.integral * plant.gain
+        / (initial.proportional * initial.proportional * plant.gain * plant.gain)
+            .max(TVec3::splat(TReal::EPSILON));
+    let mut best = cost(&with_params(kp_scale, ki_ratio));
+    let mut stride = TVec3::splat(0.5);
+    for _ in 0..config.max_iterations {
+        let mut improved = [false; 3];
+        for (dkp, dki) in [(1., 0.), (-1., 0.), (0., 1.), (0., -1.)] {
+            let cand_kp = kp_scale * (stride * dkp).exp();
+            let cand_ki = (ki_ratio + stride * dki * KI_RATIO_STRIDE).max(TVec3::ZERO);
+            let cand_cost = cost(&with_params(cand_kp, cand_ki));
+            for axis in 0..3 {
+                if cand_cost[axis] < best[axis] {
+                    best[axis] = cand_cost[axis];
+                    kp_scale[axis] = cand_kp[axis];
+                    ki_ratio[axis] = cand_ki[axis];
+                    improved[axis] = true;
+                }
+            }
+        }
+        for axis in 0..3 {
+            if !improved[axis] {
+                stride[axis] *= 0.5;
+            }
+        }
+        if stride.max_element() < 1e-3 {
+            break;
+        }
+    }
+    with_params(kp_scale, ki_ratio)
+}
+
+/// How far a unit stride moves the integral gain's ratio to the squared bandwidth.
+const KI_RATIO_STRIDE: TReal = 0.1;
+
+/// Tunes both drivers of a craft class.
+pub fn autotune_craft(
+    engine_config: &EngineConfig,
+    dimensions: CraftDimensions,
+    config: &TuningConfig,
+) -> GainProfile {
+    let profile = engine_config.gain_profile(dimensions);
+    GainProfile {
+        linear: autotune(
+            &PlantModel::linear(engine_config),
+            profile.linear,
+            engine_config.linvel_limit,
+            config,
+        ),
+        angular: autotune(
+            &PlantModel::angular(engine_config, dimensions),
+            profile.angular,
+            engine_config.angvel_limit,
+            config,
+        ),
+    }
+}
+
+#[cfg(test)]
+fn assert_step_spec(plant: &PlantModel, gains: &PidGains, vel_limit: TVec3) -> TReal {
+    let mut itae = 0.;
+    for fraction in TuningConfig::default().step_fractions {
+        let setpoint = vel_limit * fraction;
+        let response = step_response(plant, gains, setpoint, 10.);
+        // however long it takes at full thrust and then some
+        let saturated_secs = setpoint / (plant.flame_limit * plant.gain);
+        assert!(
+            response.overshoot.max_element() < 0.05,
+            "{fraction} {response:?}"
+        );
+        assert!(
+            response
+                .settling_time
+                .cmplt(saturated_secs + TVec3::ONE)
+                .all(),
+            "{fraction} {response:?}"
+        );
+        itae += response.itae.max_element();
+    }
+    itae
+}
+
+#[test]
+fn profile_step_response() {
+    let engine_config = EngineConfig::default();
+    let dimensions = CraftDimensions(TVec3::ONE * 8.);
+    let profile = engine_config.gain_profile(dimensions);
+    assert_step_spec(
+        &PlantModel::linear(&engine_config),
+        &profile.linear,
+        engine_config.linvel_limit,
+    );
+    assert_step_spec(
+        &PlantModel::angular(&engine_config, dimensions),
+        &profile.angular,
+        engine_config.angvel_limit,
+    );
+}
+
+#[test]
+fn autotune_improves_on_profile() {
+    let engine_config = EngineConfig::default();
+    let dimensions = CraftDimensions(TVec3::ONE * 8.);
+    let profile = engine_config.gain_profile(dimensions);
+    let tuned = autotune_craft(&engine_config, dimensions, &Default::default());
+    for (plant, initial, tuned, vel_limit) in [
+        (
+            PlantModel::linear(&engine_config),
+            profile.linear,
+            tuned.linear,
+            engine_config.linvel_limit,
+        ),
+        (
+            PlantModel::angular(&engine_config, dimensions),
+            profile.angular,
+            tuned.angular,
+            engine_config.angvel_limit,
+        ),
+    ] {
+        let before = assert_step_spec(&plant, &initial, vel_limit);
+        let after = assert_step_spec(&plant, &tuned, vel_limit);
+        assert!(after <= before, "{before} {after} {tuned:?}");
+    }
+}
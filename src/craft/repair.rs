@@ -0,0 +1,325 @@
+use deps::*;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
+
+use crate::craft::{arms::*, attire::*};
+use crate::math::*;
+use crate::mind::tribe::Factions;
+
+pub struct RepairPlugin;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, SystemLabel)]
+pub enum RepairSystems {
+    Docking,
+}
+
+impl Plugin for RepairPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(self_repair)
+            .add_system(repair_auras)
+            .add_system(station_docking.label(RepairSystems::Docking))
+            .add_system(resupply_docked_crafts.after(RepairSystems::Docking))
+            .add_event::<RepairRequestEvent>();
+    }
+}
+
+/// Attach to a craft to have it patch up the attires of nearby crafts.
+#[derive(Debug, Clone, Component, Reflect, Inspectable)]
+pub struct RepairAura {
+    /// In meters.
+    pub radius: TReal,
+    /// Integrity restored per second on every attire profile in range. Goes to the
+    /// profile's members in order, see [`AttireProfile::repair`].
+    pub rate: TReal,
+}
+
+/// Crafts that slow down within the `docking_radius` of one of these get
+/// their integrity and ammo restored.
+#[derive(Debug, Clone, Component, Reflect, Inspectable)]
+pub struct ResupplyStation {
+    /// In meters.
+    pub docking_radius: TReal,
+    /// Crafts moving faster than this relative to the station won't dock.
+    /// In m/s.
+    pub max_docking_speed: TReal,
+    /// Integrity restored per second on every attire profile of a docked craft.
+    /// Goes to the profile's members in order, see [`AttireProfile::repair`].
+    pub integrity_rate: TReal,
+    /// Rounds restored per second on every weapon of a docked craft.
+    pub ammo_rate: TReal,
+}
+
+impl Default for ResupplyStation {
+    fn default() -> Self {
+        Self {
+            docking_radius: 50.,
+            max_docking_speed: 5.,
+            integrity_rate: 100.,
+            ammo_rate: 10.,
+        }
+    }
+}
+
+/// Tags crafts currently docked at a [`ResupplyStation`].
+#[derive(Debug, Clone, Component)]
+#[component(storage = "SparseSet")]
+pub struct Docked {
+    pub station: Entity,
+    /// Fractional rounds accumulated for resupply.
+    ammo_credit: TReal,
+}
+
+/// Sent by boid strategies when their craft wants to get patched up.
+#[derive(Debug, Clone)]
+pub struct RepairRequestEvent {
+    pub boid_entt: Entity,
+}
+
+fn self_repair(time: Res<Time>, mut attires: Query<&mut AttireProfile>) {
+    let delta = time.delta_seconds();
+    for mut profile in attires.iter_mut() {
+        // avoid triggering change detection on healthy attires
+        if profile.members.iter().all(|a| {
+            a.repair_rate <= 0. || a.remaining_integrity >= a.factory_integrity * a.repair_limit
+        }) {
+            continue;
+        }
+        for attire in profile.members.iter_mut() {
+            let limit = attire.repair_limit;
+            attire.repair(attire.repair_rate * delta, limit);
+        }
+    }
+}
+
+fn repair_auras(
+    time: Res<Time>,
+    auras: Query<(Entity, &RepairAura, &GlobalTransform)>,
+    crafts: Query<&GlobalTransform, With<CollisionDamageEnabledRb>>,
+    mut attires: Query<(&mut AttireProfile, &ColliderParentComponent)>,
+    factions: Factions,
+) {
+    if auras.is_empty() {
+        return;
+    }
+    let delta = time.delta_seconds();
+    for (mut profile, parent) in attires.iter_mut() {
+        let craft_entt = parent.handle.entity();
+        let craft_xform = match crafts.get(craft_entt) {
+            Ok(xform) => xform,
+            Err(_) => continue,
+        };
+        if profile.is_intact() {
+            continue;
+        }
+        for (aura_entt, aura, aura_xform) in auras.iter() {
+            // repair crafts don't service themselves or strangers
+            if aura_entt == craft_entt || !factions.is_allied(aura_entt, craft_entt) {
+                continue;
+            }
+            if aura_xform
+                .translation
+                .distance_squared(craft_xform.translation)
+                < aura.radius * aura.radius
+            {
+                profile.repair(aura.rate * delta, 1.);
+            }
+        }
+    }
+}
+
+/// Docks and undocks crafts depending on their proximity and speed relative to stations.
+fn station_docking(
+    mut commands: Commands,
+    stations: Query<(
+        Entity,
+        &ResupplyStation,
+        &GlobalTransform,
+        Option<&RigidBodyVelocityComponent>,
+    )>,
+    crafts: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &RigidBodyVelocityComponent,
+            Option<&Docked>,
+        ),
+        With<CollisionDamageEnabledRb>,
+    >,
+) {
+    for (craft_entt, xform, vel, docked) in crafts.iter() {
+        let linvel = TVec3::from(vel.linvel);
+        let station = stations.iter().find(|(_, station, st_xform, st_vel)| {
+            let st_linvel = st_vel.map(|v| TVec3::from(v.linvel)).unwrap_or_default();
+            st_xform.translation.distance_squared(xform.translation)
+                < station.docking_radius * station.docking_radius
+                && (linvel - st_linvel).length_squared()
+                    < station.max_docking_speed * station.max_docking_speed
+        });
+        match (station, docked) {
+            (Some((station_entt, ..)), None) => {
+                tracing::info!(?craft_entt, ?station_entt, "craft docked at station");
+                commands.entity(craft_entt).insert(Docked {
+                    station: station_entt,
+                    ammo_credit: 0.,
+                });
+            }
+            (Some((station_entt, ..)), Some(docked)) if docked.station != station_entt => {
+                commands.entity(craft_entt).insert(Docked {
+                    station: station_entt,
+                    ammo_credit: 0.,
+                });
+            }
+            (None, Some(_)) => {
+                tracing::info!(?craft_entt, "craft undocked from station");
+                commands.entity(craft_entt).remove::<Docked>();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn resupply_docked_crafts(
+    time: Res<Time>,
+    stations: Query<&ResupplyStation>,
+    mut crafts: Query<&mut Docked>,
+    mut attires: Query<(&mut AttireProfile, &ColliderParentComponent)>,
+    mut weapons: Query<(&CraftWeapon, &mut Ammunition)>,
+    mut rounds: Local<HashMap<Entity, u32>>,
+) {
+    let delta = time.delta_seconds();
+    for (mut profile, parent) in attires.iter_mut() {
+        if let Ok(docked) = crafts.get(parent.handle.entity()) {
+            if profile.is_intact() {
+                continue;
+            }
+            if let Ok(station) = stations.get(docked.station) {
+                profile.repair(station.integrity_rate * delta, 1.);
+            }
+        }
+    }
+    for (wpn, mut ammo) in weapons.iter_mut() {
+        if ammo.is_full() {
+            continue;
+        }
+        if let Ok(mut docked) = crafts.get_mut(wpn.boid_entt()) {
+            let count = match rounds.get(&wpn.boid_entt()) {
+                Some(count) => *count,
+                None => {
+                    let station = match stations.get(docked.station) {
+                        Ok(station) => station,
+                        Err(_) => continue,
+                    };
+                    docked.ammo_credit += station.ammo_rate * delta;
+                    let count = docked.ammo_credit.floor();
+                    docked.ammo_credit -= count;
+                    rounds.insert(wpn.boid_entt(), count as u32);
+                    count as u32
+                }
+            };
+            ammo.resupply(count);
+        }
+    }
+    rounds.clear();
+}
+
+#[test]
+fn self_repair_stops_at_limit() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    let mut world = World::new();
+    let mut time = Time::default();
+    time.update();
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    time.update();
+    world.insert_resource(time);
+
+    let mut profile = AttireProfile::default();
+    profile.members[0].remaining_integrity = 1.;
+    profile.members[0].repair_rate = 1e9;
+    let attire = world.spawn().insert(profile).id();
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(self_repair);
+    stage.run(&mut world);
+
+    let attire = &world.get::<AttireProfile>(attire).unwrap().members[0];
+    assert_eq!(
+        attire.remaining_integrity,
+        attire.factory_integrity * attire.repair_limit
+    );
+}
+
+#[test]
+fn docking_and_resupply() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    let mut world = World::new();
+    let mut time = Time::default();
+    time.update();
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    time.update();
+    world.insert_resource(time);
+
+    // rates high enough to top off in a single frame
+    let station = world
+        .spawn()
+        .insert(ResupplyStation {
+            integrity_rate: 1e9,
+            ammo_rate: 1e9,
+            ..Default::default()
+        })
+        .insert(GlobalTransform::identity())
+        .id();
+    let craft = world
+        .spawn()
+        .insert(GlobalTransform::from_translation(TVec3::X * 10.))
+        .insert(RigidBodyVelocityComponent(Default::default()))
+        .insert(CollisionDamageEnabledRb)
+        .id();
+    // too fast to dock
+    let passing = world
+        .spawn()
+        .insert(GlobalTransform::from_translation(TVec3::X * 10.))
+        .insert(RigidBodyVelocityComponent(RigidBodyVelocity {
+            linvel: [100., 0., 0.].into(),
+            ..Default::default()
+        }))
+        .insert(CollisionDamageEnabledRb)
+        .id();
+    let mut profile = AttireProfile::default();
+    profile.members[0].remaining_integrity = 1.;
+    let attire = world
+        .spawn()
+        .insert(profile)
+        .insert(ColliderParentComponent(ColliderParent {
+            handle: craft.handle(),
+            pos_wrt_parent: Isometry::identity(),
+        }))
+        .id();
+    let weapon = world
+        .spawn()
+        .insert(CraftWeapon::new(craft, WeaponKind::of::<Docked>(), "test"))
+        .insert(Ammunition {
+            remaining: 0,
+            capacity: 30,
+        })
+        .id();
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(station_docking.label(RepairSystems::Docking));
+    stage.add_system(resupply_docked_crafts.after(RepairSystems::Docking));
+
+    stage.run(&mut world);
+    assert_eq!(world.get::<Docked>(craft).unwrap().station, station);
+    assert!(world.get::<Docked>(passing).is_none());
+
+    stage.run(&mut world);
+    assert!(world.get::<AttireProfile>(attire).unwrap().is_intact());
+    assert!(world.get::<Ammunition>(weapon).unwrap().is_full());
+
+    world.get_mut::<GlobalTransform>(craft).unwrap().translation = TVec3::X * 1_000.;
+    stage.run(&mut world);
+    assert!(world.get::<Docked>(craft).is_none());
+}
@@ -0,0 +1,187 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::craft::{arms::*, engine::*, CraftDimensions};
+use crate::math::*;
+
+pub struct SignaturePlugin;
+
+impl Plugin for SignaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_craft_signatures);
+    }
+}
+
+/// Knobs that determine how loud a craft's [`CraftSignature`] gets.
+#[derive(Debug, Clone, Component, Reflect, Inspectable)]
+pub struct SignatureProfile {
+    /// Scales the mean cross-sectional area of the craft.
+    pub rcs_multiplier: TReal,
+    /// Heat given off when the linear engine's idle.
+    pub idle_heat: TReal,
+    /// Heat given off when the linear engine's at its acceleration limit.
+    pub full_thrust_heat: TReal,
+    /// EM emitted by each weapon right after firing.
+    pub em_per_weapon: TReal,
+    /// Time constant for the weapon EM falloff. In seconds.
+    pub em_decay_secs: TReal,
+}
+
+impl Default for SignatureProfile {
+    fn default() -> Self {
+        Self {
+            rcs_multiplier: 1.,
+            idle_heat: 5.,
+            full_thrust_heat: 100.,
+            em_per_weapon: 50.,
+            em_decay_secs: 1.,
+        }
+    }
+}
+
+/// How detectable a craft currently is.
+/// Craft component.
+#[derive(Debug, Clone, Default, Component, Reflect, Inspectable)]
+pub struct CraftSignature {
+    /// Radar cross-section.
+    pub rcs: TReal,
+    pub heat: TReal,
+    pub em: TReal,
+}
+
+impl CraftSignature {
+    #[inline]
+    pub fn total(&self) -> TReal {
+        self.rcs + self.heat + self.em
+    }
+
+    /// The total as seen from `local_dir`, in the craft's basis. The radar
+    /// cross-section's scaled by how much of the craft's showing.
+    pub fn total_from(&self, dim: &CraftDimensions, local_dir: TVec3) -> TReal {
+        let mean = mean_cross_section(dim);
+        let rcs = if mean > TReal::EPSILON {
+            self.rcs * (cross_section(dim, local_dir) / mean)
+        } else {
+            self.rcs
+        };
+        rcs + self.heat + self.em
+    }
+}
+
+/// The area of the craft's bounding box projected along `local_dir`.
+#[inline]
+pub fn cross_section(dim: &CraftDimensions, local_dir: TVec3) -> TReal {
+    let dir = local_dir.normalize_or_zero().abs();
+    (dir.x * dim.y * dim.z) + (dir.y * dim.x * dim.z) + (dir.z * dim.x * dim.y)
+}
+
+/// The [`cross_section`] averaged over the three axes.
+#[inline]
+pub fn mean_cross_section(dim: &CraftDimensions) -> TReal {
+    ((dim.x * dim.y) + (dim.y * dim.z) + (dim.x * dim.z)) / 3.
+}
+
+fn update_craft_signatures(
+    time: Res<Time>,
+    mut crafts: Query<(
+        &mut CraftSignature,
+        &SignatureProfile,
+        &CraftDimensions,
+        &LinearEngineState,
+        &EngineConfig,
+    )>,
+    weapons: Query<(&CraftWeapon, &WeaponActivationState)>,
+) {
+    let now = time.seconds_since_startup();
+    for (mut signature, profile, dim, lin_state, config) in crafts.iter_mut() {
+        signature.rcs = profile.rcs_multiplier * mean_cross_section(dim);
+
+        let accel_limit = config.actual_acceleration_limit().length();
+        let throttle = if accel_limit > TReal::EPSILON {
+            (lin_state.flame.length() / accel_limit).min(1.)
+        } else {
+            0.
+        };
+        signature.heat =
+            profile.idle_heat + (profile.full_thrust_heat - profile.idle_heat) * throttle;
+
+        signature.em = 0.;
+    }
+    for (wpn, activation_state) in weapons.iter() {
+        if let Ok((mut signature, profile, ..)) = crafts.get_mut(wpn.boid_entt()) {
+            match activation_state {
+                WeaponActivationState::Discrete {
+                    last_firing_time, ..
+                } => {
+                    // never fired
+                    if *last_firing_time <= 0. {
+                        continue;
+                    }
+                    let since = (now - last_firing_time) as TReal;
+                    signature.em += profile.em_per_weapon * (-since / profile.em_decay_secs).exp();
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn signature_aspect_throttle_and_distance() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use crate::mind::sensors::radar::{detection_probability, Radar};
+
+    // long and narrow, smallest nose on
+    let dim = CraftDimensions(TVec3::new(2., 2., 10.));
+    let signature = CraftSignature {
+        rcs: mean_cross_section(&dim),
+        ..Default::default()
+    };
+    let nose_on = signature.total_from(&dim, -TVec3::Z);
+    let broadside = signature.total_from(&dim, TVec3::X);
+    let quartering = signature.total_from(&dim, TVec3::new(1., 0., -1.));
+    assert!(nose_on < signature.total() && signature.total() < broadside);
+    assert!(nose_on < quartering, "{nose_on} {quartering}");
+
+    // cutting thrust cools it down
+    let mut world = World::new();
+    world.insert_resource(Time::default());
+    let config = EngineConfig::default();
+    let mut spawn_craft = |throttle: TReal| {
+        world
+            .spawn()
+            .insert(CraftSignature::default())
+            .insert(SignatureProfile::default())
+            .insert(dim)
+            .insert(LinearEngineState {
+                flame: TVec3::X * config.actual_acceleration_limit().length() * throttle,
+                ..Default::default()
+            })
+            .insert(config.clone())
+            .id()
+    };
+    let (cold, warm, hot) = (spawn_craft(0.), spawn_craft(0.5), spawn_craft(1.));
+    SystemStage::single_threaded()
+        .with_system(update_craft_signatures)
+        .run(&mut world);
+    let profile = SignatureProfile::default();
+    let heat = |entt| world.get::<CraftSignature>(entt).unwrap().heat;
+    assert_eq!(heat(cold), profile.idle_heat);
+    assert_eq!(heat(hot), profile.full_thrust_heat);
+    assert!((heat(warm) - (profile.idle_heat + profile.full_thrust_heat) * 0.5).abs() < 1e-3);
+
+    // and the same signature's harder to pick up further out
+    let radar = Radar::default();
+    let total = world.get::<CraftSignature>(hot).unwrap().total();
+    let near = detection_probability(&radar, total, radar.range * 0.5);
+    let far = detection_probability(&radar, total, radar.range * 2.);
+    assert!(near > far);
+    let cold_far = detection_probability(
+        &radar,
+        world.get::<CraftSignature>(cold).unwrap().total(),
+        radar.range * 2.,
+    );
+    assert!(cold_far < far);
+}
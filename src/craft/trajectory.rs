@@ -0,0 +1,396 @@
+//! Forward simulation of a craft's point-mass model for predicting where it'll
+//! be under a given control policy.
+//!
+//! All vectors are in world space unless otherwise remarked to be so.
+
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_prototype_debug_lines::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::craft::engine::*;
+use crate::math::*;
+
+pub struct TrajectoryPlugin;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, SystemLabel)]
+pub enum TrajectorySystems {
+    UpdatePredictions,
+}
+
+impl Plugin for TrajectoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_predictions.label(TrajectorySystems::UpdatePredictions))
+            .add_system(draw_predictions.after(TrajectorySystems::UpdatePredictions));
+    }
+}
+
+/// The craft reduced to a point mass along with the limits its engine enforces.
+/// Rotation's assumed to stay fixed through out the simulation.
+#[derive(Debug, Clone, Copy)]
+pub struct PointMassModel {
+    /// In the local basis.
+    pub accel_limit: TVec3,
+    /// In the local basis.
+    pub linvel_limit: TVec3,
+    pub limit_linvel: bool,
+    pub rotation: TQuat,
+}
+
+impl PointMassModel {
+    pub fn new(config: &EngineConfig, rotation: TQuat) -> Self {
+        Self {
+            accel_limit: config.effective_lin_accel(),
+            linvel_limit: config.linvel_limit,
+            limit_linvel: config.limit_strafe_v,
+            rotation,
+        }
+    }
+
+    /// Advances the state by `dt` seconds having the engine chase `desired_linvel`.
+    #[inline]
+    pub fn step(&self, state: &mut PointMassState, desired_linvel: TVec3, dt: TReal) {
+        let inv_rot = self.rotation.inverse();
+        let mut desired = inv_rot * desired_linvel;
+        if self.limit_linvel {
+            desired = desired.clamp(-self.linvel_limit, self.linvel_limit);
+        }
+        let linvel = inv_rot * state.linvel;
+        let accel = ((desired - linvel) / dt).clamp(-self.accel_limit, self.accel_limit);
+        state.linvel = self.rotation * (linvel + (accel * dt));
+        state.pos += state.linvel * dt;
+        state.secs += dt;
+    }
+}
+
+/// The rotational counterpart of [`PointMassModel::step`] for tests. Has the
+/// local `angvel` chase `desired_angvel` under `angaccel_limit`.
+#[cfg(test)]
+pub fn step_rotation(
+    rotation: &mut TQuat,
+    angvel: &mut TVec3,
+    desired_angvel: TVec3,
+    angaccel_limit: TVec3,
+    dt: TReal,
+) {
+    *angvel += ((desired_angvel - *angvel) / dt).clamp(-angaccel_limit, angaccel_limit) * dt;
+    *rotation = (*rotation * TQuat::from_scaled_axis(*angvel * dt)).normalize();
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PointMassState {
+    /// Since the start of the prediction.
+    pub secs: TReal,
+    pub pos: TVec3,
+    pub linvel: TVec3,
+}
+
+/// What the simulated craft's engine will be told to do.
+#[derive(Debug, Clone, Copy)]
+pub enum ControlPolicy {
+    /// No thrust.
+    Coast,
+    /// Whatever the craft's engine is currently set to.
+    /// Only meaningful on [`PredictedTrajectory`]s where it's resolved to
+    /// [`ControlPolicy::HoldLinvel`] every refresh.
+    EngineInput,
+    HoldLinvel {
+        linvel: TVec3,
+    },
+    Seek {
+        pos: TVec3,
+        speed: TReal,
+    },
+    /// See [`arrive_with_linvel`].
+    ArriveWithLinvel {
+        at_pos: TVec3,
+        pos_linvel: TVec3,
+        with_linvel: TVec3,
+    },
+}
+
+impl ControlPolicy {
+    /// The linear velocity the engine would be told to attain at the given state.
+    pub fn desired_linvel(&self, model: &PointMassModel, state: &PointMassState) -> TVec3 {
+        match *self {
+            ControlPolicy::Coast | ControlPolicy::EngineInput => state.linvel,
+            ControlPolicy::HoldLinvel { linvel } => linvel,
+            ControlPolicy::Seek { pos, speed } => (pos - state.pos).normalize_or_zero() * speed,
+            ControlPolicy::ArriveWithLinvel {
+                at_pos,
+                pos_linvel,
+                with_linvel,
+            } => arrive_with_linvel(
+                state.pos,
+                model.rotation,
+                at_pos + (pos_linvel * state.secs),
+                pos_linvel,
+                with_linvel,
+                model.accel_limit,
+                model.linvel_limit,
+            ),
+        }
+    }
+}
+
+/// Samples of a predicted path, evenly spaced in time.
+#[derive(Debug, Clone, Default)]
+pub struct Trajectory {
+    /// In seconds.
+    pub timestep: TReal,
+    /// The first sample is the starting state.
+    pub samples: Vec<PointMassState>,
+}
+
+impl Trajectory {
+    #[inline]
+    pub fn horizon_secs(&self) -> TReal {
+        self.samples.last().map(|s| s.secs).unwrap_or_default()
+    }
+
+    /// Linearly interpolated state at `secs` since the start. Clamped to the
+    /// simulated range.
+    pub fn sample_at(&self, secs: TReal) -> Option<PointMassState> {
+        let first = self.samples.first()?;
+        if self.samples.len() == 1 || secs <= 0. {
+            return Some(*first);
+        }
+        let idx = secs / self.timestep;
+        let ii = idx.floor() as usize;
+        if ii + 1 >= self.samples.len() {
+            return self.samples.last().copied();
+        }
+        let (a, b) = (self.samples[ii], self.samples[ii + 1]);
+        let weight = idx.fract();
+        Some(PointMassState {
+            secs,
+            pos: a.pos.lerp(b.pos, weight),
+            linvel: a.linvel.lerp(b.linvel, weight),
+        })
+    }
+
+    /// Time and distance of the closest approach to the other trajectory,
+    /// comparing samples at the same times. Assumes both started at the same time.
+    pub fn closest_approach(&self, other: &Trajectory) -> Option<(TReal, TReal)> {
+        let horizon = self.horizon_secs().min(other.horizon_secs());
+        let mut closest: Option<(TReal, TReal)> = None;
+        for sample in self.samples.iter().take_while(|s| s.secs <= horizon) {
+            let other_pos = other.sample_at(sample.secs)?.pos;
+            let dst = sample.pos.distance_squared(other_pos);
+            if closest.map(|(_, closest)| dst < closest).unwrap_or(true) {
+                closest = Some((sample.secs, dst));
+            }
+        }
+        closest.map(|(secs, dst)| (secs, dst.sqrt()))
+    }
+
+    /// Time and distance of the closest approach to a point moving at
+    /// `pos_linvel`.
+    pub fn closest_approach_to(&self, pos: TVec3, pos_linvel: TVec3) -> Option<(TReal, TReal)> {
+        self.samples
+            .iter()
+            .map(|s| (s.secs, s.pos.distance_squared(pos + (pos_linvel * s.secs))))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(secs, dst)| (secs, dst.sqrt()))
+    }
+
+    /// The first time the path gets within `tolerance` of a point moving at
+    /// `pos_linvel`.
+    pub fn time_to_reach(&self, pos: TVec3, pos_linvel: TVec3, tolerance: TReal) -> Option<TReal> {
+        self.samples
+            .iter()
+            .find(|s| s.pos.distance_squared(pos + (pos_linvel * s.secs)) <= tolerance * tolerance)
+            .map(|s| s.secs)
+    }
+}
+
+/// Forward simulates the model from `start` for `horizon_secs`.
+pub fn predict(
+    model: &PointMassModel,
+    start: PointMassState,
+    policy: &ControlPolicy,
+    horizon_secs: TReal,
+    timestep: TReal,
+) -> Trajectory {
+    predict_with(
+        model,
+        start,
+        |model, state| policy.desired_linvel(model, state),
+        horizon_secs,
+        timestep,
+    )
+}
+
+/// Like [`predict`] but with the policy given as a closure returning the
+/// desired linear velocity.
+pub fn predict_with(
+    model: &PointMassModel,
+    start: PointMassState,
+    mut policy: impl FnMut(&PointMassModel, &PointMassState) -> TVec3,
+    horizon_secs: TReal,
+    timestep: TReal,
+) -> Trajectory {
+    // nothing to step through
+    if timestep <= TReal::EPSILON || !horizon_secs.is_finite() {
+        return Trajectory {
+            timestep,
+            samples: vec![start],
+        };
+    }
+    let steps = (horizon_secs / timestep).ceil().max(0.) as usize;
+    let mut samples = Vec::with_capacity(steps + 1);
+    let mut state = start;
+    samples.push(state);
+    for _ in 0..steps {
+        let desired = policy(model, &state);
+        model.step(&mut state, desired, timestep);
+        samples.push(state);
+    }
+    Trajectory { timestep, samples }
+}
+
+/// Caches the latest prediction of where the craft's headed.
+/// Craft component.
+#[derive(Debug, Clone, Component)]
+pub struct PredictedTrajectory {
+    pub policy: ControlPolicy,
+    /// In seconds.
+    pub horizon_secs: TReal,
+    /// In seconds.
+    pub timestep: TReal,
+    /// How often to redo the prediction. In seconds.
+    pub refresh_interval_secs: f64,
+    /// Draw the path with debug lines.
+    pub draw: bool,
+    pub last_refresh_secs: Option<f64>,
+    pub trajectory: Trajectory,
+}
+
+impl Default for PredictedTrajectory {
+    fn default() -> Self {
+        Self {
+            policy: ControlPolicy::EngineInput,
+            horizon_secs: 5.,
+            timestep: 0.1,
+            refresh_interval_secs: 0.25,
+            draw: false,
+            last_refresh_secs: None,
+            trajectory: Default::default(),
+        }
+    }
+}
+
+pub fn update_predictions(
+    mut crafts: Query<(
+        &mut PredictedTrajectory,
+        &GlobalTransform,
+        &RigidBodyVelocityComponent,
+        &EngineConfig,
+        &LinearEngineState,
+    )>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for (mut prediction, xform, vel, config, lin_state) in crafts.iter_mut() {
+        if let Some(last) = prediction.last_refresh_secs {
+            if now - last < prediction.refresh_interval_secs {
+                continue;
+            }
+        }
+        let model = PointMassModel::new(config, xform.rotation);
+        let policy = match prediction.policy {
+            ControlPolicy::EngineInput => ControlPolicy::HoldLinvel {
+                linvel: xform.rotation * lin_state.input,
+            },
+            policy => policy,
+        };
+        let start = PointMassState {
+            secs: 0.,
+            pos: xform.translation,
+            linvel: vel.linvel.into(),
+        };
+        prediction.trajectory = predict(
+            &model,
+            start,
+            &policy,
+            prediction.horizon_secs,
+            prediction.timestep,
+        );
+        prediction.last_refresh_secs = Some(now);
+    }
+}
+
+pub fn draw_predictions(crafts: Query<&PredictedTrajectory>, mut lines: ResMut<DebugLines>) {
+    for prediction in crafts.iter() {
+        if !prediction.draw {
+            continue;
+        }
+        for pair in prediction.trajectory.samples.windows(2) {
+            lines.line_colored(pair[0].pos, pair[1].pos, 0., Color::YELLOW);
+        }
+    }
+}
+
+#[test]
+fn point_mass_prediction() {
+    let model = PointMassModel {
+        accel_limit: TVec3::ONE * 10.,
+        linvel_limit: TVec3::ONE * 100.,
+        limit_linvel: true,
+        rotation: TQuat::IDENTITY,
+    };
+    let start = PointMassState {
+        linvel: TVec3::X * 10.,
+        ..Default::default()
+    };
+
+    // coasting keeps to a straight line
+    let coast = predict(&model, start, &ControlPolicy::Coast, 2., 0.1);
+    assert_eq!(coast.samples.len(), 21);
+    let end = coast.sample_at(2.).unwrap();
+    assert!((end.pos - (TVec3::X * 20.)).length() < 1e-3, "{end:?}");
+
+    // accelerates at the limit then holds the velocity
+    let hold = predict(
+        &model,
+        start,
+        &ControlPolicy::HoldLinvel {
+            linvel: TVec3::X * 30.,
+        },
+        4.,
+        0.01,
+    );
+    let end = hold.sample_at(4.).unwrap();
+    assert!((end.linvel - (TVec3::X * 30.)).length() < 1e-3, "{end:?}");
+    // 2s ramping up covering 40m then 2s at 30m/s
+    assert!((end.pos.x - 100.).abs() < 0.5, "{end:?}");
+
+    // head on, meeting at the 2s mark
+    let oncoming = predict(
+        &model,
+        PointMassState {
+            pos: TVec3::X * 40.,
+            linvel: -TVec3::X * 10.,
+            ..Default::default()
+        },
+        &ControlPolicy::Coast,
+        2.,
+        0.1,
+    );
+    let (secs, dst) = coast.closest_approach(&oncoming).unwrap();
+    assert!((secs - 2.).abs() < 0.11 && dst < 1., "{secs} {dst}");
+    let (secs, dst) = coast
+        .closest_approach_to(TVec3::X * 40., -TVec3::X * 10.)
+        .unwrap();
+    assert!((secs - 2.).abs() < 0.11 && dst < 1., "{secs} {dst}");
+    assert!(coast
+        .time_to_reach(TVec3::X * 15., TVec3::ZERO, 0.5)
+        .is_some());
+
+    // degenerate timesteps only get the start
+    for timestep in [0., -0.1] {
+        let none = predict(&model, start, &ControlPolicy::Coast, 2., timestep);
+        assert_eq!(none.samples, vec![start]);
+    }
+}
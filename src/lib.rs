@@ -0,0 +1,10 @@
+#![allow(
+    clippy::too_many_arguments,
+    clippy::type_complexity,
+    clippy::single_component_path_imports
+)]
+
+pub mod craft;
+pub mod math;
+pub mod mind;
+pub mod utils;
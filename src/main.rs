@@ -18,10 +18,7 @@ use rand::prelude::*;
 
 use math::{TReal, TVec3, *};
 
-pub mod craft;
-pub mod math;
-pub mod mind;
-pub mod utils;
+use isis::{craft, math, mind};
 
 // pub struct ConsoleLog {}
 // impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for ConsoleLog {}
@@ -257,8 +254,16 @@ fn setup_world(
     mut materials: ResMut<Assets<StandardMaterial>>,
     asset_server: Res<AssetServer>,
     mut cur_craft: ResMut<mind::player::CurrentCraft>,
+    mut faction_relations: ResMut<mind::tribe::FactionRelations>,
 ) {
     let mut rng = rand::thread_rng();
+    let player_faction = mind::tribe::Faction(0);
+    let ai_faction = mind::tribe::Faction(1);
+    faction_relations.set(
+        player_faction,
+        ai_faction,
+        mind::tribe::Relationship::Hostile,
+    );
     // setup the random floating spheres
     {
         const SIZE_RANGE: TReal = 100.;
@@ -410,6 +415,21 @@ fn setup_world(
             .insert_bundle(bevy_mod_picking::PickableBundle::default());
     }
 
+    // spawn the resupply station
+    commands
+        .spawn()
+        .insert(Name::new("resupply_station"))
+        .insert_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: 20.,
+                ..Default::default()
+            })),
+            material: materials.add(Color::TEAL.into()),
+            transform: Transform::from_translation([-600., 0., 0.].into()),
+            ..Default::default()
+        })
+        .insert(craft::repair::ResupplyStation::default());
+
     // setup the test circuit
     let _initial_point = {
         let material = materials.add(Color::PINK.into());
@@ -529,6 +549,7 @@ fn setup_world(
             }).insert_bundle(boid::BoidMindBundle{
                 ..Default::default()
             })
+            .insert(player_faction)
             .with_children(|parent| {
                 let parent_entt = parent.parent_entity();
                 // the model
@@ -620,11 +641,12 @@ fn setup_world(
                 directive: boid::BoidMindDirective::AttackPresue {
                     param: boid::strategy::attack_persue::AttackPersue {
                         attacking_range: 300.,
-                        quarry_rb: _player_craft_id.handle()
+                        quarry: _player_craft_id
                     }
                 },
                 ..Default::default()
             })
+            .insert(ai_faction)
             .with_children(|parent| {
                 let parent_entt = parent.parent_entity();
                 parent
@@ -664,6 +686,13 @@ fn setup_world(
                         ..Default::default()
                     });
             }).id());
+        // the outermost ones pick their own strategies
+        if ii.abs() == 7 {
+            commands
+                .entity(*members.last().unwrap_or_log())
+                .insert(boid::utility::UtilityMind::default())
+                .insert(boid::utility::thinker());
+        }
     }
 
     /* let flock_entt = commands.spawn().insert(Name::new("flock")).id();
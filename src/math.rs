@@ -173,6 +173,91 @@ impl QuatExt for TQuat {
     }
 }
 
+/// Linear velocity, in world space, that'll have the craft arrive at a target
+/// moving at `pos_linvel` while matching `with_linvel` on arrival.
+///
+/// Treats each local axis as a double integrator limited by `accel_limit`.
+/// Every axis gets the constant acceleration that'll have it arrive at the
+/// terminal velocity at the same time, the earliest one that's within all of
+/// their limits. With a zero terminal velocity, the limiting axis follows the
+/// time-optimal switching curve `v = sqrt(2 a |e|)`. There's a linear zone
+/// near the target to avoid chattering.
+/// `accel_limit` and `linvel_limit` are in the local basis.
+#[inline]
+#[allow(clippy::too_many_arguments)]
+pub fn arrive_with_linvel(
+    current_pos: TVec3,
+    rotation: TQuat,
+    at_pos: TVec3,
+    pos_linvel: TVec3,
+    with_linvel: TVec3,
+    accel_limit: TVec3,
+    linvel_limit: TVec3,
+) -> TVec3 {
+    // leave some accel for the lag of the engine and the timestep
+    const ACCEL_MARGIN: TReal = 0.8;
+    // in m/s per meter off, the slope of the final approach when holding still
+    // relative to the target
+    const LINEAR_GAIN: TReal = 2.;
+    // in meters, how far out the final approach turns linear at the terminal velocity
+    const ARRIVAL_SLACK: TReal = 1.;
+
+    let inv_rot = rotation.inverse();
+    let accel_limit = accel_limit * ACCEL_MARGIN;
+    // everything relative to the moving target
+    let err = inv_rot * (current_pos - at_pos);
+    let rel_with_linvel = inv_rot * (with_linvel - pos_linvel);
+
+    // an axis leaving the feasible range can push the others into theirs so
+    // go around a few times
+    let mut secs: TReal = 0.;
+    for _ in 0..4 {
+        for ii in 0..3 {
+            secs = next_feasible_arrival(secs, err[ii], rel_with_linvel[ii], accel_limit[ii]);
+        }
+    }
+    // how far off we'd be by then coasting at the terminal velocity
+    let miss = err + (rel_with_linvel * secs);
+    let min_secs =
+        (2. / LINEAR_GAIN).min(ARRIVAL_SLACK / rel_with_linvel.length().max(TReal::EPSILON));
+    let desired = rel_with_linvel - (miss * 2. / secs.max(min_secs));
+
+    let desired = (inv_rot * pos_linvel) + desired;
+    rotation * desired.clamp(-linvel_limit, linvel_limit)
+}
+
+/// The earliest time, from `secs` on, in which a constant acceleration within
+/// `accel` can take a double integrator `err` off the target to it while
+/// matching `linvel`, all relative to the target.
+fn next_feasible_arrival(secs: TReal, err: TReal, linvel: TReal, accel: TReal) -> TReal {
+    // the acceleration needed, `2 (err + linvel t) / t²`, is out of bounds
+    // between the roots of `accel t² / 2 ∓ (linvel t + err)`
+    let gaps = [
+        (linvel, (linvel * linvel) + (2. * accel * err)),
+        (-linvel, (linvel * linvel) - (2. * accel * err)),
+    ];
+    if accel <= TReal::EPSILON {
+        return secs;
+    }
+    let mut secs = secs;
+    // the gaps might overlap
+    for _ in 0..2 {
+        for (half_b, disc) in gaps {
+            if disc <= 0. {
+                continue;
+            }
+            let (lo, hi) = (
+                (half_b - disc.sqrt()) / accel,
+                (half_b + disc.sqrt()) / accel,
+            );
+            if secs > lo && secs < hi {
+                secs = hi;
+            }
+        }
+    }
+    secs
+}
+
 #[test]
 fn parent_xform_calc() {
     let parent_xform = Transform::from_translation([1., 2., 3.].into())
@@ -216,3 +301,112 @@ fn inverse_xform() {
     assert!((xform.rotation - inv_inv_xform.rotation).length_squared() < f32::EPSILON);
     assert!((xform.scale - inv_inv_xform.scale).length_squared() < f32::EPSILON);
 }
+
+#[test]
+fn arrive_with_linvel_point_mass() {
+    /// Simulates a point mass whose engine tracks the desired velocity under
+    /// the acceleration limit. Returns final position error, velocity error,
+    /// the farthest it went past the target along the initial offset and the
+    /// position and velocity errors at the closest approach.
+    fn simulate(
+        start_pos: TVec3,
+        start_linvel: TVec3,
+        at_pos: TVec3,
+        pos_linvel: TVec3,
+        with_linvel: TVec3,
+        rotation: TQuat,
+    ) -> (TReal, TReal, TReal, (TReal, TReal)) {
+        use crate::craft::trajectory::{PointMassModel, PointMassState};
+
+        let model = PointMassModel {
+            accel_limit: TVec3::new(10., 10., 20.),
+            linvel_limit: TVec3::ONE * 100.,
+            limit_linvel: false,
+            rotation,
+        };
+        let dt = 1. / 60.;
+        let mut state = PointMassState {
+            pos: start_pos,
+            linvel: start_linvel,
+            ..Default::default()
+        };
+        let mut at_pos = at_pos;
+        let approach_dir = (at_pos - start_pos).normalize();
+        let mut overshoot: TReal = 0.;
+        let mut closest = (TReal::INFINITY, TReal::INFINITY);
+        for _ in 0..(30 * 60) {
+            let desired = arrive_with_linvel(
+                state.pos,
+                rotation,
+                at_pos,
+                pos_linvel,
+                with_linvel,
+                model.accel_limit,
+                model.linvel_limit,
+            );
+            model.step(&mut state, desired, dt);
+            at_pos += pos_linvel * dt;
+            let (pos, linvel) = (state.pos, state.linvel);
+            overshoot = overshoot.max((pos - at_pos).dot(approach_dir));
+            if (pos - at_pos).length() < closest.0 {
+                closest = ((pos - at_pos).length(), (linvel - with_linvel).length());
+            }
+        }
+        (
+            (state.pos - at_pos).length(),
+            (state.linvel - with_linvel).length(),
+            overshoot,
+            closest,
+        )
+    }
+
+    // static target
+    let (pos_err, vel_err, overshoot, _) = simulate(
+        TVec3::ZERO,
+        TVec3::ZERO,
+        TVec3::new(300., -200., 500.),
+        TVec3::ZERO,
+        TVec3::ZERO,
+        TQuat::IDENTITY,
+    );
+    assert!(pos_err < 0.1, "pos_err: {pos_err}");
+    assert!(vel_err < 0.1, "vel_err: {vel_err}");
+    assert!(overshoot < 0.5, "overshoot: {overshoot}");
+
+    // moving target from an initial velocity away from it, rotated craft
+    let target_linvel = TVec3::new(10., 0., -20.);
+    let (pos_err, vel_err, overshoot, _) = simulate(
+        TVec3::ZERO,
+        TVec3::new(-30., 0., 0.),
+        TVec3::new(100., 50., -200.),
+        target_linvel,
+        target_linvel,
+        TQuat::from_rotation_y(real::consts::FRAC_PI_4),
+    );
+    assert!(pos_err < 0.1, "pos_err: {pos_err}");
+    assert!(vel_err < 0.1, "vel_err: {vel_err}");
+    assert!(overshoot < 0.5, "overshoot: {overshoot}");
+
+    // arriving with a different velocity than the target's, there's no staying
+    // on it after so check the closest approach
+    for (start_linvel, target_linvel, rotation) in [
+        (
+            TVec3::new(-30., 0., 0.),
+            target_linvel,
+            TQuat::from_rotation_y(real::consts::FRAC_PI_4),
+        ),
+        (TVec3::ZERO, TVec3::ZERO, TQuat::IDENTITY),
+    ] {
+        let with_linvel = target_linvel + TVec3::new(15., -10., 5.);
+        let (_, _, _, (pos_err, vel_err)) = simulate(
+            TVec3::ZERO,
+            start_linvel,
+            TVec3::new(100., 50., -200.),
+            target_linvel,
+            with_linvel,
+            rotation,
+        );
+        assert!(pos_err < 0.5, "pos_err: {pos_err}");
+        assert!(vel_err < 0.5, "vel_err: {vel_err}");
+    }
+}
@@ -4,13 +4,15 @@ use bevy::prelude::*;
 use bevy_inspector_egui::RegisterInspectable;
 
 pub mod boid;
+pub mod bt;
 pub mod flock;
 pub mod guy;
+pub mod nav;
 pub mod player;
 pub mod sensors;
+pub mod tribe;
 /*
-pub mod master {}
-pub mod tribe {} */
+pub mod master {} */
 
 pub struct MindPlugin;
 
@@ -27,12 +29,25 @@ pub enum CraftMindSystems {
     ComposeButler,
     FlockChangeListener,
     FormationUpdate,
+    Sensors,
+    SpatialIndex,
 }
 
 impl Plugin for MindPlugin {
     fn build(&self, app: &mut App) {
         use CraftMindSystems::*;
-        app.init_resource::<sensors::CraftWeaponCrossRefIndex>()
+        app.add_plugin(big_brain::BigBrainPlugin)
+            .init_resource::<tribe::FactionRelations>()
+            .init_resource::<sensors::spatial::CraftSpatialIndex>()
+            .init_resource::<sensors::radar::RadarRng>()
+            .add_system(
+                sensors::spatial::rebuild
+                    .label(SpatialIndex)
+                    .before(Sensors),
+            )
+            .init_resource::<nav::NavGrid>()
+            .add_system(nav::rebuild.before(SteeringRoutine))
+            .init_resource::<sensors::CraftWeaponCrossRefIndex>()
             .add_system_to_stage(
                 CoreStage::PreUpdate,
                 sensors::craft_wpn_index_butler.before(BoidStrategyButler),
@@ -47,6 +62,13 @@ impl Plugin for MindPlugin {
                 CoreStage::PreUpdate,
                 sensors::craft_routine_index_butler.after(ComposeButler),
             )
+            .add_system(sensors::radar::sweep.label(Sensors).before(BoidStrategy))
+            .add_system(
+                flock::blackboard::fuse_contacts
+                    .after(Sensors)
+                    .before(BoidStrategy)
+                    .before(FlockStrategy),
+            )
             // flock formation systems
             .add_system_to_stage(
                 CoreStage::PreUpdate,
@@ -66,7 +88,8 @@ impl Plugin for MindPlugin {
                     .label(FlockStrategyButler)
                     .after(FlockChangeListener)
                     .with_system(flock::strategy::form_up::butler)
-                    .with_system(flock::strategy::cas::butler),
+                    .with_system(flock::strategy::cas::butler)
+                    .with_system(flock::strategy::withdraw::butler),
             )
             .add_system_set(
                 SystemSet::new()
@@ -80,15 +103,27 @@ impl Plugin for MindPlugin {
                     .label(BoidStrategyButler)
                     .after(FlockChangeListener)
                     .with_system(boid::strategy::attack_persue::butler)
+                    .with_system(boid::strategy::engage::butler)
+                    .with_system(boid::strategy::escort::butler)
+                    .with_system(boid::strategy::dogfight::butler)
+                    .with_system(boid::strategy::evade::butler)
                     .with_system(boid::strategy::run_circuit::butler)
                     .with_system(boid::strategy::form::butler)
+                    .with_system(boid::strategy::patrol_volume::butler)
+                    .with_system(boid::strategy::retreat::butler)
                     .with_system(boid::strategy::custom::butler),
             )
             .add_system_set(
                 SystemSet::new()
                     .label(BoidStrategy)
                     .with_system(boid::strategy::attack_persue::update)
+                    .with_system(boid::strategy::engage::update)
+                    .with_system(boid::strategy::escort::update)
+                    .with_system(boid::strategy::dogfight::update)
+                    .with_system(boid::strategy::evade::update)
                     .with_system(boid::strategy::form::update)
+                    .with_system(boid::strategy::patrol_volume::update)
+                    .with_system(boid::strategy::retreat::update)
                     .with_system(boid::strategy::run_circuit::update),
             )
             .add_system(
@@ -97,6 +132,10 @@ impl Plugin for MindPlugin {
                     .after(BoidStrategy),
             )
             // boid steering systems
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                boid::steering::routine_garbage_collector,
+            )
             .add_system_to_stage(
                 CoreStage::PreUpdate,
                 boid::steering::compose::butler
@@ -112,14 +151,41 @@ impl Plugin for MindPlugin {
             .add_system_set(
                 SystemSet::new()
                     .label(SteeringRoutine)
+                    .after(SpatialIndex)
                     .with_system(boid::steering::intercept::update)
                     .with_system(boid::steering::fly_with_flock::update)
                     .with_system(boid::steering::avoid_collision::update)
-                    .with_system(boid::steering::arrive::update)
+                    .with_system(boid::steering::avoid_crafts::update)
+                    .with_system(boid::steering::avoid_obstacles::update)
                     .with_system(boid::steering::player::update)
-                    .with_system(boid::steering::face::update)
-                    .with_system(boid::steering::seek::update),
+                    .with_system(boid::steering::follow_path::update)
+                    .with_system(boid::steering::maneuver::update)
+                    .with_system(boid::steering::orient::update),
             )
+            .add_plugin(boid::steering::behaviour::SteeringBehaviourPlugin::<
+                boid::steering::seek::Seek,
+            >::default())
+            .add_plugin(boid::steering::behaviour::SteeringBehaviourPlugin::<
+                boid::steering::face::Face,
+            >::default())
+            .add_plugin(boid::steering::behaviour::SteeringBehaviourPlugin::<
+                boid::steering::flee::Flee,
+            >::default())
+            .add_plugin(boid::steering::behaviour::SteeringBehaviourPlugin::<
+                boid::steering::wander::Wander,
+            >::default())
+            .add_plugin(boid::steering::behaviour::SteeringBehaviourPlugin::<
+                boid::steering::arrive::Arrive,
+            >::default())
+            .add_plugin(boid::steering::behaviour::SteeringBehaviourPlugin::<
+                boid::steering::cruise::Cruise,
+            >::default())
+            .add_plugin(boid::steering::behaviour::SteeringBehaviourPlugin::<
+                boid::steering::orbit::Orbit,
+            >::default())
+            .add_plugin(boid::steering::behaviour::SteeringBehaviourPlugin::<
+                boid::steering::offset_pursuit::OffsetPursuit,
+            >::default())
             .add_system(
                 boid::steering::compose::update
                     .label(ComposeRoutineUpdate)
@@ -132,12 +198,33 @@ impl Plugin for MindPlugin {
             .add_system(player::engine_input)
             .add_system(player::wpn_input)
             .add_startup_system(player::setup_markers)
+            .add_startup_system(boid::steering::avoid_obstacles::setup_diagnostic)
             .add_system(player::update_ui_markers)
+            .add_system(player::show_current_craft_prediction)
             .insert_resource(player::PlayerMindConfig::default())
             .insert_resource(player::PlayerBoidInput::default())
             .insert_resource(player::CurrentCraft::default())
             // minds
             .add_system_to_stage(CoreStage::PreUpdate, boid::boid_mind)
+            .add_system(boid::handle_repair_requests)
+            .add_system(boid::targeting::record_damage)
+            .add_system(boid::targeting::decay_damage_ledgers)
+            .add_system(boid::resupply_resume)
+            .add_system(boid::retreat_when_damaged)
+            .add_system(boid::retreat_resume)
+            .add_system(boid::escort_ward_lost)
+            // these swap out the current strategy so they go where the strategies
+            // they spawn get flushed before the output manager sees them
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                boid::strategy::evade::interrupt.before(BoidStrategyButler),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                boid::strategy::evade::resume.before(BoidStrategyButler),
+            )
+            .add_system_to_stage(big_brain::BigBrainStage::Scorers, boid::utility::score)
+            .add_system_to_stage(big_brain::BigBrainStage::Actions, boid::utility::act)
             .add_system_to_stage(CoreStage::PreUpdate, flock::flock_mind)
             .add_system_to_stage(CoreStage::PreUpdate, player::player_mind)
             // types
@@ -148,13 +235,10 @@ impl Plugin for MindPlugin {
             .register_inspectable::<player::CraftCamera>()
             .register_inspectable::<flock::strategy::cas::CASState>()
             .register_inspectable::<boid::BoidMindConfig>()
+            .register_inspectable::<boid::utility::UtilityMind>()
+            .register_inspectable::<tribe::Faction>()
+            .register_inspectable::<sensors::radar::Radar>()
             .register_inspectable::<boid::steering::LinearRoutineOutput>()
             .register_inspectable::<boid::steering::AngularRoutineOutput>();
     }
 }
-/* #[derive(Debug, Clone, Component)]
-pub enum ScanPresence {
-    Obstacle,
-    Boid,
-}
- */
@@ -2,6 +2,7 @@ use deps::*;
 
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
 use educe::Educe;
 
 use crate::{craft::*, math::*, mind::sensors::*};
@@ -11,16 +12,37 @@ use strategy::*;
 
 pub mod steering;
 pub mod strategy;
+pub mod targeting;
+pub mod utility;
 
 #[derive(Debug, Clone, Inspectable, Component)]
 pub struct BoidMindConfig {
     pub angular_input_multiplier: TReal,
+    /// Strategies will request repairs when the craft's integrity fraction drops below this.
+    pub repair_request_threshold: TReal,
+    /// Attacking crafts will evade hostiles on their six closer than this. In meters.
+    pub evasion_tail_range: TReal,
+    /// Attacking crafts will evade projectiles headed their way closer than this. In meters.
+    pub evasion_projectile_range: TReal,
+    /// Minimum time between evasions. In seconds.
+    pub evasion_cooldown_secs: TReal,
+    /// Crafts will retreat when their integrity fraction drops below this.
+    pub retreat_threshold: TReal,
+    /// How far to retreat from hostiles when there's no formation to fall back to.
+    /// In meters.
+    pub retreat_distance: TReal,
 }
 
 impl Default for BoidMindConfig {
     fn default() -> Self {
         Self {
             angular_input_multiplier: 10.,
+            repair_request_threshold: 0.25,
+            evasion_tail_range: 800.,
+            evasion_projectile_range: 300.,
+            evasion_cooldown_secs: 5.,
+            retreat_threshold: 0.15,
+            retreat_distance: 1_500.,
         }
     }
 }
@@ -33,6 +55,11 @@ pub struct BoidMindBundle {
     pub cur_routine: CurrentSteeringRoutine,
     pub directive: BoidMindDirective,
 
+    // sensors
+    pub radar: radar::Radar,
+    pub contacts: radar::Contacts,
+    pub damage_ledger: targeting::DamageLedger,
+
     // indices
     pub routine_index: SteeringRoutinesIndex,
     pub wpn_index: CraftWeaponsIndex,
@@ -60,6 +87,36 @@ pub enum BoidMindDirective {
     AttackPresue {
         param: strategy::attack_persue::AttackPersue,
     },
+    /// Attack whichever perceived hostiles are deemed most worthy.
+    Engage {
+        param: strategy::engage::Engage,
+    },
+    /// Guard the ward, engaging any hostiles that come close to it.
+    Escort {
+        ward: Entity,
+    },
+    Evade {
+        param: strategy::evade::Evade,
+    },
+    Dogfight {
+        param: strategy::dogfight::Dogfight,
+    },
+    /// Roam about within the volume.
+    Patrol {
+        volume: strategy::patrol_volume::Volume,
+    },
+    /// Disengage and make for the rally point.
+    /// Get back to `resume`, if given, once no longer under fire.
+    Retreat {
+        param: strategy::retreat::Retreat,
+        resume: Option<Box<BoidMindDirective>>,
+    },
+    /// Dock at the given [`repair::ResupplyStation`] and get back to `resume` once
+    /// fully repaired.
+    Resupply {
+        station: Entity,
+        resume: Option<Box<BoidMindDirective>>,
+    },
 }
 
 pub fn boid_mind(
@@ -72,165 +129,550 @@ pub fn boid_mind(
             &engine::EngineConfig,
             &CraftDimensions,
         ),
-        Changed<BoidMindDirective>,
+        (Changed<BoidMindDirective>, Without<utility::UtilityMind>),
     >,
+    objects: Query<&GlobalTransform>,
 ) {
     for (boid_entt, directive, mut cur_stg, engine_config, dim) in boids.iter_mut() {
-        if let Some(cur_stg) = cur_stg.strategy.take() {
-            commands.entity(cur_stg).despawn_recursive();
+        for old in [cur_stg.strategy.take(), cur_stg.suspended.take()]
+            .into_iter()
+            .flatten()
+        {
+            commands.entity(old).despawn_recursive();
+        }
+        cur_stg.strategy = spawn_strategy(
+            &mut commands,
+            boid_entt,
+            directive,
+            engine_config,
+            dim,
+            &objects,
+        );
+    }
+}
+
+/// Spawns the [`BoidStrategy`] that carries out the directive.
+pub fn spawn_strategy(
+    commands: &mut Commands,
+    boid_entt: Entity,
+    directive: &BoidMindDirective,
+    engine_config: &engine::EngineConfig,
+    dim: &CraftDimensions,
+    objects: &Query<&GlobalTransform>,
+) -> Option<Entity> {
+    match directive {
+        BoidMindDirective::None => None,
+        BoidMindDirective::SlaveToPlayerControl => {
+            let player: Box<strategy::custom::RoutineSpawner> = Box::new(move |commands, _| {
+                commands
+                    .spawn()
+                    .insert_bundle(steering::player::Bundle::new(
+                        steering::player::Player,
+                        boid_entt,
+                    ))
+                    .id()
+            });
+            Some(
+                commands
+                    .spawn()
+                    .insert_bundle(strategy::custom::Bundle::new(
+                        strategy::custom::Custom::new(strategy::custom::Composition::Single {
+                            routine_spawner: player,
+                        }),
+                        boid_entt,
+                    ))
+                    .id(),
+            )
         }
-        cur_stg.strategy = match directive {
-            BoidMindDirective::None => None,
-            BoidMindDirective::SlaveToPlayerControl => {
-                let player: Box<strategy::custom::RoutineSpawner> = Box::new(move |commands, _| {
+        BoidMindDirective::HoldPosition { pos } => {
+            let pos = *pos;
+            let linvel_limit = engine_config.linvel_limit;
+            let accel_limit = engine_config.actual_acceleration_limit();
+            let raycast_toi_modifier = dim.max_element();
+            let cast_shape_radius = raycast_toi_modifier * 0.5;
+            let avoid_collision: Box<strategy::custom::RoutineSpawner> =
+                Box::new(move |commands, _| {
                     commands
                         .spawn()
-                        .insert_bundle(steering::player::Bundle::new(
-                            steering::player::Player,
+                        .insert_bundle(steering::avoid_collision::Bundle::new(
+                            steering::avoid_collision::AvoidCollision::new(
+                                cast_shape_radius,
+                                raycast_toi_modifier,
+                            ),
                             boid_entt,
+                            Default::default(),
                         ))
                         .id()
                 });
-                Some(
+            let arrive: Box<strategy::custom::RoutineSpawner> = Box::new(move |commands, _| {
+                commands
+                    .spawn()
+                    .insert_bundle(steering::arrive::Bundle::new(
+                        steering::arrive::Arrive {
+                            target: arrive::Target::Vector {
+                                at_pos: pos,
+                                pos_linvel: Default::default(),
+                                // with_linvel: Default::default(),
+                                with_speed: 0.,
+                            },
+                            arrival_tolerance: 5.,
+                            deceleration_radius: None,
+                            linvel_limit,
+                            avail_accel: accel_limit,
+                            max_speed: None,
+                        },
+                        boid_entt,
+                    ))
+                    .id()
+            });
+
+            Some(
+                commands
+                    .spawn()
+                    .insert_bundle(strategy::custom::Bundle::new(
+                        strategy::custom::Custom::new(
+                            strategy::custom::Composition::PriorityOverride {
+                                routines: smallvec::smallvec![avoid_collision, arrive],
+                            },
+                        ),
+                        boid_entt,
+                    ))
+                    .id(),
+            )
+        }
+        BoidMindDirective::JoinFomation { formation } => {
+            let formation = *formation;
+            Some(
+                commands
+                    .spawn()
+                    .insert_bundle(strategy::form::Bundle::new(
+                        strategy::form::Form { formation },
+                        boid_entt,
+                        Default::default(),
+                    ))
+                    .id(),
+            )
+        }
+        BoidMindDirective::FlyWithFlockCAS { param } => {
+            let param = param.clone();
+            let raycast_toi_modifier = dim.max_element();
+            let cast_shape_radius = raycast_toi_modifier * 0.5;
+            let avoid_collision: Box<strategy::custom::RoutineSpawner> =
+                Box::new(move |commands, _| {
                     commands
                         .spawn()
-                        .insert_bundle(strategy::custom::Bundle::new(
-                            strategy::custom::Custom::new(strategy::custom::Composition::Single {
-                                routine_spawner: player,
-                            }),
+                        .insert_bundle(steering::avoid_collision::Bundle::new(
+                            steering::avoid_collision::AvoidCollision::new(
+                                cast_shape_radius,
+                                raycast_toi_modifier,
+                            ),
                             boid_entt,
+                            Default::default(),
                         ))
-                        .id(),
-                )
-            }
-            BoidMindDirective::HoldPosition { pos } => {
-                let pos = *pos;
-                let linvel_limit = engine_config.linvel_limit;
-                let accel_limit = engine_config.actual_acceleration_limit();
-                let raycast_toi_modifier = dim.max_element();
-                let cast_shape_radius = raycast_toi_modifier * 0.5;
-                let avoid_collision: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, _| {
-                        commands
-                            .spawn()
-                            .insert_bundle(steering::avoid_collision::Bundle::new(
-                                steering::avoid_collision::AvoidCollision::new(
-                                    cast_shape_radius,
-                                    raycast_toi_modifier,
-                                ),
-                                boid_entt,
-                                Default::default(),
-                            ))
-                            .id()
-                    });
-                let arrive: Box<strategy::custom::RoutineSpawner> = Box::new(move |commands, _| {
+                        .id()
+                });
+            let avoid_crafts: Box<strategy::custom::RoutineSpawner> =
+                Box::new(move |commands, _| {
                     commands
                         .spawn()
-                        .insert_bundle(steering::arrive::Bundle::new(
-                            steering::arrive::Arrive {
-                                target: arrive::Target::Vector {
-                                    at_pos: pos,
-                                    pos_linvel: Default::default(),
-                                    // with_linvel: Default::default(),
-                                    with_speed: 0.,
-                                },
-                                arrival_tolerance: 5.,
-                                deceleration_radius: None,
-                                linvel_limit,
-                                avail_accel: accel_limit,
-                            },
+                        .insert_bundle(steering::avoid_crafts::Bundle::new(
+                            Default::default(),
                             boid_entt,
+                            Default::default(),
                         ))
                         .id()
                 });
-
-                Some(
+            let fly_with_flock: Box<strategy::custom::RoutineSpawner> =
+                Box::new(move |commands, _| {
                     commands
                         .spawn()
-                        .insert_bundle(strategy::custom::Bundle::new(
-                            strategy::custom::Custom::new(
-                                strategy::custom::Composition::PriorityOverride {
-                                    routines: smallvec::smallvec![avoid_collision, arrive],
-                                },
-                            ),
-                            boid_entt,
-                        ))
-                        .id(),
-                )
-            }
-            BoidMindDirective::JoinFomation { formation } => {
-                let formation = *formation;
-                Some(
+                        .insert_bundle(steering::fly_with_flock::Bundle::new(param, boid_entt))
+                        .id()
+                });
+
+            Some(
+                commands
+                    .spawn()
+                    .insert_bundle(strategy::custom::Bundle::new(
+                        strategy::custom::Custom::new(
+                            strategy::custom::Composition::PriorityOverride {
+                                routines: smallvec::smallvec![
+                                    avoid_collision,
+                                    avoid_crafts,
+                                    fly_with_flock
+                                ],
+                            },
+                        ),
+                        boid_entt,
+                    ))
+                    .id(),
+            )
+        }
+        BoidMindDirective::RunCircuit { param } => Some(
+            commands
+                .spawn()
+                .insert_bundle(strategy::run_circuit::Bundle::new(
+                    param.clone(),
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        ),
+        BoidMindDirective::AttackPresue { param } => Some(
+            commands
+                .spawn()
+                .insert_bundle(strategy::attack_persue::Bundle::new(
+                    param.clone(),
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        ),
+        BoidMindDirective::Engage { param } => Some(
+            commands
+                .spawn()
+                .insert_bundle(strategy::engage::Bundle::new(
+                    param.clone(),
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        ),
+        BoidMindDirective::Escort { ward } => Some(
+            commands
+                .spawn()
+                .insert_bundle(strategy::escort::Bundle::new(
+                    strategy::escort::Escort::new(*ward),
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        ),
+        BoidMindDirective::Evade { param } => Some(
+            commands
+                .spawn()
+                .insert_bundle(strategy::evade::Bundle::new(
+                    param.clone(),
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        ),
+        BoidMindDirective::Dogfight { param } => Some(
+            commands
+                .spawn()
+                .insert_bundle(strategy::dogfight::Bundle::new(
+                    param.clone(),
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        ),
+        BoidMindDirective::Patrol { volume } => Some(
+            commands
+                .spawn()
+                .insert_bundle(strategy::patrol_volume::Bundle::new(
+                    strategy::patrol_volume::PatrolVolume { volume: *volume },
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        ),
+        BoidMindDirective::Retreat { param, .. } => Some(
+            commands
+                .spawn()
+                .insert_bundle(strategy::retreat::Bundle::new(
+                    param.clone(),
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        ),
+        BoidMindDirective::Resupply { station, .. } => {
+            let pos = match objects.get(*station) {
+                Ok(xform) => xform.translation,
+                Err(err) => {
+                    tracing::error!(?err, ?station, "station not found for Resupply");
+                    return None;
+                }
+            };
+            let raycast_toi_modifier = dim.max_element();
+            let cast_shape_radius = raycast_toi_modifier * 0.5;
+            let avoid_collision: Box<strategy::custom::RoutineSpawner> =
+                Box::new(move |commands, _| {
                     commands
                         .spawn()
-                        .insert_bundle(strategy::form::Bundle::new(
-                            strategy::form::Form { formation },
+                        .insert_bundle(steering::avoid_collision::Bundle::new(
+                            steering::avoid_collision::AvoidCollision::new(
+                                cast_shape_radius,
+                                raycast_toi_modifier,
+                            ),
                             boid_entt,
                             Default::default(),
                         ))
-                        .id(),
-                )
-            }
-            BoidMindDirective::FlyWithFlockCAS { param } => {
-                let param = param.clone();
-                let raycast_toi_modifier = dim.max_element();
-                let cast_shape_radius = raycast_toi_modifier * 0.5;
-                let avoid_collision: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, _| {
-                        commands
-                            .spawn()
-                            .insert_bundle(steering::avoid_collision::Bundle::new(
-                                steering::avoid_collision::AvoidCollision::new(
-                                    cast_shape_radius,
-                                    raycast_toi_modifier,
-                                ),
-                                boid_entt,
-                                Default::default(),
-                            ))
-                            .id()
-                    });
-                let fly_with_flock: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, _| {
-                        commands
-                            .spawn()
-                            .insert_bundle(steering::fly_with_flock::Bundle::new(param, boid_entt))
-                            .id()
-                    });
-
-                Some(
+                        .id()
+                });
+            // stations tend to be tucked away behind things
+            let follow_path: Box<strategy::custom::RoutineSpawner> =
+                Box::new(move |commands, _| {
                     commands
                         .spawn()
-                        .insert_bundle(strategy::custom::Bundle::new(
-                            strategy::custom::Custom::new(
-                                strategy::custom::Composition::PriorityOverride {
-                                    routines: smallvec::smallvec![avoid_collision, fly_with_flock],
-                                },
-                            ),
+                        .insert_bundle(steering::follow_path::Bundle::new(
+                            steering::follow_path::FollowPath::new(pos),
                             boid_entt,
+                            Default::default(),
                         ))
-                        .id(),
-                )
-            }
-            BoidMindDirective::RunCircuit { param } => Some(
-                commands
-                    .spawn()
-                    .insert_bundle(strategy::run_circuit::Bundle::new(
-                        param.clone(),
-                        boid_entt,
-                        Default::default(),
-                    ))
-                    .id(),
-            ),
-            BoidMindDirective::AttackPresue { param } => Some(
+                        .id()
+                });
+
+            Some(
                 commands
                     .spawn()
-                    .insert_bundle(strategy::attack_persue::Bundle::new(
-                        param.clone(),
+                    .insert_bundle(strategy::custom::Bundle::new(
+                        strategy::custom::Custom::new(
+                            strategy::custom::Composition::PriorityOverride {
+                                routines: smallvec::smallvec![avoid_collision, follow_path],
+                            },
+                        ),
                         boid_entt,
-                        Default::default(),
                     ))
                     .id(),
-            ),
+            )
+        }
+    }
+}
+
+/// Sends crafts that requested repairs to the closest [`repair::ResupplyStation`].
+/// Crafts with a [`utility::UtilityMind`] see to it themselves.
+pub fn handle_repair_requests(
+    mut requests: EventReader<repair::RepairRequestEvent>,
+    mut boids: Query<(&GlobalTransform, &mut BoidMindDirective), Without<utility::UtilityMind>>,
+    utility_minds: Query<(), With<utility::UtilityMind>>,
+    stations: Query<(Entity, &GlobalTransform), With<repair::ResupplyStation>>,
+) {
+    for event in requests.iter() {
+        let (xform, mut directive) = match boids.get_mut(event.boid_entt) {
+            Ok(boid) => boid,
+            Err(_) if utility_minds.get(event.boid_entt).is_ok() => continue,
+            Err(err) => {
+                tracing::error!(?err, "boid not found for RepairRequestEvent");
+                continue;
+            }
+        };
+        if let BoidMindDirective::Resupply { .. } = directive.as_ref() {
+            continue;
+        }
+        let closest = stations.iter().min_by(|(_, a), (_, b)| {
+            a.translation
+                .distance_squared(xform.translation)
+                .partial_cmp(&b.translation.distance_squared(xform.translation))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        if let Some((station, _)) = closest {
+            let resume = directive.clone();
+            *directive = BoidMindDirective::Resupply {
+                station,
+                resume: Some(Box::new(resume)),
+            };
+        } else {
+            tracing::debug!(boid_entt = ?event.boid_entt, "repair requested but no stations found");
+        }
+    }
+}
+
+/// Sends crafts back to what they were doing once they're fully repaired and rearmed
+/// at a station.
+pub fn resupply_resume(
+    mut boids: Query<
+        (
+            &mut BoidMindDirective,
+            &attire::CraftIntegrity,
+            Option<&CraftWeaponsIndex>,
+        ),
+        With<repair::Docked>,
+    >,
+    ammo: Query<&arms::Ammunition>,
+) {
+    for (mut directive, integrity, weapons) in boids.iter_mut() {
+        if integrity.is_below(1.) {
+            continue;
+        }
+        if weapons.map_or(false, |weapons| {
+            weapons
+                .entt_to_desc
+                .keys()
+                .any(|wpn| ammo.get(*wpn).map_or(false, |ammo| !ammo.is_full()))
+        }) {
+            continue;
+        }
+        if let BoidMindDirective::Resupply { resume, .. } = directive.as_ref() {
+            let resume = resume.as_deref().cloned().unwrap_or_default();
+            *directive = resume;
+        }
+    }
+}
+
+/// Pulls badly damaged crafts that are under fire out of the fight, back to their
+/// flock's formation if they've got one or away from whoever's been hurting them
+/// otherwise. See [`retreat_resume`] for getting them back to it.
+/// Crafts with a [`utility::UtilityMind`] see to it themselves.
+#[allow(clippy::type_complexity)]
+pub fn retreat_when_damaged(
+    mut boids: Query<
+        (
+            &GlobalTransform,
+            &BoidMindConfig,
+            &attire::CraftIntegrity,
+            &mut BoidMindDirective,
+            &targeting::DamageLedger,
+            Option<&crate::mind::flock::CraftFlock>,
+        ),
+        (
+            Changed<attire::CraftIntegrity>,
+            Without<utility::UtilityMind>,
+        ),
+    >,
+    formations: Query<&crate::mind::flock::CurrentFlockFormation>,
+    index: Res<spatial::CraftSpatialIndex>,
+) {
+    for (xform, config, integrity, mut directive, ledger, flock) in boids.iter_mut() {
+        if !integrity.is_below(config.retreat_threshold) || ledger.dealt_by.is_empty() {
+            continue;
+        }
+        match directive.as_ref() {
+            BoidMindDirective::Retreat { .. }
+            | BoidMindDirective::Resupply { .. }
+            | BoidMindDirective::SlaveToPlayerControl => continue,
+            _ => {}
+        }
+        let threat = ledger
+            .dealt_by
+            .iter()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .and_then(|(attacker, _)| index.position(*attacker).map(|pos| (*attacker, pos)));
+        let rally = match flock.and_then(|f| formations.get(f.0).ok()) {
+            Some(formation) => strategy::retreat::RallyPoint::Formation(formation.formation),
+            None => {
+                let away = threat
+                    .map(|(_, pos)| (xform.translation - pos).normalize_or_zero())
+                    .unwrap_or_else(|| -xform.forward());
+                strategy::retreat::RallyPoint::Position(
+                    xform.translation + (away * config.retreat_distance),
+                )
+            }
+        };
+        let resume = directive.clone();
+        *directive = BoidMindDirective::Retreat {
+            param: strategy::retreat::Retreat {
+                threat: threat.map(|(entt, _)| entt),
+                ..strategy::retreat::Retreat::new(rally)
+            },
+            resume: Some(Box::new(resume)),
+        };
+    }
+}
+
+/// Sends crafts that retreated back to what they were doing once whoever was
+/// hurting them has let up, i.e. their [`targeting::DamageLedger`]'s decayed
+/// away, or once they're no longer badly damaged.
+pub fn retreat_resume(
+    mut boids: Query<(
+        &BoidMindConfig,
+        &attire::CraftIntegrity,
+        &targeting::DamageLedger,
+        &mut BoidMindDirective,
+    )>,
+) {
+    for (config, integrity, ledger, mut directive) in boids.iter_mut() {
+        let resume = match directive.as_ref() {
+            BoidMindDirective::Retreat {
+                resume: Some(resume),
+                ..
+            } => resume,
+            _ => continue,
+        };
+        if integrity.is_below(config.retreat_threshold) && !ledger.dealt_by.is_empty() {
+            continue;
+        }
+        let resume = resume.as_ref().clone();
+        *directive = resume;
+    }
+}
+
+/// Has escorts whose ward's gone engage whatever hostiles are about instead.
+/// Crafts with a [`utility::UtilityMind`] see to it themselves.
+pub fn escort_ward_lost(
+    mut boids: Query<&mut BoidMindDirective, Without<utility::UtilityMind>>,
+    // escort wards must have a rigid body
+    wards: Query<(), With<RigidBodyVelocityComponent>>,
+) {
+    for mut directive in boids.iter_mut() {
+        let ward_lost = match directive.as_ref() {
+            BoidMindDirective::Escort { ward } => wards.get(*ward).is_err(),
+            _ => continue,
+        };
+        if ward_lost {
+            *directive = BoidMindDirective::Engage {
+                param: Default::default(),
+            };
         }
     }
 }
+
+#[test]
+fn retreat_and_resume() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    let mut world = World::new();
+    world.insert_resource(spatial::CraftSpatialIndex::default());
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::identity())
+        .insert(BoidMindConfig::default())
+        .insert(attire::CraftIntegrity {
+            remaining: 0.01,
+            factory: 1.,
+        })
+        .insert(targeting::DamageLedger {
+            dealt_by: [(Entity::from_raw(1_000), 100.)].into_iter().collect(),
+        })
+        .insert(BoidMindDirective::HoldPosition { pos: TVec3::ONE })
+        .id();
+    let mut stage = SystemStage::single_threaded().with_system(retreat_when_damaged);
+    let mut resume_stage = SystemStage::single_threaded().with_system(retreat_resume);
+
+    // hurt and under fire
+    stage.run(&mut world);
+    resume_stage.run(&mut world);
+    assert!(matches!(
+        world.get::<BoidMindDirective>(boid).unwrap(),
+        BoidMindDirective::Retreat {
+            resume: Some(resume),
+            ..
+        } if matches!(**resume, BoidMindDirective::HoldPosition { .. })
+    ));
+
+    // still hurt but no one's shooting anymore
+    world
+        .get_mut::<targeting::DamageLedger>(boid)
+        .unwrap()
+        .dealt_by
+        .clear();
+    resume_stage.run(&mut world);
+    assert!(matches!(
+        world.get::<BoidMindDirective>(boid).unwrap(),
+        BoidMindDirective::HoldPosition { .. }
+    ));
+
+    // hurt without anyone to blame, e.g. collisions
+    world
+        .get_mut::<attire::CraftIntegrity>(boid)
+        .unwrap()
+        .remaining = 0.005;
+    stage.run(&mut world);
+    resume_stage.run(&mut world);
+    assert!(matches!(
+        world.get::<BoidMindDirective>(boid).unwrap(),
+        BoidMindDirective::HoldPosition { .. }
+    ));
+}
@@ -9,13 +9,25 @@ use crate::mind::*;
 
 pub mod arrive;
 pub mod avoid_collision;
+pub mod avoid_crafts;
+pub mod avoid_obstacles;
+pub mod behaviour;
 pub mod compose;
+pub mod context;
+pub mod cruise;
 pub mod face;
+pub mod flee;
 pub mod fly_with_flock;
+pub mod follow_path;
 pub mod intercept;
+pub mod maneuver;
+pub mod offset_pursuit;
+pub mod orbit;
+pub mod orient;
 pub mod player;
 pub mod seek;
 pub mod steering_behaviours;
+pub mod wander;
 
 #[derive(Debug, Default, Clone, Component, Reflect, Inspectable)]
 pub struct CurrentSteeringRoutine {
@@ -244,39 +256,85 @@ pub fn steering_output_to_engine(
     }
 }
 
-/* fn routine_garbage_collector(
+/// What a routine's being kept around for, usually strategies. Routines with
+/// this get despawned by [`routine_garbage_collector`] once all their owners are
+/// gone and they're no longer composed or current. An empty one means the
+/// routine's only kept alive by composition.
+#[derive(Debug, Clone, Default, Component)]
+pub struct RoutineOwners(pub smallvec::SmallVec<[Entity; 2]>);
+
+impl RoutineOwners {
+    pub fn new(owner: Entity) -> Self {
+        Self(smallvec::smallvec![owner])
+    }
+}
+
+/// Adds an owner to the routine's [`RoutineOwners`].
+pub struct ClaimRoutine {
+    pub routine: Entity,
+    pub owner: Entity,
+}
+
+impl bevy::ecs::system::Command for ClaimRoutine {
+    fn write(self, world: &mut World) {
+        let mut routine = match world.get_entity_mut(self.routine) {
+            Some(routine) => routine,
+            None => {
+                tracing::error!(routine = ?self.routine, "claimed routine not found");
+                return;
+            }
+        };
+        match routine.get_mut::<RoutineOwners>() {
+            Some(mut owners) => {
+                if !owners.0.contains(&self.owner) {
+                    owners.0.push(self.owner);
+                }
+            }
+            None => {
+                routine.insert(RoutineOwners::new(self.owner));
+            }
+        }
+    }
+}
+
+/// Reuses one of the craft's active routines of kind `P` if there's any, spawning
+/// one otherwise. The routine's claimed for `owner` either way.
+pub fn reuse_or_spawn<P: Component>(
+    commands: &mut Commands,
+    index: &sensors::SteeringRoutinesIndex,
+    owner: Entity,
+    spawner: impl FnOnce(&mut Commands) -> Entity,
+) -> Entity {
+    let routine = index
+        .kind::<P>()
+        .and_then(|entts| entts.first().copied())
+        .unwrap_or_else(|| spawner(commands));
+    commands.add(ClaimRoutine { routine, owner });
+    routine
+}
+
+/// Despawns routines that are no longer owned, composed or current.
+pub fn routine_garbage_collector(
     mut commands: Commands,
-    mut crafts: Query<
-        (Entity, &RoutineComposer, &mut CraftRoutinesIndex),
-        Changed<RoutineComposer>,
-    >,
-    routines: Query<&SteeringRoutine>,
-    mut cache: Local<bevy::utils::HashSet<Entity>>,
+    mut routines: Query<(Entity, &mut RoutineOwners)>,
+    composers: Query<&compose::Compose>,
+    crafts: Query<&CurrentSteeringRoutine>,
+    entities: Query<()>,
+    mut referenced: Local<bevy::utils::HashSet<Entity>>,
 ) {
-    for (boid_entt, composer, mut index) in crafts.iter_mut() {
-        // make a set of all the composed routines
-        cache.extend(composer.all_routines());
-        // for all index
-        for routine in index.entt_to_kind.keys() {
-            // if being composed
-            if cache.contains(routine) {
-                // remove from set
-                cache.remove(routine);
-            } else {
-                // destroy routine
-                commands.entity(*routine).despawn_recursive();
-            }
+    referenced.clear();
+    referenced.extend(composers.iter().flat_map(|c| c.composer.all_routines()));
+    referenced.extend(crafts.iter().filter_map(|c| c.routine));
+    for (entt, mut owners) in routines.iter_mut() {
+        // avoid tripping change detection
+        if owners.0.iter().any(|owner| entities.get(*owner).is_err()) {
+            owners.0.retain(|owner| entities.get(*owner).is_ok());
         }
-        // for remaining composed routines not in indices
-        for entt in cache.drain() {
-            let routine = routines
-                .get(entt)
-                .expect_or_log("composed steering routine not found");
-            index.add_routine(entt, routine.kind);
+        if owners.0.is_empty() && !referenced.contains(&entt) {
+            commands.entity(entt).despawn_recursive();
         }
     }
 }
- */
 
 /// Output of linear steering routines which is usually linear velocity desired next frame in
 /// fraction of [`EngineConfig:.linvel_limit`] in world space.
@@ -324,6 +382,7 @@ pub fn look_to(local_dir: TVec3) -> TVec3 {
     // scaling by the angle proves troublesome
     // it takes too long to settle, the final inputs being progressively too minute
     // as we close on the target direction
+    // see `orient` for a damped take on it that minds the roll as well
     /* fwd.angle_between(dir) * */
     fwd.cross(dir)
     /*
@@ -349,3 +408,82 @@ pub fn look_to(local_dir: TVec3) -> TVec3 {
         )
     */
 }
+
+#[test]
+fn orphaned_routines_get_collected() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use boid::strategy::custom;
+
+    let mut world = World::new();
+    let boid = world.spawn().id();
+    let mut strategy_stage = SystemStage::single_threaded();
+    strategy_stage.add_system(custom::butler);
+    let mut gc_stage = SystemStage::single_threaded();
+    gc_stage.add_system(routine_garbage_collector);
+
+    let seek = || -> Box<custom::RoutineSpawner> {
+        Box::new(|commands, strategy| {
+            commands
+                .spawn()
+                .insert_bundle(seek::Bundle::new(
+                    seek::Seek {
+                        target: seek::Target::Position { pos: TVec3::ZERO },
+                    },
+                    strategy.boid_entt(),
+                ))
+                .id()
+        })
+    };
+    let mut strategy = None;
+    for _ in 0..1000 {
+        // what `boid_mind` does on directive changes
+        if let Some(old) = strategy.take() {
+            world.despawn(old);
+        }
+        strategy = Some(
+            world
+                .spawn()
+                .insert_bundle(custom::Bundle::new(
+                    custom::Custom::new(custom::Composition::PriorityOverride {
+                        routines: smallvec::smallvec![seek(), seek()],
+                    }),
+                    boid,
+                ))
+                .id(),
+        );
+        strategy_stage.run(&mut world);
+        gc_stage.run(&mut world);
+    }
+    // the composer goes first then the routines it composed
+    gc_stage.run(&mut world);
+    gc_stage.run(&mut world);
+    // the current strategy's composer and its two routines
+    assert_eq!(
+        world
+            .query_filtered::<(), With<SteeringRoutine>>()
+            .iter(&world)
+            .count(),
+        3
+    );
+
+    // shared routines live on as long as any of their owners
+    let (owner_a, owner_b) = (world.spawn().id(), world.spawn().id());
+    let shared = world
+        .spawn()
+        .insert(SteeringRoutine::new(boid, RoutineKind::of::<seek::Seek>()))
+        .insert(RoutineOwners::new(owner_a))
+        .id();
+    bevy::ecs::system::Command::write(
+        ClaimRoutine {
+            routine: shared,
+            owner: owner_b,
+        },
+        &mut world,
+    );
+    world.despawn(owner_a);
+    gc_stage.run(&mut world);
+    assert!(world.get_entity(shared).is_some());
+    world.despawn(owner_b);
+    gc_stage.run(&mut world);
+    assert!(world.get_entity(shared).is_none());
+}
@@ -4,7 +4,7 @@ use bevy::prelude::*;
 // use bevy_prototype_debug_lines::*;
 // use bevy_rapier3d::prelude::*;
 
-use super::{ActiveSteeringRoutine, LinOnlyRoutineBundle, LinearRoutineOutput, SteeringRoutine};
+use super::{behaviour::*, steering_behaviours};
 use crate::math::*;
 
 /// All vectors are in in world basis
@@ -18,6 +18,14 @@ pub enum Target {
         with_speed: TReal,
         pos_linvel: TVec3,
     },
+    /// Arrive at a target moving at `pos_linvel` matching `with_linvel`.
+    /// Respects the per axis `avail_accel`, see
+    /// [`crate::math::arrive_with_linvel`].
+    WithLinvel {
+        at_pos: TVec3,
+        pos_linvel: TVec3,
+        with_linvel: TVec3,
+    },
 }
 
 #[derive(Debug, Clone, Component)]
@@ -27,7 +35,10 @@ pub struct Arrive {
     /// If not given, will be calculated based on accel and linvel_limit
     pub deceleration_radius: Option<TReal>,
     pub avail_accel: TVec3,
+    /// Caps the desired velocity along each local axis.
     pub linvel_limit: TVec3,
+    /// Caps the desired speed. Only used by [`Target::WithLinvel`]. In m/s.
+    pub max_speed: Option<TReal>,
 }
 
 /*
@@ -51,74 +62,120 @@ impl Default for ArriveState {
     }
 } */
 
-pub type Bundle = LinOnlyRoutineBundle<Arrive>;
+pub type Bundle = BehaviourBundle<Arrive>;
 
-pub fn update(
-    mut routines: Query<
-        (
-            &SteeringRoutine,
-            &Arrive,
-            // &mut ArriveState,
-            &mut LinearRoutineOutput,
-        ),
-        With<ActiveSteeringRoutine>,
-    >,
-    boids: Query<(&GlobalTransform,)>, // boids
-                                       // objects: Query<&GlobalTransform>,
-                                       // mut lines: ResMut<DebugLines>,
-) {
-    for (routine, param, mut output) in routines.iter_mut() {
-        let (xform,) = boids
-            .get(routine.boid_entt)
-            .expect_or_log("craft entt not found for routine");
+impl SteeringBehaviour for Arrive {
+    type State = Stateless;
+    type Outputs = LinOnly;
 
-        *output = match param.target {
-            /* Target::Object { entt, offset } => match objects.get(entt) {
-                Ok(obj_xform) => obj_xform.translation + offset,
-                Err(err) => {
-                    tracing::error!("error getting SeekTarget Object g_xform: {err:?}");
-                    continue;
-                }
-            }, */
+    fn update(&self, _: &mut Stateless, ctx: &SteeringCtx) -> SteeringOutput {
+        let xform = ctx.xform;
+        let lin = match self.target {
             Target::Vector {
                 at_pos,
-                // with_linvel,
                 pos_linvel,
                 with_speed,
+            } => steering_behaviours::arrive_at_position(
+                xform.translation,
+                at_pos + pos_linvel,
+                with_speed,
+                self.linvel_limit.z,
+                self.arrival_tolerance,
+                {
+                    let accel = xform.rotation * self.avail_accel;
+                    let target_offset = (at_pos + pos_linvel) - xform.translation;
+                    let accel = accel.project_onto(target_offset).length();
+
+                    steering_behaviours::dst_to_change(self.linvel_limit.z, with_speed, accel)
+                },
+            ),
+            Target::WithLinvel {
+                at_pos,
+                pos_linvel,
+                with_linvel,
             } => {
-                super::steering_behaviours::arrive_at_position(
+                let desired = arrive_with_linvel(
                     xform.translation,
-                    at_pos + pos_linvel,
-                    with_speed,
-                    param.linvel_limit.z,
-                    param.arrival_tolerance,
-                    {
-                        let accel = xform.rotation * param.avail_accel;
-                        let target_offset = (at_pos + pos_linvel) - xform.translation;
-                        let accel = accel.project_onto(target_offset).length();
-
-                        super::steering_behaviours::dst_to_change(
-                            param.linvel_limit.z,
-                            with_speed,
-                            accel,
-                        )
-                    },
-                )
-                .into()
-                /*  super::steering_behaviours::be_ray(
+                    xform.rotation,
                     at_pos,
-                    // with_linvel,
-                    TVec3::Z * -90.,
                     pos_linvel,
-                    xform.translation,
-                    vel.linvel.into(),
-                    param.avail_accel,
-                    param.linvel_limit,
+                    with_linvel,
+                    self.avail_accel,
+                    self.linvel_limit,
+                );
+                let desired = match self.max_speed {
+                    Some(max_speed) => desired.clamp_length_max(max_speed),
+                    None => desired,
+                };
+                // the engine scales the output by its own limits
+                steering_behaviours::linvel_to_output(
                     xform.rotation,
-                    &mut |v1, v2| lines.line(v1, v2, 0.),
+                    desired,
+                    ctx.engine_config.linvel_limit,
                 )
-                .into() */
             }
         };
+        SteeringOutput::lin(lin)
     }
 }
+
+#[test]
+fn arrive_keeps_to_max_speed() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use bevy_rapier3d::prelude::*;
+
+    use super::{ActiveSteeringRoutine, AngularRoutineOutput, CurrentSteeringRoutine};
+    use crate::craft::engine;
+    use crate::mind::boid::BoidMindConfig;
+
+    let pace = 10.;
+    let mut world = World::new();
+    world.insert_resource(Time::default());
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::from_rotation(TQuat::from_rotation_y(1.)))
+        .insert(RigidBodyVelocityComponent(RigidBodyVelocity::default()))
+        .insert(engine::EngineConfig::default())
+        .insert(engine::LinearEngineState::default())
+        .insert(engine::AngularEngineState::default())
+        .insert(BoidMindConfig::default())
+        .id();
+    let routine = world
+        .spawn()
+        .insert_bundle(Bundle::new(
+            Arrive {
+                target: Target::WithLinvel {
+                    // far off along the diagonal so no one axis limits it
+                    at_pos: TVec3::new(1_000., -1_000., -1_000.),
+                    pos_linvel: TVec3::ZERO,
+                    with_linvel: TVec3::ZERO,
+                },
+                arrival_tolerance: 5.,
+                deceleration_radius: None,
+                avail_accel: TVec3::splat(60.),
+                linvel_limit: engine::EngineConfig::default().linvel_limit,
+                max_speed: Some(pace),
+            },
+            boid,
+        ))
+        .insert(AngularRoutineOutput::default())
+        .insert(ActiveSteeringRoutine)
+        .id();
+    world.entity_mut(boid).insert(CurrentSteeringRoutine {
+        routine: Some(routine),
+    });
+
+    SystemStage::single_threaded()
+        .with_system(update::<Arrive>)
+        .run(&mut world);
+    SystemStage::single_threaded()
+        .with_system(super::steering_output_to_engine)
+        .run(&mut world);
+
+    // the engine's input is the velocity it'll try to attain
+    let input = world.get::<engine::LinearEngineState>(boid).unwrap().input;
+    assert!(
+        input.length() <= pace + 1e-3 && input.length() > pace * 0.9,
+        "{input:?}"
+    );
+}
@@ -0,0 +1,138 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    steering_behaviours, ActiveSteeringRoutine, LinOnlyRoutineBundleExtra, LinearRoutineOutput,
+    SteeringRoutine,
+};
+use crate::{
+    craft::{engine::*, *},
+    math::*,
+    mind::sensors::{spatial::CraftSpatialIndex, SteeringRoutinesIndex},
+};
+
+/// Reciprocal velocity obstacles (ORCA) against neighbouring crafts.
+/// Outputs zero unless the velocity the craft's going for is on a collision
+/// course, making it suitable for the first slot of priority compositions.
+#[derive(Debug, Clone, Component)]
+pub struct AvoidCrafts {
+    /// How far ahead to look for collisions. In seconds.
+    pub time_horizon_secs: TReal,
+    /// Only neighbours within this are considered.
+    pub neighbour_radius: TReal,
+    pub max_neighbours: usize,
+    /// Added to the combined radii of crafts.
+    pub safety_margin: TReal,
+}
+
+impl Default for AvoidCrafts {
+    fn default() -> Self {
+        Self {
+            time_horizon_secs: 3.,
+            neighbour_radius: 500.,
+            max_neighbours: 10,
+            safety_margin: 2.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct AvoidCraftsState {
+    /// What the craft was going for before we started avoiding. In world space.
+    pub preferred_linvel: TVec3,
+    pub avoiding: bool,
+}
+
+pub type Bundle = LinOnlyRoutineBundleExtra<AvoidCrafts, AvoidCraftsState>;
+
+pub fn update(
+    // NOTE: this steering system is stateful.
+    mut routines: Query<
+        (
+            &AvoidCrafts,
+            &mut AvoidCraftsState,
+            &SteeringRoutine,
+            &mut LinearRoutineOutput,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    crafts: Query<(
+        &GlobalTransform,
+        &RigidBodyVelocityComponent,
+        &CraftDimensions,
+        Option<&SteeringRoutinesIndex>,
+    )>,
+    engines: Query<(&LinearEngineState, &EngineConfig)>,
+    index: Res<CraftSpatialIndex>,
+    time: Res<Time>,
+) {
+    let timestep = if time.delta_seconds() > TReal::EPSILON {
+        time.delta_seconds()
+    } else {
+        1. / 60.
+    };
+    for (param, mut state, routine, mut lin_out) in routines.iter_mut() {
+        let boid_entt = routine.boid_entt();
+        let (xform, vel, dim, _) = crafts
+            .get(boid_entt)
+            .expect_or_log("craft entt not found for routine");
+        let (lin_state, config) = engines
+            .get(boid_entt)
+            .expect_or_log("craft entt not found for routine");
+
+        // last frame's desired vel is what the other routines want unless
+        // it was us who set it
+        if !state.avoiding {
+            state.preferred_linvel = xform.rotation * lin_state.input;
+        }
+        let pos = xform.translation;
+        let linvel = TVec3::from(vel.linvel);
+        let radius = dim.max_element() * 0.5;
+
+        let planes = index
+            .k_nearest(
+                pos,
+                param.max_neighbours,
+                param.neighbour_radius,
+                Some(boid_entt),
+            )
+            .into_iter()
+            .filter_map(|(other, other_pos)| {
+                let (_, other_vel, other_dim, other_routines) = crafts.get(other).ok()?;
+                // split the avoidance if they're doing their part
+                let responsibility = match other_routines.and_then(|r| r.kind::<AvoidCrafts>()) {
+                    Some(_) => 0.5,
+                    None => 1.,
+                };
+                Some(steering_behaviours::orca_plane(
+                    pos,
+                    linvel,
+                    other_pos,
+                    other_vel.linvel.into(),
+                    radius + (other_dim.max_element() * 0.5) + param.safety_margin,
+                    param.time_horizon_secs,
+                    timestep,
+                    responsibility,
+                ))
+            })
+            .collect::<smallvec::SmallVec<[_; 10]>>();
+
+        if planes
+            .iter()
+            .all(|plane| plane.contains(state.preferred_linvel))
+        {
+            state.avoiding = false;
+            *lin_out = Default::default();
+            continue;
+        }
+        state.avoiding = true;
+        let max_speed = config.linvel_limit.max_element();
+        let safe_linvel =
+            steering_behaviours::orca_linvel(state.preferred_linvel, &planes[..], max_speed);
+        *lin_out =
+            steering_behaviours::linvel_to_output(xform.rotation, safe_linvel, config.linvel_limit)
+                .into();
+    }
+}
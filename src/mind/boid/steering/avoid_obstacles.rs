@@ -0,0 +1,298 @@
+use deps::*;
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+    utils::HashSet,
+};
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    context::{self, ContextMap},
+    steering_behaviours::{self, Probe},
+    ActiveSteeringRoutine, LinOnlyRoutineBundleExtra, LinearRoutineOutput, SteeringRoutine,
+};
+use crate::craft::{attire::*, engine::*};
+use crate::math::*;
+
+/// Total shape casts by [`AvoidObstacles`] routines per frame.
+pub const CAST_COUNT_DIAGNOSTIC: DiagnosticId =
+    DiagnosticId::from_u128(0x5a1f_3c1e_77b4_4b7c_9e0d_a5d1_0b57_ac1e);
+
+/// Searches a sphere of directions for a clear path when the desired one's
+/// obstructed. Unlike [`super::avoid_collision::AvoidCollision`], casts are
+/// cached across frames and limited to a per frame budget.
+/// Writes the obstructed directions as danger if it has a [`ContextMap`].
+#[derive(Debug, Clone, Component)]
+pub struct AvoidObstacles {
+    /// How many directions to sample.
+    pub ray_count: usize,
+    /// Casts further than the distance covered in this at the current speed.
+    pub lookahead_secs: TReal,
+    /// Added to the lookahead distance.
+    pub raycast_toi_modifier: TReal,
+    pub cast_shape_radius: TReal,
+    /// Casts allowed each frame, including the one for the desired direction.
+    pub casts_per_frame: usize,
+    /// How long a cast result's trusted for. In seconds.
+    pub cache_secs: f64,
+    pub raycast_exclusion: HashSet<ColliderHandle>,
+}
+
+impl AvoidObstacles {
+    pub fn new(cast_shape_radius: TReal, raycast_toi_modifier: TReal) -> Self {
+        Self {
+            ray_count: 64,
+            lookahead_secs: 5.,
+            raycast_toi_modifier,
+            cast_shape_radius,
+            casts_per_frame: 8,
+            cache_secs: 0.25,
+            raycast_exclusion: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct AvoidObstaclesState {
+    /// Unit vectors in world space. Filled in on the first update.
+    pub directions: Vec<TVec3>,
+    /// Per direction, when it was last cast and what it found.
+    pub cache: Vec<Option<(f64, Probe)>>,
+    /// What the craft was going for before we started avoiding. In world space.
+    pub preferred_dir: TVec3,
+    pub avoiding: bool,
+    pub last_dodge_dir: TVec3,
+    /// Diagnostic.
+    pub casts_last_frame: usize,
+}
+
+pub type Bundle = LinOnlyRoutineBundleExtra<AvoidObstacles, AvoidObstaclesState>;
+
+pub fn setup_diagnostic(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(
+        CAST_COUNT_DIAGNOSTIC,
+        "avoid_obstacles_casts",
+        20,
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update(
+    // NOTE: this steering system is stateful.
+    mut routines: Query<
+        (
+            &AvoidObstacles,
+            &mut AvoidObstaclesState,
+            &SteeringRoutine,
+            &mut LinearRoutineOutput,
+            Option<&mut ContextMap>,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    crafts: Query<(
+        &GlobalTransform,
+        &LinearEngineState,
+        &RigidBodyVelocityComponent,
+        &RigidBodyCollidersComponent,
+    )>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    time: Res<Time>,
+    mut diagnostics: ResMut<Diagnostics>,
+) {
+    let now = time.seconds_since_startup();
+    let mut cast_ctr = 0usize;
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    for (param, mut state, routine, mut lin_out, context) in routines.iter_mut() {
+        let (xform, lin_state, vel, colliders) = crafts
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        if state.directions.len() != param.ray_count {
+            state.directions = crate::utils::points_on_sphere(param.ray_count);
+            state.cache = vec![None; param.ray_count];
+        }
+        // last frame's desired vel is what the other routines want unless
+        // it was us who set it
+        if !state.avoiding {
+            state.preferred_dir = (xform.rotation * lin_state.input).normalize_or_zero();
+        }
+        let toi =
+            (param.lookahead_secs * TVec3::from(vel.linvel).length()) + param.raycast_toi_modifier;
+        if let Some(mut context) = context {
+            write_danger(&mut context, &state, param, now, toi);
+        }
+        let preferred_dir = state.preferred_dir;
+        if preferred_dir == TVec3::ZERO {
+            *lin_out = Default::default();
+            continue;
+        }
+
+        let cast_shape = Ball::new(param.cast_shape_radius);
+        // shape rotation matters not for balls
+        let cast_pose = (xform.translation, xform.rotation).into();
+        let mut budget = param.casts_per_frame.max(1);
+        let cast = |dir: TVec3| {
+            query_pipeline
+                .cast_shape(
+                    &collider_set,
+                    &cast_pose,
+                    &dir.into(),
+                    &cast_shape,
+                    toi,
+                    InteractionGroups::new(
+                        ColliderGroups::SOLID.bits(),
+                        ColliderGroups::SOLID.bits(),
+                    ),
+                    Some(&|handle| {
+                        // not a craft collider
+                        !colliders.0 .0.contains(&handle)
+                            // not in the exclusion list
+                            && !param.raycast_exclusion.contains(&handle)
+                    }),
+                )
+                .map(|(_, hit)| Probe::Obstructed { toi: hit.toi })
+                .unwrap_or(Probe::Clear)
+        };
+
+        // the desired direction always gets a fresh look
+        budget -= 1;
+        if cast(preferred_dir) == Probe::Clear {
+            state.avoiding = false;
+            state.last_dodge_dir = TVec3::ZERO;
+            state.casts_last_frame = 1;
+            cast_ctr += 1;
+            *lin_out = Default::default();
+            continue;
+        }
+
+        let AvoidObstaclesState {
+            directions, cache, ..
+        } = &mut *state;
+        let escape = steering_behaviours::escape_direction(
+            preferred_dir,
+            &directions[..],
+            &mut |idx, dir| {
+                let cached = cache[idx];
+                match cached {
+                    Some((timestamp, probe)) if now - timestamp < param.cache_secs => probe,
+                    _ if budget > 0 => {
+                        budget -= 1;
+                        let probe = cast(dir);
+                        cache[idx] = Some((now, probe));
+                        probe
+                    }
+                    _ => Probe::Unknown,
+                }
+            },
+        );
+        let casts = param.casts_per_frame.max(1) - budget;
+        state.casts_last_frame = casts;
+        cast_ctr += casts;
+
+        state.avoiding = true;
+        if let Some(dir) = escape {
+            state.last_dodge_dir = dir;
+        } else if state.last_dodge_dir == TVec3::ZERO {
+            // haven't found anything yet, slow down while we look
+            state.last_dodge_dir = -preferred_dir * 0.25;
+        }
+        *lin_out = state.last_dodge_dir.into();
+    }
+    diagnostics.add_measurement(CAST_COUNT_DIAGNOSTIC, cast_ctr as f64);
+}
+
+/// Obstructed directions still in the cache become danger lobes, stronger the
+/// closer the hit.
+fn write_danger(
+    context: &mut ContextMap,
+    state: &AvoidObstaclesState,
+    param: &AvoidObstacles,
+    now: f64,
+    toi: TReal,
+) {
+    context.clear();
+    // roughly the angle between neighbouring slots of the map, never narrower
+    // than needed to cover the gaps between our own samples
+    let sample_spacing =
+        TReal::sqrt(4. * real::consts::PI / state.directions.len().max(1) as TReal);
+    let spread =
+        TReal::sqrt(4. * real::consts::PI / context::RESOLUTION as TReal).max(sample_spacing * 0.5);
+    let spread_cos = spread.cos();
+    for (dir, cached) in state.directions.iter().zip(state.cache.iter()) {
+        if let Some((timestamp, Probe::Obstructed { toi: hit_toi })) = cached {
+            if now - timestamp < param.cache_secs {
+                let strength = (1. - (hit_toi / toi.max(TReal::EPSILON))).clamp(0.1, 1.);
+                context.add_danger_lobe(*dir, strength, spread_cos);
+            }
+        }
+    }
+}
+
+#[test]
+fn danger_ahead_steers_context_sideways() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use super::{compose, RoutineKind};
+
+    let param = AvoidObstacles::new(1., 10.);
+    let mut state = AvoidObstaclesState {
+        directions: crate::utils::points_on_sphere(param.ray_count),
+        ..Default::default()
+    };
+    // everything within a sample of straight ahead is obstructed
+    let sample_spacing = TReal::sqrt(4. * real::consts::PI / param.ray_count as TReal);
+    state.cache = state
+        .directions
+        .iter()
+        .map(|dir| {
+            (dir.dot(-TVec3::Z) > sample_spacing.cos())
+                .then(|| (0., Probe::Obstructed { toi: 10. }))
+        })
+        .collect();
+    let mut danger = ContextMap::default();
+    write_danger(&mut danger, &state, &param, 0., 100.);
+
+    let mut world = World::new();
+    let boid = world.spawn().insert(GlobalTransform::identity()).id();
+    let avoid = world
+        .spawn()
+        .insert(SteeringRoutine::new(
+            boid,
+            RoutineKind::of::<LinearRoutineOutput>(),
+        ))
+        .insert(LinearRoutineOutput::default())
+        .insert(danger)
+        .id();
+    let seek = world
+        .spawn()
+        .insert(SteeringRoutine::new(
+            boid,
+            RoutineKind::of::<LinearRoutineOutput>(),
+        ))
+        .insert(LinearRoutineOutput(-TVec3::Z))
+        .id();
+    let composer = world
+        .spawn()
+        .insert_bundle(compose::Bundle::new(
+            compose::Compose {
+                composer: compose::SteeringRoutineComposer::Context {
+                    routines: smallvec::smallvec![
+                        (compose::ContextAdapter::Native { weight: 1. }, avoid),
+                        (compose::ContextAdapter::Interest { weight: 1. }, seek),
+                    ],
+                },
+            },
+            boid,
+        ))
+        .id();
+
+    SystemStage::single_threaded()
+        .with_system(compose::update)
+        .run(&mut world);
+
+    let out = world.get::<LinearRoutineOutput>(composer).unwrap().0;
+    let cos = out.normalize_or_zero().dot(-TVec3::Z);
+    // off to the side but still making headway
+    assert!(cos > 0. && cos < 0.9, "out: {out}");
+}
@@ -0,0 +1,292 @@
+//! A trait that takes care of the boilerplate of routines that only need to
+//! look at their own craft.
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Component)]
+//! pub struct Halt;
+//!
+//! impl SteeringBehaviour for Halt {
+//!     type State = Stateless;
+//!     type Outputs = LinOnly;
+//!     fn update(&self, _: &mut Stateless, ctx: &SteeringCtx) -> SteeringOutput {
+//!         SteeringOutput::lin(-ctx.linvel / ctx.engine_config.linvel_limit)
+//!     }
+//! }
+//!
+//! pub type Bundle = BehaviourBundle<Halt>;
+//!
+//! app.add_plugin(SteeringBehaviourPlugin::<Halt>::default());
+//! ```
+//!
+//! Routines that need more than their own craft and the transforms and
+//! velocities of others still roll their own update systems:
+//! - `intercept` reads its craft's `Contacts` and the flock blackboards.
+//! - `maneuver` reads its craft's `Contacts`.
+//! - `fly_with_flock` reads its flock strategy's `CASState` and the
+//!   `CraftSpatialIndex`.
+//! - `avoid_crafts` reads the `CraftSpatialIndex` and the dimensions, engine
+//!   states and routines of the crafts around it.
+//! - `avoid_collision` casts against rapier's `QueryPipeline` and draws
+//!   `DebugLines`.
+//! - `avoid_obstacles` casts against rapier's `QueryPipeline`, writes a
+//!   `ContextMap` and reports `Diagnostics`.
+//! - `follow_path` reads the `NavGrid`.
+//! - `orient` reads its craft's `AngularEngineState` and `BoidMindConfig`.
+//! - `player` reads the `PlayerBoidInput` and not the craft at all.
+//! - `compose` reads the outputs of the routines it composes.
+
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    ActiveSteeringRoutine, AngularRoutineOutput, LinearRoutineOutput, RoutineKind, SteeringRoutine,
+};
+use crate::craft::engine;
+use crate::math::*;
+use crate::mind::CraftMindSystems;
+
+/// What a [`SteeringBehaviour`] gets to look at.
+pub struct SteeringCtx<'a, 'w, 's> {
+    pub boid_entt: Entity,
+    pub xform: &'a GlobalTransform,
+    /// In world space.
+    pub linvel: TVec3,
+    /// In world space.
+    pub angvel: TVec3,
+    pub engine_config: &'a engine::EngineConfig,
+    pub time: &'a Time,
+    /// For looking up targets.
+    pub xforms: &'a Query<'w, 's, &'static GlobalTransform>,
+    /// For looking up targets.
+    pub vels: &'a Query<'w, 's, &'static RigidBodyVelocityComponent>,
+}
+
+/// What a [`SteeringBehaviour`] wants done. Only the parts its [`RoutineOutputs`]
+/// have room for are used.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SteeringOutput {
+    /// Same as [`LinearRoutineOutput`].
+    pub lin: TVec3,
+    /// Same as [`AngularRoutineOutput`].
+    pub ang: TVec3,
+}
+
+impl SteeringOutput {
+    #[inline]
+    pub fn lin(lin: TVec3) -> Self {
+        Self {
+            lin,
+            ang: TVec3::ZERO,
+        }
+    }
+
+    #[inline]
+    pub fn ang(ang: TVec3) -> Self {
+        Self {
+            lin: TVec3::ZERO,
+            ang,
+        }
+    }
+}
+
+/// Which output components a [`SteeringBehaviour`]'s routines get.
+pub trait RoutineOutputs: Send + Sync + 'static {
+    type Bundle: Bundle + Default;
+}
+
+pub struct LinOnly;
+
+impl RoutineOutputs for LinOnly {
+    type Bundle = (LinearRoutineOutput,);
+}
+
+pub struct AngOnly;
+
+impl RoutineOutputs for AngOnly {
+    type Bundle = (AngularRoutineOutput,);
+}
+
+pub struct LinAng;
+
+impl RoutineOutputs for LinAng {
+    type Bundle = (LinearRoutineOutput, AngularRoutineOutput);
+}
+
+/// State for behaviours that don't need any.
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct Stateless;
+
+/// A steering routine's parameter component. Have a [`SteeringBehaviourPlugin`]
+/// added for it and spawn it with a [`BehaviourBundle`].
+pub trait SteeringBehaviour: Component + Sized {
+    type State: Component + Default;
+    type Outputs: RoutineOutputs;
+
+    /// Called every frame the routine's active.
+    fn update(&self, state: &mut Self::State, ctx: &SteeringCtx) -> SteeringOutput;
+
+    /// Register any inspectable types here.
+    fn register_types(_app: &mut App) {}
+}
+
+/// A generic bundle for [`SteeringBehaviour`] routines.
+#[derive(Bundle)]
+pub struct BehaviourBundle<B>
+where
+    B: SteeringBehaviour,
+{
+    pub param: B,
+    pub state: B::State,
+    #[bundle]
+    pub outputs: <B::Outputs as RoutineOutputs>::Bundle,
+    pub tag: SteeringRoutine,
+    pub name: Name,
+    pub parent: Parent,
+}
+
+impl<B> BehaviourBundle<B>
+where
+    B: SteeringBehaviour,
+{
+    pub const DEFAULT_NAME: &'static str = "steering_behaviour";
+    pub fn new(param: B, boid_entt: Entity) -> Self {
+        Self {
+            param,
+            state: Default::default(),
+            outputs: Default::default(),
+            tag: SteeringRoutine::new(boid_entt, RoutineKind::of::<B>()),
+            name: Self::DEFAULT_NAME.into(),
+            parent: Parent(boid_entt),
+        }
+    }
+}
+
+/// Adds the update system for the behaviour to the [`CraftMindSystems::SteeringRoutine`]
+/// set and registers its types.
+pub struct SteeringBehaviourPlugin<B>(std::marker::PhantomData<B>);
+
+impl<B> Default for SteeringBehaviourPlugin<B> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<B> Plugin for SteeringBehaviourPlugin<B>
+where
+    B: SteeringBehaviour,
+{
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            update::<B>
+                .label(CraftMindSystems::SteeringRoutine)
+                .after(CraftMindSystems::SpatialIndex),
+        );
+        B::register_types(app);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update<B: SteeringBehaviour>(
+    mut routines: Query<
+        (
+            &B,
+            &mut B::State,
+            &SteeringRoutine,
+            Option<&mut LinearRoutineOutput>,
+            Option<&mut AngularRoutineOutput>,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(&RigidBodyVelocityComponent, &engine::EngineConfig)>,
+    xforms: Query<&GlobalTransform>,
+    vels: Query<&RigidBodyVelocityComponent>,
+    time: Res<Time>,
+) {
+    for (param, mut state, routine, lin_out, ang_out) in routines.iter_mut() {
+        let (vel, engine_config) = boids
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        let xform = xforms
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        let out = param.update(
+            &mut state,
+            &SteeringCtx {
+                boid_entt: routine.boid_entt(),
+                xform,
+                linvel: vel.linvel.into(),
+                angvel: vel.angvel.into(),
+                engine_config,
+                time: &time,
+                xforms: &xforms,
+                vels: &vels,
+            },
+        );
+        if let Some(mut lin_out) = lin_out {
+            *lin_out = out.lin.into();
+        }
+        if let Some(mut ang_out) = ang_out {
+            *ang_out = out.ang.into();
+        }
+    }
+}
+
+#[test]
+fn behaviour_boilerplate() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    #[derive(Debug, Clone, Component)]
+    struct Halt;
+
+    #[derive(Debug, Clone, Default, Component)]
+    struct HaltState {
+        updates: usize,
+    }
+
+    impl SteeringBehaviour for Halt {
+        type State = HaltState;
+        type Outputs = LinOnly;
+        fn update(&self, state: &mut HaltState, ctx: &SteeringCtx) -> SteeringOutput {
+            state.updates += 1;
+            SteeringOutput::lin(-ctx.linvel / ctx.engine_config.linvel_limit)
+        }
+    }
+
+    let mut world = World::new();
+    world.insert_resource(Time::default());
+    let engine_config = engine::EngineConfig::default();
+    let linvel = engine_config.linvel_limit * 0.5;
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::identity())
+        .insert(RigidBodyVelocityComponent(RigidBodyVelocity {
+            linvel: linvel.into(),
+            ..Default::default()
+        }))
+        .insert(engine_config)
+        .id();
+    let routine = world
+        .spawn()
+        .insert_bundle(BehaviourBundle::new(Halt, boid))
+        .insert(ActiveSteeringRoutine)
+        .id();
+    // inactive ones are left alone
+    let inactive = world
+        .spawn()
+        .insert_bundle(BehaviourBundle::new(Halt, boid))
+        .id();
+    assert!(world.get::<AngularRoutineOutput>(routine).is_none());
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(update::<Halt>);
+    stage.run(&mut world);
+
+    assert_eq!(
+        world.get::<LinearRoutineOutput>(routine).unwrap().0,
+        TVec3::splat(-0.5)
+    );
+    assert_eq!(world.get::<HaltState>(routine).unwrap().updates, 1);
+    assert_eq!(world.get::<HaltState>(inactive).unwrap().updates, 0);
+}
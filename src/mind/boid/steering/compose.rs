@@ -6,8 +6,8 @@ use crate::math::*;
 use crate::mind::*;
 
 use super::{
-    ActiveSteeringRoutine, AngularRoutineOutput, LinAngRoutineBundle, LinearRoutineOutput,
-    SteeringRoutine,
+    context::ContextMap, ActiveSteeringRoutine, AngularRoutineOutput, LinAngRoutineBundle,
+    LinearRoutineOutput, SteeringRoutine,
 };
 
 #[derive(Debug, Clone, Component)]
@@ -17,17 +17,56 @@ pub struct Compose {
 
 pub type Bundle = LinAngRoutineBundle<Compose>;
 
+/// Composers can't be nested deeper than this.
+pub const MAX_COMPOSITION_DEPTH: usize = 8;
+
 // TODO: run a similar ActiveRoutine tagging for `CurrentSteeringRoutine`s
 pub fn butler(
     mut commands: Commands,
-    changed: Query<(&Compose, &SteeringRoutine), Changed<Compose>>,
+    changed: Query<(Entity, &SteeringRoutine), Changed<Compose>>,
+    composers: Query<(Entity, &Compose, &SteeringRoutine)>,
     crafts: Query<(&sensors::SteeringRoutinesIndex,)>,
     mut cache: Local<bevy::utils::HashSet<Entity>>,
+    mut parents: Local<bevy::utils::HashMap<Entity, (Entity, Entity)>>,
 ) {
-    for (param, routine) in changed.iter() {
+    let mut changed = changed.iter().peekable();
+    if changed.peek().is_none() {
+        return;
+    }
+    // composed routine to its composer and the composer's boid
+    parents.clear();
+    for (entt, param, routine) in composers.iter() {
+        for child in param.composer.all_routines() {
+            parents.insert(child, (entt, routine.boid_entt()));
+        }
+    }
+    for (changed_entt, routine) in changed {
+        // a nested composer changing changes the whole tree
+        let mut root = changed_entt;
+        let mut climbed = 0;
+        while let Some((parent, _)) = parents
+            .get(&root)
+            .filter(|(_, boid_entt)| *boid_entt == routine.boid_entt())
+        {
+            climbed += 1;
+            if climbed > MAX_COMPOSITION_DEPTH {
+                break;
+            }
+            root = *parent;
+        }
         let (index,) = crafts.get(routine.boid_entt()).unwrap_or_log();
         // make a set of all the composed routines
-        cache.extend(param.composer.all_routines());
+        let mut stack = vec![root];
+        while let Some(entt) = stack.pop() {
+            if let Ok((_, param, _)) = composers.get(entt) {
+                for child in param.composer.all_routines() {
+                    // the set doubles as a guard against cycles
+                    if cache.insert(child) {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
         // for all index
         for routine in index.entt_to_kind.keys() {
             // if being composed
@@ -48,177 +87,313 @@ pub fn butler(
     }
 }
 
-#[allow(clippy::type_complexity)]
-pub fn update(
-    mut composer_routines: Query<
-        (
-            &Compose,
-            &SteeringRoutine,
-            &mut LinearRoutineOutput,
-            &mut AngularRoutineOutput,
-        ),
-        // With<ActiveSteeringRoutine>,
-    >,
-    other_routines: Query<
-        (Option<&LinearRoutineOutput>, Option<&AngularRoutineOutput>),
-        (With<SteeringRoutine>, Without<Compose>),
-    >,
-    boids: Query<(&GlobalTransform,)>,
-) {
-    for (param, routine, mut lin_out, mut ang_out) in composer_routines.iter_mut() {
-        // FIXME: i hate this code
-        let active_res = match &param.composer {
-            SteeringRoutineComposer::None => Default::default(),
-            SteeringRoutineComposer::Single { entt: routine_entt } => {
-                match other_routines
-                    .get(*routine_entt)
-                    .map(|(lin, ang)| BoidSteeringSystemOutput::get_active_res(lin, ang))
-                {
-                    Ok(Some(res)) => res,
-                    Ok(None) => {
-                        tracing::error!(
-                            ?routine_entt,
-                            "Routine doesn't have linear or angular results"
-                        );
-                        Default::default()
-                    }
-                    Err(err) => {
-                        tracing::error!(
-                            ?err,
-                            ?routine_entt,
-                            "routine not found for ActiveRoutines::Single"
-                        );
-                        Default::default()
-                    }
+/// Why a [`Compose`] routine couldn't be evaluated. Such routines output
+/// nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompositionError {
+    /// The composers compose each other, in order.
+    Cycle { composers: Vec<Entity> },
+    /// Nests more than [`MAX_COMPOSITION_DEPTH`] composers.
+    TooDeep { composer: Entity },
+}
+
+#[derive(Debug, Default)]
+pub struct EvaluationOrder {
+    /// Composers come after all the composers they compose.
+    pub order: Vec<Entity>,
+    /// Composers that compose any of the erroneous ones are left out
+    /// of `order` too.
+    pub errors: Vec<CompositionError>,
+}
+
+/// Topologically sorts the composers given the routines they each compose.
+pub fn evaluation_order(
+    composers: &bevy::utils::HashMap<Entity, smallvec::SmallVec<[Entity; 4]>>,
+) -> EvaluationOrder {
+    enum Mark {
+        Visiting,
+        Done { depth: usize },
+        Failed,
+    }
+    fn visit(
+        entt: Entity,
+        composers: &bevy::utils::HashMap<Entity, smallvec::SmallVec<[Entity; 4]>>,
+        marks: &mut bevy::utils::HashMap<Entity, Mark>,
+        stack: &mut Vec<Entity>,
+        out: &mut EvaluationOrder,
+    ) -> Option<usize> {
+        match marks.get(&entt) {
+            Some(Mark::Done { depth }) => return Some(*depth),
+            Some(Mark::Failed) => return None,
+            Some(Mark::Visiting) => {
+                let start = stack.iter().position(|e| *e == entt).unwrap_or_log();
+                out.errors.push(CompositionError::Cycle {
+                    composers: stack[start..].to_vec(),
+                });
+                return None;
+            }
+            None => {}
+        }
+        marks.insert(entt, Mark::Visiting);
+        stack.push(entt);
+        let mut depth = 1;
+        let mut ok = true;
+        for child in &composers[&entt] {
+            if composers.contains_key(child) {
+                match visit(*child, composers, marks, stack, out) {
+                    Some(child_depth) => depth = depth.max(child_depth + 1),
+                    None => ok = false,
                 }
             }
-            SteeringRoutineComposer::WeightSummed { routines: summed } => {
-                // zero it out first
-                let mut sum = Default::default();
-                for (weight, routine_entt) in summed {
-                    match other_routines
-                        .get(*routine_entt)
-                        .map(|(lin, ang)| BoidSteeringSystemOutput::get_active_res(lin, ang))
-                    {
-                        Ok(Some(res)) => {
-                            sum = sum + (*weight * res);
-                        }
-                        Ok(None) => {
-                            tracing::error!(
-                                ?routine_entt,
-                                "Routine doesn't have linear or angular results"
-                            );
-                            sum = Default::default();
-                            break;
-                        }
-                        Err(_) => {
-                            tracing::error!(
-                                ?routine_entt,
-                                "routine not found for ActiveRoutines::WeightSummed"
-                            );
-                            sum = Default::default();
+        }
+        stack.pop();
+        if ok && depth > MAX_COMPOSITION_DEPTH {
+            out.errors
+                .push(CompositionError::TooDeep { composer: entt });
+            ok = false;
+        }
+        if ok {
+            marks.insert(entt, Mark::Done { depth });
+            out.order.push(entt);
+            Some(depth)
+        } else {
+            marks.insert(entt, Mark::Failed);
+            None
+        }
+    }
+
+    let mut out = EvaluationOrder::default();
+    let mut marks = Default::default();
+    let mut stack = vec![];
+    for entt in composers.keys() {
+        visit(*entt, composers, &mut marks, &mut stack, &mut out);
+    }
+    out
+}
+
+type RoutinesQueryState = QueryState<
+    (
+        Option<&'static LinearRoutineOutput>,
+        Option<&'static AngularRoutineOutput>,
+        Option<&'static ContextMap>,
+    ),
+    With<SteeringRoutine>,
+>;
+
+type RoutinesQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static LinearRoutineOutput>,
+        Option<&'static AngularRoutineOutput>,
+        Option<&'static ContextMap>,
+    ),
+    With<SteeringRoutine>,
+>;
+
+/// Where composers get the results of the routines they compose from.
+struct ComposeSources<'a, 'w, 's> {
+    routines: &'a RoutinesQuery<'w, 's>,
+    /// Results of the composers evaluated so far this frame.
+    composed: &'a bevy::utils::HashMap<Entity, BoidSteeringSystemOutput>,
+}
+
+impl<'a, 'w, 's> ComposeSources<'a, 'w, 's> {
+    /// Logs and returns `None` if the routine's missing or has no results.
+    fn res(&self, routine_entt: Entity) -> Option<BoidSteeringSystemOutput> {
+        if let Some(res) = self.composed.get(&routine_entt) {
+            return Some(*res);
+        }
+        match self
+            .routines
+            .get(routine_entt)
+            .map(|(lin, ang, _)| BoidSteeringSystemOutput::get_active_res(lin, ang))
+        {
+            Ok(Some(res)) => Some(res),
+            Ok(None) => {
+                tracing::error!(
+                    ?routine_entt,
+                    "Routine doesn't have linear or angular results"
+                );
+                None
+            }
+            Err(err) => {
+                tracing::error!(?err, ?routine_entt, "composed routine not found");
+                None
+            }
+        }
+    }
+
+    /// Zero if any of the routines fail.
+    fn weight_summed(
+        &self,
+        summed: &[(SteeringRoutineWeight, Entity)],
+    ) -> BoidSteeringSystemOutput {
+        let mut sum = Default::default();
+        for (weight, routine_entt) in summed {
+            match self.res(*routine_entt) {
+                Some(res) => sum = sum + (*weight * res),
+                None => return Default::default(),
+            }
+        }
+        sum
+    }
+}
+
+/// Rolls for [`SteeringRoutineComposer::PrioritizedDithering`]. Seeded so that
+/// runs are reproducible.
+pub struct ComposeRng(pub rand::rngs::StdRng);
+
+impl Default for ComposeRng {
+    fn default() -> Self {
+        use rand::SeedableRng;
+        Self(rand::rngs::StdRng::seed_from_u64(420))
+    }
+}
+
+impl SteeringRoutineComposer {
+    fn compose(&self, sources: &ComposeSources, rng: &mut ComposeRng) -> BoidSteeringSystemOutput {
+        match self {
+            SteeringRoutineComposer::None => Default::default(),
+            SteeringRoutineComposer::Single { entt } => sources.res(*entt).unwrap_or_default(),
+            SteeringRoutineComposer::WeightSummed { routines } => {
+                sources.weight_summed(&routines[..])
+            }
+            SteeringRoutineComposer::PriorityOverride { routines } => {
+                let mut pick = Default::default();
+                for routine_entt in routines {
+                    match sources.res(*routine_entt) {
+                        Some(res) if res.is_zero() => {}
+                        Some(res) => {
+                            pick = res;
                             break;
                         }
+                        None => break,
                     }
                 }
-                sum
+                pick
             }
-            // FIXME: CLEAN ME UP
-            SteeringRoutineComposer::PriorityOverride { routines: priority } => {
-                // zero it out first
+            SteeringRoutineComposer::PrioritizedDithering { routines } => {
+                use rand::Rng;
                 let mut pick = Default::default();
-                'priority_loop: for routine_entt in priority {
-                    match other_routines
-                        .get(*routine_entt)
-                        .map(|(lin, ang)| BoidSteeringSystemOutput::get_active_res(lin, ang))
-                    {
-                        Ok(Some(res)) => {
-                            if !res.is_zero() {
-                                // tracing::info!(?res, ?routine_entt, "wasn't zero");
-                                pick = res;
-                                break 'priority_loop;
-                            }
-                        }
-                        Ok(None) => {
-                            tracing::error!(
-                                ?routine_entt,
-                                "Routine doesn't have linear or angular results"
-                            );
-                            pick = Default::default();
-                            break 'priority_loop;
-                        }
-                        Err(_) => {
-                            tracing::error!(
-                                ?routine_entt,
-                                "routine not found for ActiveRoutines::PriorityOverride"
-                            );
-                            pick = Default::default();
-                            break 'priority_loop;
+                for (probability, routine_entt) in routines {
+                    if !rng.0.gen_bool(probability.clamp(0., 1.) as f64) {
+                        continue;
+                    }
+                    match sources.res(*routine_entt) {
+                        Some(res) if res.is_zero() => {}
+                        Some(res) => {
+                            pick = res;
+                            break;
                         }
+                        None => break,
                     }
                 }
                 pick
             }
-            // FIXME: DRY this up
+            SteeringRoutineComposer::TruncatedSum { routines, budget } => {
+                let (mut lin_left, mut ang_left) = (budget.lin, budget.ang);
+                let mut sum = BoidSteeringSystemOutput::default();
+                for (weight, routine_entt) in routines {
+                    if lin_left < TReal::EPSILON && ang_left < TReal::EPSILON {
+                        break;
+                    }
+                    let res = match sources.res(*routine_entt) {
+                        Some(res) => *weight * res,
+                        None => return Default::default(),
+                    };
+                    let lin = res.lin().clamp_length_max(lin_left);
+                    let ang = res.ang().clamp_length_max(ang_left);
+                    lin_left -= lin.length();
+                    ang_left -= ang.length();
+                    sum = sum + res.with(lin, ang);
+                }
+                sum
+            }
             SteeringRoutineComposer::AvoidCollisionHelper {
                 avoid_collision,
-                routines: summed,
+                routines,
             } => {
-                let avoid_coll_out = match other_routines
-                    .get(*avoid_collision)
-                    .map(|(lin, ang)| BoidSteeringSystemOutput::get_active_res(lin, ang))
-                {
-                    Ok(Some(res)) => res,
-                    Ok(None) => {
-                        tracing::error!(
-                            ?avoid_collision,
-                            "Routine doesn't have linear or angular results"
-                        );
-                        Default::default()
-                    }
-                    Err(err) => {
-                        tracing::error!(
-                            ?err,
-                            ?avoid_collision,
-                            "routine not found for ActiveRoutines::AvoidCollisionHelper"
-                        );
-                        Default::default()
-                    }
-                };
+                let avoid_coll_out = sources.res(*avoid_collision).unwrap_or_default();
                 if avoid_coll_out.is_zero() {
-                    let mut sum = Default::default();
-                    for (weight, routine_entt) in summed {
-                        match other_routines
-                            .get(*routine_entt)
-                            .map(|(lin, ang)| BoidSteeringSystemOutput::get_active_res(lin, ang))
-                        {
-                            Ok(Some(res)) => {
-                                sum = sum + (*weight * res);
-                            }
-                            Ok(None) => {
-                                tracing::error!(
-                                    ?routine_entt,
-                                    "Routine doesn't have linear or angular results"
-                                );
-                                sum = Default::default();
-                                break;
-                            }
-                            Err(_) => {
-                                tracing::error!(
-                                    ?routine_entt,
-                                    "routine not found for ActiveRoutines::AvoidCollisionHelper"
-                                );
-                                sum = Default::default();
-                                break;
+                    sources.weight_summed(&routines[..])
+                } else {
+                    avoid_coll_out
+                }
+            }
+            SteeringRoutineComposer::Context { routines } => {
+                let mut map = ContextMap::default();
+                for (adapter, routine_entt) in routines {
+                    match (adapter, sources.routines.get(*routine_entt)) {
+                        (ContextAdapter::Native { weight }, Ok((_, _, Some(routine_map)))) => {
+                            map.merge(routine_map, *weight);
+                        }
+                        (ContextAdapter::Interest { weight }, Ok(_)) => {
+                            if let Some(res) = sources.res(*routine_entt) {
+                                map.add_interest_lobe(res.lin() * *weight);
                             }
                         }
+                        (_, Ok(_)) => {
+                            tracing::error!(
+                                ?routine_entt,
+                                ?adapter,
+                                "routine doesn't have what the context adapter needs"
+                            );
+                        }
+                        (_, Err(err)) => {
+                            tracing::error!(?err, ?routine_entt, "composed routine not found");
+                        }
                     }
-                    sum
-                } else {
-                    avoid_coll_out
                 }
+                BoidSteeringSystemOutput::LinOnly { lin: map.resolve() }
             }
-        };
+        }
+    }
+}
+
+/// Composers are evaluated children first so that they can compose each other.
+#[allow(clippy::type_complexity)]
+pub fn update(
+    mut routines: QuerySet<(
+        RoutinesQueryState,
+        QueryState<(&mut LinearRoutineOutput, &mut AngularRoutineOutput), With<Compose>>,
+    )>,
+    composers: Query<(Entity, &Compose, &SteeringRoutine)>,
+    boids: Query<(&GlobalTransform,)>,
+    mut graph: Local<bevy::utils::HashMap<Entity, smallvec::SmallVec<[Entity; 4]>>>,
+    mut rng: Local<ComposeRng>,
+) {
+    graph.clear();
+    graph.extend(
+        composers
+            .iter()
+            .map(|(entt, param, _)| (entt, param.composer.all_routines())),
+    );
+    let order = evaluation_order(&graph);
+    for err in &order.errors {
+        tracing::error!(?err, "invalid steering routine composition");
+    }
+
+    let mut composed = bevy::utils::HashMap::default();
+    {
+        let routines = routines.q0();
+        for entt in order.order {
+            let (_, param, _) = composers.get(entt).unwrap_or_log();
+            let res = param.composer.compose(
+                &ComposeSources {
+                    routines: &routines,
+                    composed: &composed,
+                },
+                &mut rng,
+            );
+            composed.insert(entt, res);
+        }
+    }
+
+    let mut outputs = routines.q1();
+    for (entt, _, routine) in composers.iter() {
+        // the erroneous ones output nothing
+        let active_res = composed.get(&entt).copied().unwrap_or_default();
         let (lin, ang) = match active_res {
             BoidSteeringSystemOutput::Both { lin, ang } => (lin, ang),
             BoidSteeringSystemOutput::LinOnly { lin } => {
@@ -229,6 +404,7 @@ pub fn update(
             }
             BoidSteeringSystemOutput::AngOnly { ang } => (TVec3::ZERO, ang),
         };
+        let (mut lin_out, mut ang_out) = outputs.get_mut(entt).unwrap_or_log();
         *lin_out = lin.into();
         *ang_out = ang.into();
     }
@@ -293,6 +469,23 @@ pub enum SteeringRoutineComposer {
     PriorityOverride {
         routines: smallvec::SmallVec<[Entity; 4]>,
     },
+    /// Routines vote on a [`ContextMap`] and the most interesting direction
+    /// out of the safest ones is taken. Linear only.
+    Context {
+        routines: smallvec::SmallVec<[(ContextAdapter, Entity); 4]>,
+    },
+    /// Like PriorityOverride except each routine is only considered with its
+    /// probability. Cheap variety and a way out of the higher priority routines
+    /// getting stuck.
+    PrioritizedDithering {
+        routines: smallvec::SmallVec<[(TReal, Entity); 4]>,
+    },
+    /// Adds up routines in order until the magnitude budget is used up. The
+    /// routine that crosses it gets truncated.
+    TruncatedSum {
+        routines: smallvec::SmallVec<[(SteeringRoutineWeight, Entity); 4]>,
+        budget: SteeringRoutineWeight,
+    },
     /// A variant of WeightSummed except with a single priority checked routine that goes first
     /// In order to avoid making a second composition layer for the common avoid collision case
     AvoidCollisionHelper {
@@ -301,6 +494,18 @@ pub enum SteeringRoutineComposer {
     },
 }
 
+/// How a routine's results are put on the [`ContextMap`] of a
+/// [`SteeringRoutineComposer::Context`].
+#[derive(Debug, Clone, Copy)]
+pub enum ContextAdapter {
+    /// The routine writes its own [`ContextMap`]. Its interest is scaled by
+    /// `weight`.
+    Native { weight: TReal },
+    /// The routine's linear output becomes an interest lobe scaled by
+    /// `weight`.
+    Interest { weight: TReal },
+}
+
 impl Default for SteeringRoutineComposer {
     fn default() -> Self {
         Self::None
@@ -317,6 +522,15 @@ impl SteeringRoutineComposer {
                 routines.iter().map(|(_, entt)| *entt).collect()
             }
             SteeringRoutineComposer::PriorityOverride { routines } => routines.clone(),
+            SteeringRoutineComposer::PrioritizedDithering { routines } => {
+                routines.iter().map(|(_, entt)| *entt).collect()
+            }
+            SteeringRoutineComposer::TruncatedSum { routines, .. } => {
+                routines.iter().map(|(_, entt)| *entt).collect()
+            }
+            SteeringRoutineComposer::Context { routines } => {
+                routines.iter().map(|(_, entt)| *entt).collect()
+            }
             SteeringRoutineComposer::AvoidCollisionHelper {
                 avoid_collision,
                 routines,
@@ -368,6 +582,14 @@ impl BoidSteeringSystemOutput {
             Self::AngOnly { ang } => *ang,
         }
     }
+    /// Same variant with different values.
+    fn with(&self, lin: TVec3, ang: TVec3) -> Self {
+        match self {
+            Self::Both { .. } => Self::Both { lin, ang },
+            Self::LinOnly { .. } => Self::LinOnly { lin },
+            Self::AngOnly { .. } => Self::AngOnly { ang },
+        }
+    }
 }
 
 impl std::ops::Add for BoidSteeringSystemOutput {
@@ -443,3 +665,160 @@ impl BoidSteeringSystemOutput {
         }
     }
 }
+
+#[test]
+fn compose_linear_and_angular_routines() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    let mut world = World::new();
+    let boid = world.spawn().insert(GlobalTransform::identity()).id();
+    let lin = world
+        .spawn()
+        .insert(SteeringRoutine::new(
+            boid,
+            super::RoutineKind::of::<LinearRoutineOutput>(),
+        ))
+        .insert(LinearRoutineOutput(TVec3::X))
+        .id();
+    let ang = world
+        .spawn()
+        .insert(SteeringRoutine::new(
+            boid,
+            super::RoutineKind::of::<AngularRoutineOutput>(),
+        ))
+        .insert(AngularRoutineOutput(TVec3::Y))
+        .id();
+    let composer = world
+        .spawn()
+        .insert_bundle(Bundle::new(
+            Compose {
+                composer: SteeringRoutineComposer::WeightSummed {
+                    routines: smallvec::smallvec![
+                        (Default::default(), lin),
+                        ((1., 0.5).into(), ang)
+                    ],
+                },
+            },
+            boid,
+        ))
+        .id();
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(update);
+    stage.run(&mut world);
+
+    // the linear only routine shouldn't stomp over the angular only one and vice versa
+    assert_eq!(
+        world.get::<LinearRoutineOutput>(composer).unwrap().0,
+        TVec3::X
+    );
+    assert_eq!(
+        world.get::<AngularRoutineOutput>(composer).unwrap().0,
+        TVec3::Y * 0.5
+    );
+}
+
+#[test]
+fn nested_compositions() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    let mut world = World::new();
+    let boid = world.spawn().insert(GlobalTransform::identity()).id();
+    let spawn_routine = |world: &mut World, lin: Option<TVec3>, ang: Option<TVec3>| {
+        let kind = match (lin, ang) {
+            (Some(_), None) => super::RoutineKind::of::<LinearRoutineOutput>(),
+            (None, Some(_)) => super::RoutineKind::of::<AngularRoutineOutput>(),
+            _ => super::RoutineKind::of::<(LinearRoutineOutput, AngularRoutineOutput)>(),
+        };
+        let mut entt = world.spawn();
+        entt.insert(SteeringRoutine::new(boid, kind));
+        if let Some(lin) = lin {
+            entt.insert(LinearRoutineOutput(lin));
+        }
+        if let Some(ang) = ang {
+            entt.insert(AngularRoutineOutput(ang));
+        }
+        entt.id()
+    };
+    let lin = spawn_routine(&mut world, Some(TVec3::X), None);
+    let ang = spawn_routine(&mut world, None, Some(TVec3::Y));
+    let both = spawn_routine(&mut world, Some(-TVec3::X), Some(TVec3::Y * 0.25));
+    let spawn_composer = |world: &mut World, composer| {
+        world
+            .spawn()
+            .insert_bundle(Bundle::new(Compose { composer }, boid))
+            .id()
+    };
+    let inner = spawn_composer(
+        &mut world,
+        SteeringRoutineComposer::WeightSummed {
+            routines: smallvec::smallvec![((2., 1.).into(), lin), (Default::default(), both)],
+        },
+    );
+    let outer = spawn_composer(
+        &mut world,
+        SteeringRoutineComposer::TruncatedSum {
+            routines: smallvec::smallvec![(Default::default(), inner), (Default::default(), ang)],
+            budget: (1., 0.5).into(),
+        },
+    );
+    // composers composing each other
+    let cycle_a = spawn_composer(&mut world, SteeringRoutineComposer::None);
+    let cycle_b = spawn_composer(
+        &mut world,
+        SteeringRoutineComposer::Single { entt: cycle_a },
+    );
+    world.get_mut::<Compose>(cycle_a).unwrap().composer =
+        SteeringRoutineComposer::Single { entt: cycle_b };
+    world.get_mut::<LinearRoutineOutput>(cycle_a).unwrap().0 = TVec3::X;
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(update);
+    stage.run(&mut world);
+
+    // the inner result's summed up from both kinds of routines, its angular
+    // part leaving only some of the budget for the next one
+    assert_eq!(world.get::<LinearRoutineOutput>(outer).unwrap().0, TVec3::X);
+    assert_eq!(
+        world.get::<AngularRoutineOutput>(outer).unwrap().0,
+        TVec3::Y * 0.5
+    );
+    // and the cycle outputs nothing
+    assert_eq!(
+        world.get::<LinearRoutineOutput>(cycle_a).unwrap().0,
+        TVec3::ZERO
+    );
+
+    let mut graph = bevy::utils::HashMap::default();
+    graph.insert(cycle_a, smallvec::smallvec![cycle_b]);
+    graph.insert(cycle_b, smallvec::smallvec![cycle_a]);
+    graph.insert(outer, smallvec::smallvec![inner, ang]);
+    graph.insert(inner, smallvec::smallvec![lin, both]);
+    let order = evaluation_order(&graph);
+    assert_eq!(order.order, vec![inner, outer]);
+    assert_eq!(order.errors.len(), 1);
+    assert!(matches!(
+        &order.errors[0],
+        CompositionError::Cycle { composers } if composers.len() == 2
+    ));
+
+    // a chain one too deep
+    let mut graph = bevy::utils::HashMap::default();
+    let mut child = lin;
+    let chain = (0..=MAX_COMPOSITION_DEPTH)
+        .map(|_| {
+            let entt = world.spawn().id();
+            graph.insert(entt, smallvec::smallvec![child]);
+            child = entt;
+            entt
+        })
+        .collect::<Vec<_>>();
+    let order = evaluation_order(&graph);
+    assert_eq!(order.order[..], chain[..MAX_COMPOSITION_DEPTH]);
+    assert_eq!(
+        order.errors,
+        vec![CompositionError::TooDeep {
+            composer: *chain.last().unwrap()
+        }]
+    );
+}
@@ -0,0 +1,126 @@
+//! Context steering: routines express how much they'd like to go in and how
+//! dangerous it'd be to go in each of a fixed set of directions and the
+//! composer picks the best safe one.
+//!
+//! See Andrew Fray's *Context Steering* in Game AI Pro 2.
+
+use deps::*;
+
+use bevy::prelude::*;
+use once_cell::sync::Lazy;
+
+use crate::math::*;
+
+/// The number of directions in a [`ContextMap`].
+pub const RESOLUTION: usize = 32;
+
+/// The directions sampled by a [`ContextMap`]. In world space.
+pub static DIRECTIONS: Lazy<Vec<TVec3>> = Lazy::new(|| crate::utils::points_on_sphere(RESOLUTION));
+
+/// Interest and danger in every one of the [`DIRECTIONS`].
+/// Attach to a steering routine to have it write into the [`super::compose::Compose`]
+/// map directly.
+#[derive(Debug, Clone, Component)]
+pub struct ContextMap {
+    pub interest: [TReal; RESOLUTION],
+    pub danger: [TReal; RESOLUTION],
+}
+
+impl Default for ContextMap {
+    fn default() -> Self {
+        Self {
+            interest: [0.; RESOLUTION],
+            danger: [0.; RESOLUTION],
+        }
+    }
+}
+
+impl ContextMap {
+    pub fn clear(&mut self) {
+        *self = Default::default();
+    }
+
+    /// Adds interest falling off with the angle from `desired`, scaled by its
+    /// length. Directions facing away from it get none.
+    pub fn add_interest_lobe(&mut self, desired: TVec3) {
+        let strength = desired.length();
+        if strength < TReal::EPSILON {
+            return;
+        }
+        let dir = desired / strength;
+        for (ii, sample) in DIRECTIONS.iter().enumerate() {
+            self.interest[ii] += sample.dot(dir).max(0.) * strength;
+        }
+    }
+
+    /// Marks directions within `spread_cos`, the cosine of the half angle, of
+    /// `dir` as dangerous.
+    pub fn add_danger_lobe(&mut self, dir: TVec3, strength: TReal, spread_cos: TReal) {
+        let dir = dir.normalize_or_zero();
+        for (ii, sample) in DIRECTIONS.iter().enumerate() {
+            let cos = sample.dot(dir);
+            if cos >= spread_cos {
+                // full strength at the center fading out to the rim
+                let falloff = (cos - spread_cos) / (1. - spread_cos).max(TReal::EPSILON);
+                self.danger[ii] = self.danger[ii].max(strength * (0.5 + (0.5 * falloff)));
+            }
+        }
+    }
+
+    /// Sums up the interest and keeps the worse of the dangers.
+    pub fn merge(&mut self, other: &ContextMap, interest_weight: TReal) {
+        for ii in 0..RESOLUTION {
+            self.interest[ii] += other.interest[ii] * interest_weight;
+            self.danger[ii] = self.danger[ii].max(other.danger[ii]);
+        }
+    }
+
+    /// Picks the most interesting direction out of the least dangerous ones and
+    /// blends it with its interesting neighbours. Returns a vector in the
+    /// chosen direction with the length of its interest, clamped to one.
+    pub fn resolve(&self) -> TVec3 {
+        // mask all but the least dangerous directions
+        let min_danger = self
+            .danger
+            .iter()
+            .copied()
+            .fold(TReal::INFINITY, TReal::min);
+        const DANGER_TOLERANCE: TReal = 0.05;
+        let unmasked = |ii: usize| self.danger[ii] <= min_danger + DANGER_TOLERANCE;
+
+        let best = match (0..RESOLUTION).filter(|ii| unmasked(*ii)).max_by(|a, b| {
+            self.interest[*a]
+                .partial_cmp(&self.interest[*b])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        }) {
+            Some(best) if self.interest[best] > TReal::EPSILON => best,
+            _ => return TVec3::ZERO,
+        };
+        // smooth over the sampling resolution
+        const NEIGHBOUR_COS: TReal = 0.8;
+        let best_dir = DIRECTIONS[best];
+        let blended = (0..RESOLUTION)
+            .filter(|ii| unmasked(*ii) && DIRECTIONS[*ii].dot(best_dir) >= NEIGHBOUR_COS)
+            .map(|ii| DIRECTIONS[ii] * self.interest[ii])
+            .fold(TVec3::ZERO, |sum, v| sum + v)
+            .normalize_or_zero();
+        blended * self.interest[best].min(1.)
+    }
+}
+
+#[test]
+fn context_map_resolution() {
+    let mut map = ContextMap::default();
+    map.add_interest_lobe(TVec3::X);
+    let out = map.resolve();
+    assert!(out.normalize().dot(TVec3::X) > 0.9, "{out}");
+
+    // something in the way, go around it but still in the general direction
+    map.add_danger_lobe(TVec3::X, 1., 0.9);
+    let out = map.resolve();
+    let cos = out.normalize().dot(TVec3::X);
+    assert!(cos < 0.95 && cos > 0., "{out}");
+
+    // nothing of interest
+    assert_eq!(ContextMap::default().resolve(), TVec3::ZERO);
+}
@@ -0,0 +1,28 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{behaviour::*, steering_behaviours};
+use crate::math::*;
+
+/// Holds the given linear velocity. Holding the current velocity amounts to cutting thrust.
+#[derive(Debug, Clone, Component)]
+pub struct Cruise {
+    /// In world space. Uses the current linear velocity if None.
+    pub linvel: Option<TVec3>,
+}
+
+pub type Bundle = BehaviourBundle<Cruise>;
+
+impl SteeringBehaviour for Cruise {
+    type State = Stateless;
+    type Outputs = LinOnly;
+
+    fn update(&self, _: &mut Stateless, ctx: &SteeringCtx) -> SteeringOutput {
+        SteeringOutput::lin(steering_behaviours::linvel_to_output(
+            ctx.xform.rotation,
+            self.linvel.unwrap_or(ctx.linvel),
+            ctx.engine_config.linvel_limit,
+        ))
+    }
+}
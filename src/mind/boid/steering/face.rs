@@ -1,6 +1,6 @@
 use deps::*;
 
-use super::{ActiveSteeringRoutine, AngOnlyRoutineBundle, AngularRoutineOutput, SteeringRoutine};
+use super::behaviour::*;
 use crate::math::*;
 use bevy::prelude::*;
 
@@ -17,26 +17,20 @@ pub struct Face {
     pub target: Target,
 }
 
-pub type Bundle = AngOnlyRoutineBundle<Face>;
+pub type Bundle = BehaviourBundle<Face>;
 
-pub fn update(
-    mut routines: Query<
-        (&Face, &SteeringRoutine, &mut AngularRoutineOutput),
-        With<ActiveSteeringRoutine>,
-    >,
-    objects: Query<&GlobalTransform>,
-) {
-    for (param, routine, mut output) in routines.iter_mut() {
-        let xform = objects
-            .get(routine.boid_entt)
-            .expect_or_log("craft entt not found for routine");
-        let pos = match param.target {
+impl SteeringBehaviour for Face {
+    type State = Stateless;
+    type Outputs = AngOnly;
+
+    fn update(&self, _: &mut Stateless, ctx: &SteeringCtx) -> SteeringOutput {
+        let dir = match self.target {
             Target::Object { entt } => {
-                let target_pos = objects.get(entt).unwrap_or_log().translation;
-                (target_pos - xform.translation).normalize()
+                let target_pos = ctx.xforms.get(entt).unwrap_or_log().translation;
+                (target_pos - ctx.xform.translation).normalize()
             }
             Target::Direction { dir } => dir,
         };
-        *output = super::look_to(xform.rotation.inverse() * pos).into();
+        SteeringOutput::ang(super::look_to(ctx.xform.rotation.inverse() * dir))
     }
 }
@@ -0,0 +1,37 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{behaviour::*, seek::Target, steering_behaviours};
+use crate::math::*;
+
+/// Runs from the target when it's closer than the panic distance.
+#[derive(Debug, Clone, Component)]
+pub struct Flee {
+    pub target: Target,
+    /// In meters.
+    pub panic_distance: TReal,
+}
+
+pub type Bundle = BehaviourBundle<Flee>;
+
+impl SteeringBehaviour for Flee {
+    type State = Stateless;
+    type Outputs = LinOnly;
+
+    fn update(&self, _: &mut Stateless, ctx: &SteeringCtx) -> SteeringOutput {
+        let pos = match self.target {
+            Target::Object { entt } => match ctx.xforms.get(entt) {
+                Ok(xform) => xform.translation,
+                // nothing to be afraid of anymore
+                Err(_) => return SteeringOutput::default(),
+            },
+            Target::Position { pos } => pos,
+        };
+        SteeringOutput::lin(steering_behaviours::flee_position(
+            ctx.xform.translation,
+            pos,
+            self.panic_distance,
+        ))
+    }
+}
@@ -3,7 +3,13 @@ use deps::*;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::mind::flock::strategy::cas::*;
+use crate::{
+    math::*,
+    mind::{
+        flock::{strategy::cas::*, CraftFlock},
+        sensors::spatial::CraftSpatialIndex,
+    },
+};
 
 use super::{
     look_to, steering_behaviours, ActiveSteeringRoutine, AngularRoutineOutput, LinAngRoutineBundle,
@@ -15,6 +21,11 @@ pub struct FlyWithFlock {
     pub flock_strategy_entt: Entity,
 }
 
+/// Only this many of the closest flock mates are separated from.
+const SEPARATION_NEIGHBOURS: usize = 8;
+/// In meters.
+const SEPARATION_RADIUS: TReal = 200.;
+
 pub type Bundle = LinAngRoutineBundle<FlyWithFlock>;
 
 pub fn update(
@@ -29,19 +40,37 @@ pub fn update(
     >,
     strategies: Query<&CASState>,
     crafts: Query<(&GlobalTransform, &RigidBodyVelocityComponent)>, // crafts
+    flocks: Query<&CraftFlock>,
+    index: Res<CraftSpatialIndex>,
 ) {
     for (param, routine, mut lin_out, mut ang_out) in routines.iter_mut() {
         let (xform, vel) = crafts
             .get(routine.boid_entt)
             .expect_or_log("craft entt not found for routine");
+        let flock = flocks.get(routine.boid_entt).ok().map(|f| f.0);
         let cas = strategies
             .get(param.flock_strategy_entt)
             .expect_or_log("unable to find craft_group for fly_with_flock routine");
+        // only flock mates, other crafts are left to the avoidance routines
+        let neighbours = index
+            .k_nearest_where(
+                xform.translation,
+                SEPARATION_NEIGHBOURS,
+                SEPARATION_RADIUS,
+                |entt| {
+                    entt != routine.boid_entt
+                        && flock.is_some()
+                        && flocks.get(entt).ok().map(|f| f.0) == flock
+                },
+            )
+            .into_iter()
+            .map(|(_, pos)| pos)
+            .collect::<smallvec::SmallVec<[_; SEPARATION_NEIGHBOURS]>>();
         let (cohesion, allignment, separation) = (
             steering_behaviours::cohesion(xform.translation, cas.member_count, cas.center_sum),
             steering_behaviours::allignment(vel.linvel.into(), cas.member_count, cas.vel_sum),
             // NOTE: 10x multiplier
-            10.0 * steering_behaviours::separation(xform.translation, &cas.craft_positions[..]),
+            10.0 * steering_behaviours::separation(xform.translation, &neighbours[..]),
         );
         *lin_out = (cohesion + allignment + separation).into();
         *ang_out = look_to(xform.rotation * allignment).into();
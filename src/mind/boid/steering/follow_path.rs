@@ -0,0 +1,181 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{
+    steering_behaviours, ActiveSteeringRoutine, LinOnlyRoutineBundleExtra, LinearRoutineOutput,
+    SteeringRoutine,
+};
+use crate::{craft::engine, math::*, mind::nav::NavGrid};
+
+/// Goes around large obstacles by following a path from the [`NavGrid`].
+/// Seeks a point `lookahead` meters down the path to smooth out the corners and
+/// arrives at the goal.
+#[derive(Debug, Clone, Component)]
+pub struct FollowPath {
+    /// In world space.
+    pub goal: TVec3,
+    /// In meters.
+    pub lookahead: TReal,
+    /// The goal's replanned to once it moves more than this.
+    pub replan_distance: TReal,
+}
+
+impl FollowPath {
+    pub fn new(goal: TVec3) -> Self {
+        Self {
+            goal,
+            lookahead: 60.,
+            replan_distance: 25.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct FollowPathState {
+    pub path: Vec<TVec3>,
+    /// Index of the waypoint the current segment starts at.
+    pub segment: usize,
+    /// The goal the path was planned to.
+    pub planned_goal: Option<TVec3>,
+    /// Of the [`NavGrid`] the path was planned on.
+    pub nav_version: u64,
+}
+
+pub type Bundle = LinOnlyRoutineBundleExtra<FollowPath, FollowPathState>;
+
+pub fn update(
+    mut routines: Query<
+        (
+            &FollowPath,
+            &mut FollowPathState,
+            &SteeringRoutine,
+            &mut LinearRoutineOutput,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(&GlobalTransform, &engine::EngineConfig)>,
+    grid: Res<NavGrid>,
+) {
+    for (param, mut state, routine, mut output) in routines.iter_mut() {
+        let (xform, config) = boids
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        let pos = xform.translation;
+
+        let goal_moved = state
+            .planned_goal
+            .map(|goal| goal.distance(param.goal) > param.replan_distance)
+            .unwrap_or(true);
+        if goal_moved || state.nav_version != grid.version || state.path.len() < 2 {
+            state.path = grid.find_path(pos, param.goal).unwrap_or_else(|| {
+                tracing::warn!(?pos, goal = ?param.goal, "no path found, going straight");
+                vec![pos, param.goal]
+            });
+            state.segment = 0;
+            state.planned_goal = Some(param.goal);
+            state.nav_version = grid.version;
+        }
+        // the goal may have moved a bit
+        *state.path.last_mut().unwrap_or_log() = param.goal;
+
+        // move on to the next segment once we're close to its end
+        while state.segment + 2 < state.path.len()
+            && pos.distance(state.path[state.segment + 1]) < param.lookahead
+        {
+            state.segment += 1;
+        }
+
+        // where we are along the current segment plus the lookahead
+        let (seg_start, seg_end) = (state.path[state.segment], state.path[state.segment + 1]);
+        let seg = seg_end - seg_start;
+        let along = if seg.length_squared() > TReal::EPSILON {
+            (pos - seg_start).dot(seg) / seg.length()
+        } else {
+            0.
+        };
+        let mut remaining = along.max(0.) + param.lookahead;
+        let mut carrot = None;
+        for ii in state.segment..(state.path.len() - 1) {
+            let (from, to) = (state.path[ii], state.path[ii + 1]);
+            let len = from.distance(to);
+            if remaining <= len {
+                carrot = Some(from + ((to - from).normalize_or_zero() * remaining));
+                break;
+            }
+            remaining -= len;
+        }
+
+        *output = match carrot {
+            Some(carrot) => steering_behaviours::seek_position(pos, carrot).into(),
+            // the goal's within the lookahead, come to a stop there
+            None => steering_behaviours::linvel_to_output(
+                xform.rotation,
+                arrive_with_linvel(
+                    pos,
+                    xform.rotation,
+                    param.goal,
+                    TVec3::ZERO,
+                    TVec3::ZERO,
+                    config.actual_acceleration_limit(),
+                    config.linvel_limit,
+                ),
+                config.linvel_limit,
+            )
+            .into(),
+        };
+    }
+}
+
+#[test]
+fn follow_path_goes_around() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use crate::craft::trajectory::{PointMassModel, PointMassState};
+    use crate::mind::nav::NavObstacle;
+
+    let mut grid = NavGrid::new(10., 5.);
+    grid.rebuild(vec![NavObstacle {
+        center: TVec3::ZERO,
+        radius: 50.,
+    }]);
+    let (start, goal) = (TVec3::new(-150., 0., 0.), TVec3::new(150., 0., 0.));
+
+    let mut world = World::new();
+    world.insert_resource(grid);
+    let config = engine::EngineConfig::default();
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::from_translation(start))
+        .insert(config.clone())
+        .id();
+    let routine = world
+        .spawn()
+        .insert_bundle(Bundle::new(FollowPath::new(goal), boid, Default::default()))
+        .insert(ActiveSteeringRoutine)
+        .id();
+    let mut stage = SystemStage::single_threaded().with_system(update);
+
+    let model = PointMassModel::new(&config, TQuat::IDENTITY);
+    let mut state = PointMassState {
+        pos: start,
+        ..Default::default()
+    };
+    let dt = 1. / 60.;
+    let mut closest = TReal::INFINITY;
+    for _ in 0..(20 * 60) {
+        stage.run(&mut world);
+        let output = world.get::<LinearRoutineOutput>(routine).unwrap().0;
+        model.step(&mut state, output * config.linvel_limit, dt);
+        world.get_mut::<GlobalTransform>(boid).unwrap().translation = state.pos;
+        closest = closest.min(state.pos.length());
+    }
+
+    // went around the obstacle instead of through it
+    let path = &world.get::<FollowPathState>(routine).unwrap().path;
+    assert!(path.len() > 2, "{path:?}");
+    assert!(closest > 50., "closest: {closest}");
+    // and came to a stop at the goal
+    assert!(state.pos.distance(goal) < 1., "{:?}", state.pos);
+    assert!(state.linvel.length() < 1., "{:?}", state.linvel);
+}
@@ -4,11 +4,23 @@ use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
 use super::{ActiveSteeringRoutine, LinOnlyRoutineBundle, LinearRoutineOutput, SteeringRoutine};
-use crate::math::*;
+use crate::{
+    math::*,
+    mind::{flock::blackboard::FlockBlackboards, sensors::radar::Contacts},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    /// Reads the quarry's state straight off the physics engine.
+    Rb(RigidBodyHandle),
+    /// Uses what the craft's [`Contacts`] know about the quarry, falling back
+    /// on its flock's blackboard.
+    Contact(Entity),
+}
 
 #[derive(Debug, Clone, Component)]
 pub struct Intercept {
-    pub quarry_rb: RigidBodyHandle,
+    pub target: Target,
     /// Will use the craft engine's config if None.
     pub speed: Option<TReal>,
     pub linvel_limit: TVec3,
@@ -21,22 +33,46 @@ pub fn update(
         (&Intercept, &SteeringRoutine, &mut LinearRoutineOutput),
         With<ActiveSteeringRoutine>,
     >,
-    boids: Query<(&GlobalTransform,)>,
+    boids: Query<(&GlobalTransform, Option<&Contacts>)>,
     quarries: Query<(&GlobalTransform, &RigidBodyVelocityComponent)>,
+    time: Res<Time>,
+    blackboards: FlockBlackboards,
 ) {
     for (param, routine, mut output) in routines.iter_mut() {
-        let (xform,) = boids
+        let (xform, contacts) = boids
             .get(routine.boid_entt)
             .expect_or_log("craft entt not found for routine");
-        let (quarry_xform, quarry_vel) = quarries
-            .get(param.quarry_rb.entity())
-            .expect_or_log("quarry rigid body not found for on Intercept routine");
+        let (quarry_pos, quarry_linvel) = match param.target {
+            Target::Rb(handle) => {
+                let (quarry_xform, quarry_vel) = quarries
+                    .get(handle.entity())
+                    .expect_or_log("quarry rigid body not found for on Intercept routine");
+                (quarry_xform.translation, quarry_vel.linvel.into())
+            }
+            Target::Contact(entt) => {
+                if let Some(contact) = contacts.and_then(|c| c.get(entt)) {
+                    (
+                        contact.predicted_pos(time.seconds_since_startup()),
+                        contact.linvel,
+                    )
+                } else if let Some(track) = blackboards
+                    .of_boid(routine.boid_entt)
+                    .and_then(|b| b.track(entt))
+                {
+                    (track.pos, track.linvel)
+                } else {
+                    // lost track
+                    *output = Default::default();
+                    continue;
+                }
+            }
+        };
         let speed = param.speed.unwrap_or(param.linvel_limit.z);
         *output = super::steering_behaviours::intercept_target(
             xform.translation,
             speed,
-            quarry_xform.translation,
-            quarry_vel.linvel.into(),
+            quarry_pos,
+            quarry_linvel,
         )
         .into();
     }
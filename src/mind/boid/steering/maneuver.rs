@@ -0,0 +1,232 @@
+use deps::*;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use super::{
+    look_to, ActiveSteeringRoutine, AngularRoutineOutput, LinAngRoutineBundleExtra,
+    LinearRoutineOutput, SteeringRoutine,
+};
+use crate::{math::*, mind::sensors::radar::Contacts};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManeuverKind {
+    /// Hard turn perpendicular to the threat.
+    BreakTurn,
+    /// Corkscrew around the flight path while rolling.
+    Jink,
+    /// Randomly changing sideways thrust.
+    LateralThrust,
+    /// Reverse thrust while turning to face the threat, hoping it overshoots.
+    OvershootBait,
+}
+
+impl ManeuverKind {
+    pub const ALL: [ManeuverKind; 4] = [
+        ManeuverKind::BreakTurn,
+        ManeuverKind::Jink,
+        ManeuverKind::LateralThrust,
+        ManeuverKind::OvershootBait,
+    ];
+}
+
+#[derive(Debug, Clone, Component)]
+pub struct Maneuver {
+    pub kind: ManeuverKind,
+    /// Tracked through the craft's [`Contacts`]. Assumed to be behind us if
+    /// unknown.
+    pub threat: Option<Entity>,
+    /// Of the jinks and the lateral thrust changes. In seconds.
+    pub period_secs: f64,
+}
+
+#[derive(Debug, Clone, Default, Component)]
+pub struct ManeuverState {
+    started_secs: Option<f64>,
+    /// In world space.
+    break_dir: Option<TVec3>,
+    /// In world space.
+    lateral_dir: TVec3,
+    next_lateral_change_secs: f64,
+}
+
+pub type Bundle = LinAngRoutineBundleExtra<Maneuver, ManeuverState>;
+
+pub fn update(
+    mut routines: Query<
+        (
+            &Maneuver,
+            &SteeringRoutine,
+            &mut ManeuverState,
+            &mut LinearRoutineOutput,
+            &mut AngularRoutineOutput,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(&GlobalTransform, &Contacts)>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for (param, routine, mut state, mut lin_out, mut ang_out) in routines.iter_mut() {
+        let (xform, contacts) = boids
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        let started_secs = *state.started_secs.get_or_insert(now);
+        let fwd = xform.forward();
+        let threat_dir = param
+            .threat
+            .and_then(|threat| contacts.get(threat))
+            .map(|contact| (contact.pos - xform.translation).normalize_or_zero())
+            .filter(|dir| *dir != TVec3::ZERO)
+            .unwrap_or(-fwd);
+
+        let (lin, ang) = match param.kind {
+            ManeuverKind::BreakTurn => {
+                // stick with the side we picked
+                let break_dir = *state.break_dir.get_or_insert_with(|| {
+                    let side = threat_dir.cross(xform.up());
+                    if side.length_squared() > real::EPSILON {
+                        side.normalize()
+                    } else {
+                        xform.right()
+                    }
+                });
+                (break_dir, look_to(xform.rotation.inverse() * break_dir))
+            }
+            ManeuverKind::Jink => {
+                let phase = ((now - started_secs) / param.period_secs) * std::f64::consts::TAU;
+                let lateral =
+                    (xform.right() * phase.cos() as TReal) + (xform.up() * phase.sin() as TReal);
+                let dir = (fwd + (lateral * 0.5)).normalize();
+                // roll along the way
+                (
+                    dir,
+                    look_to(xform.rotation.inverse() * dir) + (TVec3::Z * 0.5),
+                )
+            }
+            ManeuverKind::LateralThrust => {
+                if now >= state.next_lateral_change_secs {
+                    let angle = rand::thread_rng().gen_range(0.0..real::consts::TAU);
+                    state.lateral_dir = (xform.right() * angle.cos()) + (xform.up() * angle.sin());
+                    state.next_lateral_change_secs = now + param.period_secs;
+                }
+                (
+                    ((fwd * 0.5) + state.lateral_dir).normalize_or_zero(),
+                    TVec3::ZERO,
+                )
+            }
+            ManeuverKind::OvershootBait => {
+                (-fwd * 0.25, look_to(xform.rotation.inverse() * threat_dir))
+            }
+        };
+        *lin_out = lin.into();
+        *ang_out = ang.into();
+    }
+}
+
+#[test]
+fn break_turn_clears_pursuer() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use crate::craft::trajectory::{step_rotation, PointMassModel, PointMassState};
+    use crate::mind::sensors::radar::{Contact, ScanPresence};
+
+    /// Has a point mass flying down -Z do the maneuver while a pursuer that
+    /// started on its six keeps straight on at it. Returns the closest the
+    /// pursuer got.
+    fn simulate(kind: Option<ManeuverKind>) -> TReal {
+        let model = PointMassModel {
+            accel_limit: TVec3::new(20., 20., 30.),
+            linvel_limit: TVec3::ONE * 50.,
+            limit_linvel: true,
+            rotation: TQuat::IDENTITY,
+        };
+        let (angaccel_limit, angvel_limit) = (TVec3::new(4., 4., 8.), TVec3::ONE * 3.);
+        let dt = 1. / 60.;
+
+        let mut world = World::new();
+        world.insert_resource(Time::default());
+        let pursuer = world.spawn().id();
+        let (mut pursuer_pos, pursuer_linvel) = (TVec3::Z * 400., TVec3::Z * -100.);
+        let pursuer_contact = |pos| Contact {
+            entt: pursuer,
+            pos,
+            linvel: pursuer_linvel,
+            faction: None,
+            presence: ScanPresence::Boid,
+            last_seen_secs: 0.,
+            in_sight: true,
+            track_quality: 1.,
+        };
+        let boid = world
+            .spawn()
+            .insert(GlobalTransform::identity())
+            .insert(Contacts::default())
+            .id();
+        let routine = kind.map(|kind| {
+            world
+                .spawn()
+                .insert_bundle(Bundle::new(
+                    Maneuver {
+                        kind,
+                        threat: Some(pursuer),
+                        period_secs: 0.75,
+                    },
+                    boid,
+                    Default::default(),
+                ))
+                .insert(ActiveSteeringRoutine)
+                .id()
+        });
+        let mut stage = SystemStage::single_threaded().with_system(update);
+
+        let mut state = PointMassState {
+            linvel: TVec3::Z * -50.,
+            ..Default::default()
+        };
+        let (mut rotation, mut angvel) = (TQuat::IDENTITY, TVec3::ZERO);
+        let mut closest = TReal::INFINITY;
+        for _ in 0..(9 * 60) {
+            world
+                .get_mut::<Contacts>(boid)
+                .unwrap()
+                .contacts
+                .insert(pursuer, pursuer_contact(pursuer_pos));
+            stage.run(&mut world);
+            let (lin_out, ang_out) = match routine {
+                Some(routine) => (
+                    world.get::<LinearRoutineOutput>(routine).unwrap().0,
+                    world.get::<AngularRoutineOutput>(routine).unwrap().0,
+                ),
+                // keep straight on
+                None => (state.linvel / model.linvel_limit, TVec3::ZERO),
+            };
+
+            // what steering_output_to_engine has the engine do
+            let desired_linvel = rotation * ((rotation.inverse() * lin_out) * model.linvel_limit);
+            PointMassModel { rotation, ..model }.step(&mut state, desired_linvel, dt);
+            step_rotation(
+                &mut rotation,
+                &mut angvel,
+                (ang_out * 10.).clamp(-angvel_limit, angvel_limit),
+                angaccel_limit,
+                dt,
+            );
+            pursuer_pos += pursuer_linvel * dt;
+            *world.get_mut::<GlobalTransform>(boid).unwrap() = GlobalTransform {
+                translation: state.pos,
+                rotation,
+                ..Default::default()
+            };
+            closest = closest.min(state.pos.distance(pursuer_pos));
+        }
+        closest
+    }
+
+    // flying straight on, the pursuer runs right into us
+    let straight = simulate(None);
+    assert!(straight < 5., "straight: {straight}");
+    // breaking off to the side puts some distance between us
+    let broken = simulate(Some(ManeuverKind::BreakTurn));
+    assert!(broken > 100., "broken: {broken}");
+}
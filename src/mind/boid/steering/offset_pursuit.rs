@@ -0,0 +1,52 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{behaviour::*, steering_behaviours};
+use crate::math::*;
+
+/// Holds a position relative to a moving leader, matching its velocity.
+#[derive(Debug, Clone, Component)]
+pub struct OffsetPursuit {
+    /// Must have a [`GlobalTransform`] and a rigid body.
+    pub leader: Entity,
+    /// In the leader's basis, e.g. `(-30, 0, 0)` for 30m off the port side.
+    pub offset: TVec3,
+    pub avail_accel: TVec3,
+    pub linvel_limit: TVec3,
+}
+
+pub type Bundle = BehaviourBundle<OffsetPursuit>;
+
+impl SteeringBehaviour for OffsetPursuit {
+    type State = Stateless;
+    type Outputs = LinOnly;
+
+    fn update(&self, _: &mut Stateless, ctx: &SteeringCtx) -> SteeringOutput {
+        let (leader_xform, leader_vel) = match (
+            ctx.xforms.get(self.leader),
+            ctx.vels.get(self.leader),
+        ) {
+            (Ok(xform), Ok(vel)) => (xform, vel),
+            (Err(err), _) | (_, Err(err)) => {
+                tracing::error!(?err, leader = ?self.leader, "leader not found for OffsetPursuit");
+                return Default::default();
+            }
+        };
+        SteeringOutput::lin(steering_behaviours::linvel_to_output(
+            ctx.xform.rotation,
+            steering_behaviours::offset_pursuit(
+                ctx.xform.translation,
+                ctx.xform.rotation,
+                leader_xform.translation,
+                leader_xform.rotation,
+                leader_vel.linvel.into(),
+                leader_vel.angvel.into(),
+                self.offset,
+                self.avail_accel,
+                self.linvel_limit,
+            ),
+            self.linvel_limit,
+        ))
+    }
+}
@@ -0,0 +1,39 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{behaviour::*, steering_behaviours};
+use crate::math::*;
+
+/// Circles around a point on the plane the craft's currently moving in.
+#[derive(Debug, Clone, Component)]
+pub struct Orbit {
+    /// In world space.
+    pub center: TVec3,
+    /// In meters.
+    pub radius: TReal,
+    /// In m/s.
+    pub speed: TReal,
+}
+
+pub type Bundle = BehaviourBundle<Orbit>;
+
+impl SteeringBehaviour for Orbit {
+    type State = Stateless;
+    type Outputs = LinOnly;
+
+    fn update(&self, _: &mut Stateless, ctx: &SteeringCtx) -> SteeringOutput {
+        let linvel = steering_behaviours::orbit(
+            ctx.xform.translation,
+            ctx.linvel,
+            self.center,
+            self.radius,
+            self.speed,
+        );
+        SteeringOutput::lin(steering_behaviours::linvel_to_output(
+            ctx.xform.rotation,
+            linvel,
+            ctx.engine_config.linvel_limit,
+        ))
+    }
+}
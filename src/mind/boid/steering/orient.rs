@@ -0,0 +1,124 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{
+    steering_behaviours, ActiveSteeringRoutine, AngOnlyRoutineBundle, AngularRoutineOutput,
+    SteeringRoutine,
+};
+use crate::{craft::engine, math::*, mind::boid::BoidMindConfig};
+
+/// All vectors are in world basis.
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    Rotation {
+        rot: TQuat,
+    },
+    /// Face `fwd`, rolled to `up`.
+    Direction {
+        fwd: TVec3,
+        up: TVec3,
+    },
+    /// Face the object, rolled to `up`. Must have a [`GlobalTransform`].
+    Object {
+        entt: Entity,
+        up: TVec3,
+    },
+}
+
+/// Roll into turns like an aircraft would.
+#[derive(Debug, Clone, Copy)]
+pub struct Banking {
+    /// Radians of bank per rad/s of yaw.
+    pub gain: TReal,
+    /// In radians.
+    pub max_bank: TReal,
+}
+
+impl Default for Banking {
+    fn default() -> Self {
+        Self {
+            gain: 0.5,
+            max_bank: real::consts::FRAC_PI_4,
+        }
+    }
+}
+
+/// Matches a full orientation, forward and up, unlike [`super::face::Face`]
+/// which leaves the roll be.
+#[derive(Debug, Clone, Component)]
+pub struct Orient {
+    pub target: Target,
+    /// In rad/s per radian off the target.
+    pub proportional_gain: TVec3,
+    /// Against the craft's [`engine::AngularEngineState::velocity`].
+    pub derivative_gain: TVec3,
+    pub banking: Option<Banking>,
+}
+
+impl Orient {
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            proportional_gain: TVec3::ONE * 2.,
+            derivative_gain: TVec3::ONE * 0.3,
+            banking: None,
+        }
+    }
+}
+
+pub type Bundle = AngOnlyRoutineBundle<Orient>;
+
+pub fn update(
+    mut routines: Query<
+        (&Orient, &SteeringRoutine, &mut AngularRoutineOutput),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(
+        &GlobalTransform,
+        &engine::AngularEngineState,
+        &engine::EngineConfig,
+        &BoidMindConfig,
+    )>,
+    objects: Query<&GlobalTransform>,
+) {
+    for (param, routine, mut output) in routines.iter_mut() {
+        let (xform, ang_state, engine_config, config) = boids
+            .get(routine.boid_entt)
+            .expect_or_log("craft entt not found for routine");
+        let target = match param.target {
+            Target::Rotation { rot } => rot,
+            Target::Direction { fwd, up } => steering_behaviours::rotation_facing(fwd, up),
+            Target::Object { entt, up } => match objects.get(entt) {
+                Ok(obj_xform) => steering_behaviours::rotation_facing(
+                    obj_xform.translation - xform.translation,
+                    up,
+                ),
+                Err(err) => {
+                    tracing::error!(?err, "object not found for Orient routine");
+                    *output = Default::default();
+                    continue;
+                }
+            },
+        };
+        let target = match param.banking {
+            Some(banking) => steering_behaviours::bank_into_turn(
+                target,
+                ang_state.velocity.y,
+                banking.gain,
+                banking.max_bank,
+            ),
+            None => target,
+        };
+        let angvel = steering_behaviours::orientation_pd(
+            xform.rotation,
+            target,
+            ang_state.velocity,
+            param.proportional_gain,
+            param.derivative_gain,
+        )
+        .clamp(-engine_config.angvel_limit, engine_config.angvel_limit);
+        // the output gets scaled back up on its way to the engine
+        *output = (angvel / config.angular_input_multiplier).into();
+    }
+}
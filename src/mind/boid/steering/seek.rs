@@ -1,9 +1,6 @@
 use deps::*;
 
-use super::{
-    steering_behaviours, ActiveSteeringRoutine, LinOnlyRoutineBundle, LinearRoutineOutput,
-    SteeringRoutine,
-};
+use super::{behaviour::*, steering_behaviours};
 use crate::math::*;
 use bevy::prelude::*;
 
@@ -20,23 +17,20 @@ pub struct Seek {
     pub target: Target,
 }
 
-pub type Bundle = LinOnlyRoutineBundle<Seek>;
+pub type Bundle = BehaviourBundle<Seek>;
 
-pub fn update(
-    mut routines: Query<
-        (&Seek, &SteeringRoutine, &mut LinearRoutineOutput),
-        With<ActiveSteeringRoutine>,
-    >,
-    objects: Query<&GlobalTransform>,
-) {
-    for (param, routine, mut output) in routines.iter_mut() {
-        let xform = objects
-            .get(routine.boid_entt)
-            .expect_or_log("craft entt not found for routine");
-        let pos = match param.target {
-            Target::Object { entt } => objects.get(entt).unwrap_or_log().translation,
+impl SteeringBehaviour for Seek {
+    type State = Stateless;
+    type Outputs = LinOnly;
+
+    fn update(&self, _: &mut Stateless, ctx: &SteeringCtx) -> SteeringOutput {
+        let pos = match self.target {
+            Target::Object { entt } => ctx.xforms.get(entt).unwrap_or_log().translation,
             Target::Position { pos } => pos,
         };
-        *output = steering_behaviours::seek_position(xform.translation, pos).into();
+        SteeringOutput::lin(steering_behaviours::seek_position(
+            ctx.xform.translation,
+            pos,
+        ))
     }
 }
@@ -202,6 +202,18 @@ pub fn seek_position(current_pos: TVec3, target_pos: TVec3) -> TVec3 {
     (target_pos - current_pos).normalize()
 }
 
+/// The opposite of [`seek_position`], only panics within `panic_distance` of
+/// the threat. Zero beyond it.
+#[inline]
+pub fn flee_position(current_pos: TVec3, threat_pos: TVec3, panic_distance: TReal) -> TVec3 {
+    let away = current_pos - threat_pos;
+    if away.length_squared() > panic_distance * panic_distance {
+        return TVec3::ZERO;
+    }
+    // right on top of it, any way's better than none
+    away.try_normalize().unwrap_or(TVec3::Z)
+}
+
 #[inline]
 pub fn arrive_at_position(
     current_pos: TVec3,
@@ -228,6 +240,36 @@ pub fn arrive_at_position(
     target_offset.normalize() * ((target_speed + (max_speed - target_speed) * weight) / max_speed)
 }
 
+/// Linear velocity, in world space, that'll hold the craft at `offset`, given
+/// in the leader's basis, matching the velocity of that slot. See
+/// [`arrive_with_linvel`].
+#[inline]
+#[allow(clippy::too_many_arguments)]
+pub fn offset_pursuit(
+    current_pos: TVec3,
+    rotation: TQuat,
+    leader_pos: TVec3,
+    leader_rotation: TQuat,
+    leader_linvel: TVec3,
+    leader_angvel: TVec3,
+    offset: TVec3,
+    accel_limit: TVec3,
+    linvel_limit: TVec3,
+) -> TVec3 {
+    let world_offset = leader_rotation * offset;
+    // the slot swings about with the leader's turning
+    let slot_linvel = leader_linvel + leader_angvel.cross(world_offset);
+    arrive_with_linvel(
+        current_pos,
+        rotation,
+        leader_pos + world_offset,
+        slot_linvel,
+        slot_linvel,
+        accel_limit,
+        linvel_limit,
+    )
+}
+
 #[inline]
 pub fn find_intercept_pos(
     current_pos: TVec3,
@@ -254,6 +296,44 @@ pub fn intercept_target(
     )
 }
 
+/// Converts a world space linear velocity into a [`super::LinearRoutineOutput`],
+/// i.e. in fractions of the per axis `linvel_limit`.
+#[inline]
+pub fn linvel_to_output(rotation: TQuat, linvel: TVec3, linvel_limit: TVec3) -> TVec3 {
+    rotation * ((rotation.inverse() * linvel) / linvel_limit)
+}
+
+/// Linear velocity that'd have the craft circling `center` at `radius` on the
+/// plane it's currently moving in.
+#[inline]
+pub fn orbit(
+    current_pos: TVec3,
+    current_linvel: TVec3,
+    center: TVec3,
+    radius: TReal,
+    speed: TReal,
+) -> TVec3 {
+    // how hard to correct towards the desired radius, in m/s per meter off
+    const RADIAL_GAIN: TReal = 0.5;
+    let offset = current_pos - center;
+    let dst = offset.length();
+    if dst < TReal::EPSILON {
+        // anywhere's as good as another
+        return TVec3::X * speed;
+    }
+    let radial = offset / dst;
+    let mut normal = offset.cross(current_linvel);
+    if normal.length_squared() < TReal::EPSILON {
+        normal = radial.cross(TVec3::Y);
+        if normal.length_squared() < TReal::EPSILON {
+            normal = radial.cross(TVec3::X);
+        }
+    }
+    let tangent = normal.normalize().cross(radial);
+    let correction = (-radial * (dst - radius) * RADIAL_GAIN).clamp_length_max(speed);
+    tangent * speed + correction
+}
+
 /// Assumes the current craft's in the flock.
 #[inline]
 pub fn cohesion(current_pos: TVec3, flock_size: usize, flock_center_sum: TVec3) -> TVec3 {
@@ -290,7 +370,7 @@ pub fn allignment(current_vel: TVec3, flock_size: usize, flock_vel_sum: TVec3) -
 #[inline]
 pub fn separation(current_pos: TVec3, flock_positions: &[TVec3]) -> TVec3 {
     let mut steering = TVec3::ZERO;
-    if flock_positions.len() > 1 {
+    if !flock_positions.is_empty() {
         for craft_pos in flock_positions {
             // add in steering contribution
             // (opposite of the offset direction, divided once by distance
@@ -307,6 +387,103 @@ pub fn separation(current_pos: TVec3, flock_positions: &[TVec3]) -> TVec3 {
     steering
 }
 
+/// The half-space of velocities on the side `normal` points to.
+#[derive(Debug, Clone, Copy)]
+pub struct VelocityPlane {
+    pub point: TVec3,
+    pub normal: TVec3,
+}
+
+impl VelocityPlane {
+    #[inline]
+    pub fn contains(&self, linvel: TVec3) -> bool {
+        (linvel - self.point).dot(self.normal) >= 0.
+    }
+}
+
+/// The ORCA half-space of velocities that avoid colliding with a neighbour
+/// for `time_horizon` seconds. `responsibility` is the share of the avoidance
+/// we take on, 0.5 if the neighbour's doing the same.
+///
+/// Based on the RVO2-3D library by van den Berg et al.
+#[inline]
+#[allow(clippy::too_many_arguments)]
+pub fn orca_plane(
+    current_pos: TVec3,
+    current_linvel: TVec3,
+    other_pos: TVec3,
+    other_linvel: TVec3,
+    combined_radius: TReal,
+    time_horizon: TReal,
+    timestep: TReal,
+    responsibility: TReal,
+) -> VelocityPlane {
+    let rel_pos = other_pos - current_pos;
+    let rel_vel = current_linvel - other_linvel;
+    let dst_squared = rel_pos.length_squared();
+    let radius_squared = combined_radius * combined_radius;
+
+    let (normal, u) = if dst_squared > radius_squared {
+        // no collision yet
+        let inv_horizon = 1. / time_horizon;
+        let w = rel_vel - (rel_pos * inv_horizon);
+        let w_len_squared = w.length_squared();
+        let dot = w.dot(rel_pos);
+        if dot < 0. && dot * dot > radius_squared * w_len_squared {
+            // project on the cut-off sphere
+            let w_len = w_len_squared.sqrt();
+            let unit_w = w / w_len;
+            (unit_w, unit_w * ((combined_radius * inv_horizon) - w_len))
+        } else {
+            // project on the cone
+            let a = dst_squared;
+            let b = rel_pos.dot(rel_vel);
+            let c = rel_vel.length_squared()
+                - (rel_pos.cross(rel_vel).length_squared() / (dst_squared - radius_squared));
+            let t = (b + ((b * b) - (a * c)).max(0.).sqrt()) / a;
+            let ww = rel_vel - (rel_pos * t);
+            let ww_len = ww.length();
+            let unit_ww = ww.normalize_or_zero();
+            (unit_ww, unit_ww * ((combined_radius * t) - ww_len))
+        }
+    } else {
+        // already colliding, get out within the timestep
+        let inv_timestep = 1. / timestep;
+        let w = rel_vel - (rel_pos * inv_timestep);
+        let w_len = w.length();
+        let unit_w = w.normalize_or_zero();
+        (unit_w, unit_w * ((combined_radius * inv_timestep) - w_len))
+    };
+    VelocityPlane {
+        point: current_linvel + (u * responsibility),
+        normal,
+    }
+}
+
+/// The velocity closest to `preferred` that's in all the `planes` and under
+/// `max_speed`. Found by cyclic projection so it's only approximate when the
+/// planes are crowded and settles on a compromise if they're infeasible.
+#[inline]
+pub fn orca_linvel(preferred: TVec3, planes: &[VelocityPlane], max_speed: TReal) -> TVec3 {
+    const MAX_ITERATIONS: usize = 16;
+    let mut linvel = preferred.clamp_length_max(max_speed);
+    for _ in 0..MAX_ITERATIONS {
+        let mut satisfied = true;
+        for plane in planes {
+            let depth = (plane.point - linvel).dot(plane.normal);
+            if depth > 0. {
+                linvel += plane.normal * depth;
+                satisfied = false;
+            }
+        }
+        linvel = linvel.clamp_length_max(max_speed);
+        if satisfied {
+            break;
+        }
+    }
+    linvel
+}
+
 /// Based on Craig Reynold's OpenSteer
 #[inline]
 pub fn avoid_obstacle_seblague(
@@ -357,6 +534,122 @@ pub fn avoid_obstacle_seblague(
     cast_root
 }
 
+/// Result of probing a direction for [`escape_direction`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Probe {
+    Clear,
+    /// With the distance to the obstruction.
+    Obstructed {
+        toi: TReal,
+    },
+    /// Couldn't tell, e.g. out of raycast budget.
+    Unknown,
+}
+
+/// Goes through `directions` in order of deviation from `desired` and returns
+/// the first one `probe` reports clear. If everything's obstructed, we're boxed
+/// in and it backs out along the direction with the farthest obstruction.
+/// Returns `None` if nothing clear was found but some directions are unknown.
+pub fn escape_direction(
+    desired: TVec3,
+    directions: &[TVec3],
+    probe: &mut dyn FnMut(usize, TVec3) -> Probe,
+) -> Option<TVec3> {
+    let mut order = (0..directions.len()).collect::<smallvec::SmallVec<[usize; 64]>>();
+    order.sort_by(|a, b| {
+        desired
+            .dot(directions[*b])
+            .partial_cmp(&desired.dot(directions[*a]))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut farthest: Option<(TReal, TVec3)> = None;
+    let mut any_unknown = false;
+    for idx in order {
+        let dir = directions[idx];
+        match probe(idx, dir) {
+            Probe::Clear => return Some(dir),
+            Probe::Obstructed { toi } => {
+                if farthest.map(|(far, _)| toi > far).unwrap_or(true) {
+                    farthest = Some((toi, dir));
+                }
+            }
+            Probe::Unknown => any_unknown = true,
+        }
+    }
+    if any_unknown {
+        None
+    } else {
+        farthest.map(|(_, dir)| dir)
+    }
+}
+
+/// A rotation with its forward, -Z, along `fwd` and rolled so that its up's as
+/// close to `up` as it can be.
+#[inline]
+pub fn rotation_facing(fwd: TVec3, up: TVec3) -> TQuat {
+    let back = -fwd.normalize();
+    let right = match up.cross(back).try_normalize() {
+        Some(right) => right,
+        // looking straight up or down, any roll will do
+        None => back.any_orthonormal_vector(),
+    };
+    let up = back.cross(right);
+    TQuat::from_mat3(&Mat3::from_cols(right, up, back))
+}
+
+/// Angular velocity, in the local basis, that'll turn the craft from `rotation`
+/// to `target`. Proportional to the error along the shortest arc and damped by
+/// the current local `angvel`.
+#[inline]
+pub fn orientation_pd(
+    rotation: TQuat,
+    target: TQuat,
+    angvel: TVec3,
+    proportional_gain: TVec3,
+    derivative_gain: TVec3,
+) -> TVec3 {
+    let mut delta = rotation.inverse() * target;
+    // the other way round's shorter
+    if delta.w < 0. {
+        delta = -delta;
+    }
+    (proportional_gain * delta.to_scaled_axis()) - (derivative_gain * angvel)
+}
+
+/// Rolls `target` into the turn proportionally to the `yaw_rate`, in rad/s
+/// about the local Y, up to `max_bank` radians.
+#[inline]
+pub fn bank_into_turn(target: TQuat, yaw_rate: TReal, gain: TReal, max_bank: TReal) -> TQuat {
+    // turning left, i.e. about +Y, raises the right wing
+    target * TQuat::from_rotation_z((yaw_rate * gain).clamp(-max_bank, max_bank))
+}
+
+/// Nudges the wander `target`, a point on the unit sphere, by up to `jitter`
+/// per second and turns `heading` towards it at `turn_rate`. Returns the new
+/// target and heading, both normalized.
+pub fn wander_step(
+    rng: &mut impl rand::Rng,
+    target: TVec3,
+    heading: TVec3,
+    jitter: TReal,
+    turn_rate: TReal,
+    delta_secs: TReal,
+) -> (TVec3, TVec3) {
+    let nudge = TVec3::new(
+        rng.gen_range(-1.0..1.0),
+        rng.gen_range(-1.0..1.0),
+        rng.gen_range(-1.0..1.0),
+    );
+    let target = (target + (nudge * jitter * delta_secs))
+        .try_normalize()
+        .unwrap_or(heading);
+    let heading = heading
+        .lerp(target, (turn_rate * delta_secs).min(1.))
+        .try_normalize()
+        .unwrap_or(target);
+    (target, heading)
+}
+
 #[inline]
 pub fn time_to_change(cur_spd: TReal, target_spd: TReal, accel: TReal) -> TReal {
     // a = (vf - vi) / t
@@ -382,6 +675,76 @@ pub fn dst_to_change(cur_spd: TReal, target_spd: TReal, accel: TReal) -> TReal {
     dist.abs()
 }
 
+#[test]
+fn flee_panic_distance() {
+    let threat = TVec3::new(0., 0., -100.);
+    assert_eq!(flee_position(TVec3::ZERO, threat, 50.), TVec3::ZERO);
+    assert_eq!(flee_position(TVec3::ZERO, threat, 150.), TVec3::Z);
+    assert_eq!(flee_position(threat, threat, 150.).length(), 1.);
+}
+
+#[test]
+fn orientation_pd_convergence() {
+    /// Simulates a rigid body whose angular driver tracks the desired local
+    /// angular velocity under the acceleration limit. Returns the final angle
+    /// off the target, the final angular speed and the largest angle off the
+    /// target after first getting within 5 degrees of it.
+    fn simulate(start: TQuat, target: TQuat, angvel: TVec3) -> (TReal, TReal, TReal) {
+        let accel_limit = TVec3::new(4., 4., 8.);
+        let angvel_limit = TVec3::ONE * 3.;
+        let dt = 1. / 60.;
+        let (mut rotation, mut angvel) = (start, angvel);
+        let mut overshoot: Option<TReal> = None;
+        for _ in 0..(10 * 60) {
+            let desired =
+                orientation_pd(rotation, target, angvel, TVec3::ONE * 2., TVec3::ONE * 0.3)
+                    .clamp(-angvel_limit, angvel_limit);
+            crate::craft::trajectory::step_rotation(
+                &mut rotation,
+                &mut angvel,
+                desired,
+                accel_limit,
+                dt,
+            );
+            let off = rotation.angle_between(target);
+            overshoot = match overshoot {
+                Some(max) => Some(max.max(off)),
+                None if off < (5. as TReal).to_radians() => Some(0.),
+                None => None,
+            };
+        }
+        (
+            rotation.angle_between(target),
+            angvel.length(),
+            overshoot.unwrap_or(TReal::INFINITY),
+        )
+    }
+    let one_deg = (1. as TReal).to_radians();
+
+    // full orientation, roll included
+    let target = rotation_facing(TVec3::new(1., 0.5, -0.3), TVec3::new(0., 1., 1.));
+    let (off, spd, overshoot) = simulate(TQuat::IDENTITY, target, TVec3::ZERO);
+    assert!(off < one_deg * 0.1, "off: {off}");
+    assert!(spd < 0.01, "spd: {spd}");
+    assert!(overshoot < one_deg * 5., "overshoot: {overshoot}");
+    // facing is what the target says it is
+    assert!((target * -TVec3::Z).dot(TVec3::new(1., 0.5, -0.3).normalize()) > 0.999);
+
+    // turned all the way around while already spinning the wrong way
+    let (off, spd, _) = simulate(
+        TQuat::from_rotation_y(3.),
+        TQuat::IDENTITY,
+        TVec3::new(0., 1., 0.5),
+    );
+    assert!(off < one_deg * 0.1, "off: {off}");
+    assert!(spd < 0.01, "spd: {spd}");
+
+    // banking into a left turn raises the right wing
+    let banked = bank_into_turn(TQuat::IDENTITY, 1., 0.5, 0.8);
+    assert!((banked * TVec3::X).y > 0.4);
+    assert!((banked * -TVec3::Z).dot(-TVec3::Z) > 0.999);
+}
+
 #[test]
 fn zmblo() {
     let to_target: Vec3 = [10., 10., 0.].into();
@@ -408,3 +771,171 @@ fn zmblo() {
     let out2 = b.length();
     println!("{out:?},{out2:?}");
 }
+
+#[test]
+fn offset_pursuit_holds_slot() {
+    // leader flying a wide circle, hold 30m off its port side
+    let accel_limit = TVec3::new(10., 10., 20.);
+    let linvel_limit = TVec3::ONE * 100.;
+    let offset = TVec3::new(-30., 0., 0.);
+    let leader_angvel = TVec3::Y * 0.05;
+    let dt = 1. / 60.;
+    let (mut leader_pos, mut leader_rot) = (TVec3::new(0., 0., -200.), TQuat::IDENTITY);
+    let model = crate::craft::trajectory::PointMassModel {
+        accel_limit,
+        linvel_limit,
+        limit_linvel: true,
+        rotation: TQuat::IDENTITY,
+    };
+    let mut state = crate::craft::trajectory::PointMassState::default();
+    for _ in 0..(40 * 60) {
+        let leader_linvel = leader_rot * (TVec3::Z * -20.);
+        let desired = offset_pursuit(
+            state.pos,
+            TQuat::IDENTITY,
+            leader_pos,
+            leader_rot,
+            leader_linvel,
+            leader_angvel,
+            offset,
+            accel_limit,
+            linvel_limit,
+        );
+        model.step(&mut state, desired, dt);
+        leader_pos += leader_linvel * dt;
+        leader_rot = TQuat::from_scaled_axis(leader_angvel * dt) * leader_rot;
+    }
+    let slot = leader_pos + (leader_rot * offset);
+    let slot_linvel = (leader_rot * (TVec3::Z * -20.)) + leader_angvel.cross(leader_rot * offset);
+    let pos_err = state.pos.distance(slot);
+    assert!(pos_err < 1., "pos_err: {pos_err}");
+    let vel_err = state.linvel.distance(slot_linvel);
+    assert!(vel_err < 0.5, "vel_err: {vel_err}");
+}
+
+#[test]
+fn orca_head_on_and_crossing() {
+    // agents swapping places through the origin, everyone avoiding reciprocally
+    const RADIUS: TReal = 5.;
+    const SPEED: TReal = 20.;
+    let starts = [
+        TVec3::X * 100.,
+        -TVec3::X * 100.,
+        TVec3::Z * 100.,
+        -TVec3::Z * 100.,
+        TVec3::new(70., 0., 70.),
+        TVec3::new(-70., 0., -70.),
+    ];
+    let goals = starts.map(|pos| -pos);
+    let mut positions = starts;
+    let mut linvels = [TVec3::ZERO; 6];
+    let dt = 1. / 30.;
+    let mut min_dst = TReal::INFINITY;
+    for _ in 0..(30 * 20) {
+        let mut next = linvels;
+        for ii in 0..positions.len() {
+            let preferred = ((goals[ii] - positions[ii]) * 0.5).clamp_length_max(SPEED);
+            let planes = (0..positions.len())
+                .filter(|jj| *jj != ii)
+                .map(|jj| {
+                    orca_plane(
+                        positions[ii],
+                        linvels[ii],
+                        positions[jj],
+                        linvels[jj],
+                        RADIUS * 2.,
+                        5.,
+                        dt,
+                        0.5,
+                    )
+                })
+                .collect::<Vec<_>>();
+            next[ii] = orca_linvel(preferred, &planes, SPEED);
+        }
+        linvels = next;
+        for ii in 0..positions.len() {
+            positions[ii] += linvels[ii] * dt;
+        }
+        for ii in 0..positions.len() {
+            for jj in (ii + 1)..positions.len() {
+                min_dst = min_dst.min(positions[ii].distance(positions[jj]));
+            }
+        }
+    }
+    // allow some slack for the approximate solver
+    assert!(min_dst > RADIUS * 2. * 0.9, "min_dst: {min_dst}");
+    for (pos, goal) in positions.iter().zip(goals.iter()) {
+        assert!(pos.distance(*goal) < 5., "{pos} didn't make it to {goal}");
+    }
+}
+
+#[test]
+fn escape_direction_search() {
+    let directions = crate::utils::points_on_sphere(64);
+    let desired = -TVec3::Z;
+
+    // a wall ahead blocking everything within ~70 degrees of desired
+    let mut wall = |_, dir: TVec3| {
+        if dir.dot(desired) > 0.35 {
+            Probe::Obstructed { toi: 10. }
+        } else {
+            Probe::Clear
+        }
+    };
+    let dir = escape_direction(desired, &directions[..], &mut wall).unwrap();
+    assert!(dir.dot(desired) <= 0.35);
+    // and it's the least deviating one of the clear ones
+    let best = directions
+        .iter()
+        .filter(|d| d.dot(desired) <= 0.35)
+        .map(|d| d.dot(desired))
+        .fold(TReal::NEG_INFINITY, TReal::max);
+    assert!((dir.dot(desired) - best).abs() < 1e-6);
+
+    // boxed in, backs out the way with the most room
+    let dir = escape_direction(desired, &directions[..], &mut |_, dir: TVec3| {
+        Probe::Obstructed {
+            toi: 10. - (dir.dot(desired) * 5.),
+        }
+    })
+    .unwrap();
+    assert!(dir.dot(desired) < -0.9);
+
+    // undecided if we didn't get to probe everything
+    let mut budget = 4;
+    assert!(escape_direction(desired, &directions[..], &mut |_, _| {
+        if budget > 0 {
+            budget -= 1;
+            Probe::Obstructed { toi: 10. }
+        } else {
+            Probe::Unknown
+        }
+    })
+    .is_none());
+}
+
+#[test]
+fn wander_step_is_smooth_and_deterministic() {
+    use rand::SeedableRng;
+    let walk = |seed| {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let (mut target, mut heading) = (TVec3::Z, TVec3::Z);
+        (0..600)
+            .map(|_| {
+                let prev = heading;
+                let (new_target, new_heading) =
+                    wander_step(&mut rng, target, heading, 0.5, 0.5, 1. / 60.);
+                target = new_target;
+                heading = new_heading;
+                assert!((heading.length() - 1.).abs() < 1e-4);
+                assert!(prev.dot(heading) > 0.99, "{prev} -> {heading}");
+                heading
+            })
+            .collect::<Vec<_>>()
+    };
+    let walk_a = walk(42);
+    assert_eq!(walk_a, walk(42));
+    assert_ne!(walk_a, walk(43));
+    // actually goes somewhere
+    assert!(walk_a.last().unwrap().dot(TVec3::Z) < 0.999);
+}
@@ -0,0 +1,79 @@
+use deps::*;
+
+use bevy::prelude::*;
+use rand::SeedableRng;
+
+use super::{behaviour::*, steering_behaviours};
+use crate::math::*;
+
+/// Meanders about aimlessly. Heading changes are smoothed and seeded so the
+/// same seed wanders the same way.
+#[derive(Debug, Clone, Component)]
+pub struct Wander {
+    pub seed: u64,
+    /// How far the wander target gets nudged on the unit sphere per second.
+    pub jitter: TReal,
+    /// How quickly the heading catches up to the wander target.
+    pub turn_rate: TReal,
+    /// As a fraction of the linvel limit.
+    pub speed: TReal,
+}
+
+impl Wander {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            jitter: 0.5,
+            turn_rate: 0.5,
+            speed: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Component)]
+pub struct WanderState {
+    /// Seeded on the first update.
+    pub rng: Option<rand::rngs::StdRng>,
+    pub target: TVec3,
+    /// In world space.
+    pub heading: TVec3,
+}
+
+impl WanderState {
+    /// Have the wandering pick up from wherever the craft's currently headed.
+    pub fn reset_heading(&mut self) {
+        self.heading = TVec3::ZERO;
+    }
+}
+
+pub type Bundle = BehaviourBundle<Wander>;
+
+impl SteeringBehaviour for Wander {
+    type State = WanderState;
+    type Outputs = LinOnly;
+
+    fn update(&self, state: &mut WanderState, ctx: &SteeringCtx) -> SteeringOutput {
+        let seed = self.seed;
+        let rng = state
+            .rng
+            .get_or_insert_with(|| rand::rngs::StdRng::seed_from_u64(seed));
+        if state.heading == TVec3::ZERO {
+            state.heading = ctx
+                .linvel
+                .try_normalize()
+                .unwrap_or_else(|| ctx.xform.forward());
+            state.target = state.heading;
+        }
+        let (target, heading) = steering_behaviours::wander_step(
+            rng,
+            state.target,
+            state.heading,
+            self.jitter,
+            self.turn_rate,
+            ctx.time.delta_seconds(),
+        );
+        state.target = target;
+        state.heading = heading;
+        SteeringOutput::lin(heading * self.speed)
+    }
+}
@@ -8,7 +8,13 @@ use crate::mind::*;
 
 pub mod attack_persue;
 pub mod custom;
+pub mod dogfight;
+pub mod engage;
+pub mod escort;
+pub mod evade;
 pub mod form;
+pub mod patrol_volume;
+pub mod retreat;
 pub mod run_circuit;
 
 #[derive(Debug, Component, Default)]
@@ -141,6 +147,9 @@ impl BoidStrategy {
 #[derive(Debug, Default, Clone, Component, Reflect, Inspectable)]
 pub struct CurrentBoidStrategy {
     pub strategy: Option<Entity>,
+    /// Strategy put on hold by an interruption such as [`evade::Evade`], to be
+    /// resumed once it's over.
+    pub suspended: Option<Entity>,
 }
 
 /// This system assigns the [`SteeringRoutineComposer`] emitted by the strategy to the craft
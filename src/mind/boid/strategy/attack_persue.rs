@@ -1,27 +1,36 @@
 use deps::*;
 
 use bevy::prelude::*;
-use bevy_rapier3d::prelude::*;
+use educe::Educe;
 
 use super::{ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput};
 use crate::{
     craft::*,
     math::*,
-    mind::{boid::steering::*, sensors::*},
+    mind::{
+        boid::{steering::*, BoidMindConfig},
+        bt,
+        sensors::{radar::Contacts, *},
+    },
 };
 
 #[derive(Debug, Clone, Component)]
 pub struct AttackPersue {
-    pub quarry_rb: RigidBodyHandle,
+    /// Tracked through the craft's [`Contacts`].
+    pub quarry: Entity,
     pub attacking_range: TReal,
 }
 
-#[derive(Debug, Clone, Default, Component)]
+#[derive(Debug, Clone, Component, Educe)]
+#[educe(Default)]
 pub struct AttackPersueState {
     pub composer_routine: Option<Entity>,
     pub intercept_routine: Option<Entity>,
     pub intercept_wpn_speed: Option<Entity>,
     pub avoid_collision: Option<Entity>,
+    pub repair_requested: bool,
+    #[educe(Default(expression = "attack_tree()"))]
+    pub tree: bt::BehaviourTree<AttackCtx>,
 }
 
 pub type Bundle = BoidStrategyBundleExtra<AttackPersue, AttackPersueState>;
@@ -57,10 +66,11 @@ pub fn butler(
 
         let raycast_toi_modifier = dim.max_element();
         let cast_shape_radius = raycast_toi_modifier * 0.5;
-        let avoid_collision = routines
-            .kind::<avoid_collision::AvoidCollision>()
-            .map(|v| v[0])
-            .unwrap_or_else(|| {
+        let avoid_collision = reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
                 commands
                     .spawn()
                     .insert_bundle(avoid_collision::Bundle::new(
@@ -72,23 +82,25 @@ pub fn butler(
                         Default::default(),
                     ))
                     .id()
-            });
+            },
+        );
         let intercept_routine = commands
             .spawn()
             .insert_bundle(intercept::Bundle::new(
                 intercept::Intercept {
-                    quarry_rb: param.quarry_rb,
+                    target: intercept::Target::Contact(param.quarry),
                     linvel_limit: engine_config.linvel_limit,
                     speed: None,
                 },
                 strategy.boid_entt(),
             ))
+            .insert(RoutineOwners::new(entt))
             .id();
         let intercept_wpn_speed = commands
             .spawn()
             .insert_bundle(intercept::Bundle::new(
                 intercept::Intercept {
-                    quarry_rb: param.quarry_rb,
+                    target: intercept::Target::Contact(param.quarry),
                     linvel_limit: engine_config.linvel_limit,
                     speed: if wpns.avg_projectile_speed > 0. {
                         Some(wpns.avg_projectile_speed)
@@ -98,6 +110,7 @@ pub fn butler(
                 },
                 strategy.boid_entt(),
             ))
+            .insert(RoutineOwners::new(entt))
             .id();
         let compose = commands
             .spawn()
@@ -109,6 +122,7 @@ pub fn butler(
                 },
                 strategy.boid_entt(),
             ))
+            .insert(RoutineOwners::new(entt))
             .id();
 
         state.intercept_routine = Some(intercept_routine);
@@ -137,68 +151,138 @@ pub fn butler(
     }
 }
 
-#[allow(clippy::if_same_then_else)]
+/// What the [`attack_tree`] gets to look at.
+pub struct AttackCtx {
+    pub xform: GlobalTransform,
+    pub has_quarry: bool,
+    /// Position of the quarry if it's in sight.
+    pub quarry_pos: Option<TVec3>,
+    pub attacking_range: TReal,
+    pub intercept_routine: Option<Entity>,
+    /// Intercepts using the weapons' projectile speed as opposed to the craft's.
+    pub intercept_wpn_speed: Option<Entity>,
+    pub requests: bt::StrategyRequests,
+}
+
+impl bt::StrategyTreeCtx for AttackCtx {
+    #[inline]
+    fn requests(&mut self) -> &mut bt::StrategyRequests {
+        &mut self.requests
+    }
+}
+
+impl AttackCtx {
+    #[inline]
+    fn quarry_fwdness(&self) -> Option<TReal> {
+        self.quarry_pos.map(|pos| {
+            self.xform
+                .forward()
+                .dot((pos - self.xform.translation).normalize())
+        })
+    }
+}
+
+/// Decides how to go about attacking the quarry.
+pub fn attack_tree() -> bt::BehaviourTree<AttackCtx> {
+    use bt::*;
+    // take action based on relative direction of quarry
+    const DIRECTION_DETERMINATION_COS_THRESHOLD: TReal = 0.707;
+    let in_range = Node::condition(|ctx: &AttackCtx| {
+        ctx.quarry_pos
+            .map(|pos| {
+                (pos - ctx.xform.translation).length_squared()
+                    <= ctx.attacking_range * ctx.attacking_range
+            })
+            .unwrap_or(false)
+    });
+    let ahead = Node::condition(|ctx: &AttackCtx| {
+        ctx.quarry_fwdness()
+            .map(|fwdness| fwdness > DIRECTION_DETERMINATION_COS_THRESHOLD)
+            .unwrap_or(false)
+    });
+    let lined_up = Node::condition(|ctx: &AttackCtx| {
+        ctx.quarry_fwdness()
+            .map(|fwdness| 1. - fwdness < crate::math::real::EPSILON * 10_000.)
+            .unwrap_or(false)
+    });
+    BehaviourTree::new(Node::selector([
+        // close by and ahead, lead with the weapons and fire once lined up
+        Node::sequence([
+            in_range,
+            ahead,
+            switch_routine(|ctx: &AttackCtx| ctx.intercept_wpn_speed),
+            Node::selector([
+                Node::sequence([lined_up, fire_weapons(true)]),
+                fire_weapons(false),
+            ]),
+        ]),
+        // beyond range, aside, behind or out of sight
+        Node::sequence([
+            Node::condition(|ctx: &AttackCtx| ctx.has_quarry),
+            switch_routine(|ctx: &AttackCtx| ctx.intercept_routine),
+            fire_weapons(false),
+        ]),
+        Node::sequence([clear_routine(), fire_weapons(false)]),
+    ]))
+}
+
 pub fn update(
+    mut commands: Commands,
     mut strategies: Query<
         (
             &AttackPersue,
             &BoidStrategy,
-            &AttackPersueState,
+            &mut AttackPersueState,
             &mut BoidStrategyOutput,
         ),
         With<ActiveBoidStrategy>,
     >,
     crafts: Query<&GlobalTransform>, // crafts
-    mut composers: Query<(&mut compose::Compose,)>,
+    boids: Query<(&attire::CraftIntegrity, &BoidMindConfig, &Contacts)>,
+    mut composers: Query<&mut compose::Compose>,
+    mut repair_requests: EventWriter<repair::RepairRequestEvent>,
+    time: Res<Time>,
 ) {
-    for (param, strategy, state, mut out) in strategies.iter_mut() {
+    let now = time.seconds_since_startup();
+    for (param, strategy, mut state, mut out) in strategies.iter_mut() {
         let xform = crafts
             .get(strategy.boid_entt())
             .expect_or_log("craft xform not found for CraftStrategy boid_entt");
-        let quarry_xform = crafts
-            .get(param.quarry_rb.entity())
-            .expect_or_log("quarry_xform not found for on AttackPersue strategy");
-
-        let target_distance_squared =
-            (quarry_xform.translation - xform.translation).length_squared();
-        let target_direction = (quarry_xform.translation - xform.translation).normalize();
+        let (integrity, config, contacts) = boids
+            .get(strategy.boid_entt())
+            .expect_or_log("craft not found for CraftStrategy boid_entt");
+        if !state.repair_requested && integrity.is_below(config.repair_request_threshold) {
+            state.repair_requested = true;
+            repair_requests.send(repair::RepairRequestEvent {
+                boid_entt: strategy.boid_entt(),
+            });
+        }
+        let mut ctx = AttackCtx {
+            xform: *xform,
+            has_quarry: true,
+            // hold fire until the quarry's back on the radar
+            // dead reckoned, same as what the intercept routines aim at
+            quarry_pos: match contacts.get(param.quarry) {
+                Some(contact) if contact.in_sight => Some(contact.predicted_pos(now)),
+                _ => None,
+            },
+            attacking_range: param.attacking_range,
+            intercept_routine: state.intercept_routine,
+            intercept_wpn_speed: state.intercept_wpn_speed,
+            requests: Default::default(),
+        };
+        state.tree.tick(&mut ctx);
 
-        let (mut composer,) = composers
+        let mut composer = composers
             .get_mut(state.composer_routine.unwrap_or_log())
             .unwrap_or_log();
-
-        // if beyond range
-        let (fire_wpns, second_routine) =
-            if target_distance_squared > (param.attacking_range * param.attacking_range) {
-                // intercept
-                (false, state.intercept_routine.unwrap_or_log())
-            } else {
-                // take action based on relative direction of quarry
-                const DIRECTION_DETERMINATION_COS_THRESHOLD: TReal = 0.707;
-                let fwdness = xform.forward().dot(target_direction);
-                // ahead
-                if fwdness > DIRECTION_DETERMINATION_COS_THRESHOLD {
-                    (
-                        1. - fwdness < crate::math::real::EPSILON * 10_000.,
-                        state.intercept_wpn_speed.unwrap_or_log(),
-                    )
-                }
-                // aside
-                else if fwdness < -DIRECTION_DETERMINATION_COS_THRESHOLD {
-                    (false, state.intercept_routine.unwrap_or_log())
-                }
-                // behind
-                else {
-                    (false, state.intercept_routine.unwrap_or_log())
-                }
-            };
-        out.fire_weapons = fire_wpns;
-        match &mut composer.composer {
-            compose::SteeringRoutineComposer::PriorityOverride { routines } => {
-                routines[1] = second_routine;
-            }
-            _ => unreachable!(),
-        }
+        ctx.requests.apply(
+            &mut commands,
+            strategy.boid_entt(),
+            &mut out,
+            &mut composer,
+            1,
+        );
     }
 }
 /*
@@ -70,12 +70,19 @@ pub fn butler(
                 compose::SteeringRoutineComposer::PriorityOverride { routines }
             }
         };
+        for routine in composer.all_routines() {
+            commands.add(ClaimRoutine {
+                routine,
+                owner: entt,
+            });
+        }
         let compose = commands
             .spawn()
             .insert_bundle(compose::Bundle::new(
                 compose::Compose { composer },
                 strategy.boid_entt(),
             ))
+            .insert(RoutineOwners::new(entt))
             .id();
         *out = BoidStrategyOutput {
             steering_routine: Some(compose),
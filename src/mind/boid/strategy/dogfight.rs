@@ -0,0 +1,574 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{
+        boid::steering::*,
+        flock::blackboard::FlockBlackboards,
+        sensors::{radar::Contacts, *},
+    },
+};
+
+/// Maneuvers that make use of facing being independent of velocity.
+#[derive(Debug, Clone, Copy)]
+pub enum DogfightManeuver {
+    /// Face backwards while keeping the current velocity, to fire on pursuers.
+    FlipAndBurn,
+    /// Fly past the quarry `pass_offset` meters to its side while tracking it.
+    StrafingRun { pass_offset: TReal },
+    /// Circle the quarry at `radius` while tracking it.
+    OrbitAndShoot { radius: TReal, speed: TReal },
+    /// Cut thrust and aim freely.
+    Drift,
+}
+
+#[derive(Debug, Clone, Component)]
+pub struct Dogfight {
+    /// Tracked through the craft's [`Contacts`].
+    pub quarry: Entity,
+    pub maneuver: DogfightManeuver,
+    pub attacking_range: TReal,
+    /// Weapons are fired when the quarry's within a cone of this cosine of the half angle.
+    pub firing_cone_cos: TReal,
+}
+
+impl DogfightManeuver {
+    /// Which way to face during the maneuver.
+    pub fn face_dir(&self, linvel: TVec3, quarry_dir: TVec3) -> TVec3 {
+        match self {
+            DogfightManeuver::FlipAndBurn => {
+                let retrograde = -linvel.normalize_or_zero();
+                if retrograde == TVec3::ZERO {
+                    quarry_dir
+                } else {
+                    retrograde
+                }
+            }
+            _ => quarry_dir,
+        }
+    }
+}
+
+impl Dogfight {
+    pub fn new(quarry: Entity, maneuver: DogfightManeuver) -> Self {
+        Self {
+            quarry,
+            maneuver,
+            attacking_range: 300.,
+            firing_cone_cos: 0.99,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Component)]
+pub struct DogfightState {
+    pub composer_routine: Option<Entity>,
+    pub avoid_collision: Option<Entity>,
+    pub linear_routine: Option<Entity>,
+    pub face_routine: Option<Entity>,
+    /// The strafing run pass currently being flown.
+    pub pass: Option<StrafingPass>,
+}
+
+impl DogfightState {
+    /// The pass to fly, latching a new one only once we've flown past the
+    /// quarry, cleared it and turned back towards it.
+    pub fn strafing_pass(
+        &mut self,
+        pass_offset: TReal,
+        linvel: TVec3,
+        to_quarry: TVec3,
+        up: TVec3,
+        right: TVec3,
+    ) -> StrafingPass {
+        let approaching = linvel.dot(to_quarry) > 0.;
+        if let Some(pass) = &mut self.pass {
+            if !approaching && to_quarry.length() > pass_offset * 2. {
+                pass.cleared = true;
+            }
+            if approaching && pass.cleared {
+                self.pass = None;
+            }
+        }
+        *self.pass.get_or_insert_with(|| {
+            let dir = to_quarry.normalize_or_zero();
+            let side = dir.cross(up);
+            StrafingPass {
+                side: if side.length_squared() > real::EPSILON {
+                    side.normalize()
+                } else {
+                    right
+                },
+                dir,
+                cleared: false,
+            }
+        })
+    }
+}
+
+/// A single pass of a [`DogfightManeuver::StrafingRun`].
+#[derive(Debug, Clone, Copy)]
+pub struct StrafingPass {
+    /// Which side of the quarry to pass on. In world space.
+    pub side: TVec3,
+    /// Of the approach. In world space.
+    pub dir: TVec3,
+    /// Set once we've flown past the quarry and gotten clear of it.
+    pub cleared: bool,
+}
+
+impl StrafingPass {
+    /// How far past the quarry to aim, in multiples of the pass offset, so
+    /// that the run carries through.
+    pub const OVERRUN: TReal = 4.;
+
+    /// Where to head to during the pass.
+    #[inline]
+    pub fn aim(&self, quarry_pos: TVec3, pass_offset: TReal) -> TVec3 {
+        quarry_pos + (self.side * pass_offset) + (self.dir * pass_offset * Self::OVERRUN)
+    }
+}
+
+pub type Bundle = BoidStrategyBundleExtra<Dogfight, DogfightState>;
+
+pub fn butler(
+    mut commands: Commands,
+    mut added_strategies: Query<
+        (
+            Entity,
+            &Dogfight,
+            &BoidStrategy,
+            &mut DogfightState,
+            &mut BoidStrategyOutput,
+        ),
+        Added<Dogfight>,
+    >,
+    crafts: Query<(
+        &GlobalTransform,
+        &RigidBodyVelocityComponent,
+        &CraftDimensions,
+        &SteeringRoutinesIndex,
+    )>,
+) {
+    for (entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
+        let boid_entt = strategy.boid_entt();
+        let (xform, vel, dim, routines) = crafts
+            .get(boid_entt)
+            .expect_or_log("craft not found for BoidStrategy");
+
+        let raycast_toi_modifier = dim.max_element();
+        let cast_shape_radius = raycast_toi_modifier * 0.5;
+        let avoid_collision = reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
+                commands
+                    .spawn()
+                    .insert_bundle(avoid_collision::Bundle::new(
+                        avoid_collision::AvoidCollision::new(
+                            cast_shape_radius,
+                            raycast_toi_modifier,
+                        ),
+                        boid_entt,
+                        Default::default(),
+                    ))
+                    .id()
+            },
+        );
+        // the targets get updated every frame once we've got an eye on the quarry
+        let linear_routine = match param.maneuver {
+            DogfightManeuver::FlipAndBurn => commands
+                .spawn()
+                .insert_bundle(cruise::Bundle::new(
+                    cruise::Cruise {
+                        linvel: Some(vel.linvel.into()),
+                    },
+                    boid_entt,
+                ))
+                .insert(RoutineOwners::new(entt))
+                .id(),
+            DogfightManeuver::StrafingRun { .. } => commands
+                .spawn()
+                .insert_bundle(seek::Bundle::new(
+                    seek::Seek {
+                        target: seek::Target::Position {
+                            pos: xform.translation + xform.forward(),
+                        },
+                    },
+                    boid_entt,
+                ))
+                .insert(RoutineOwners::new(entt))
+                .id(),
+            DogfightManeuver::OrbitAndShoot { radius, speed } => commands
+                .spawn()
+                .insert_bundle(orbit::Bundle::new(
+                    orbit::Orbit {
+                        center: xform.translation + (xform.forward() * radius),
+                        radius,
+                        speed,
+                    },
+                    boid_entt,
+                ))
+                .insert(RoutineOwners::new(entt))
+                .id(),
+            DogfightManeuver::Drift => commands
+                .spawn()
+                .insert_bundle(cruise::Bundle::new(
+                    cruise::Cruise { linvel: None },
+                    boid_entt,
+                ))
+                .insert(RoutineOwners::new(entt))
+                .id(),
+        };
+        let face_routine = commands
+            .spawn()
+            .insert_bundle(face::Bundle::new(
+                face::Face {
+                    target: face::Target::Direction {
+                        dir: xform.forward(),
+                    },
+                },
+                boid_entt,
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+        let compose = commands
+            .spawn()
+            .insert_bundle(compose::Bundle::new(
+                compose::Compose {
+                    composer: compose::SteeringRoutineComposer::AvoidCollisionHelper {
+                        avoid_collision,
+                        routines: smallvec::smallvec![
+                            (Default::default(), linear_routine),
+                            (Default::default(), face_routine),
+                        ],
+                    },
+                },
+                boid_entt,
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+
+        state.avoid_collision = Some(avoid_collision);
+        state.linear_routine = Some(linear_routine);
+        state.face_routine = Some(face_routine);
+        state.composer_routine = Some(compose);
+
+        *out = BoidStrategyOutput {
+            steering_routine: Some(compose),
+            fire_weapons: false,
+        };
+        commands.entity(entt).insert(ActiveBoidStrategy);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update(
+    mut strategies: Query<
+        (
+            &Dogfight,
+            &BoidStrategy,
+            &mut DogfightState,
+            &mut BoidStrategyOutput,
+        ),
+        With<ActiveBoidStrategy>,
+    >,
+    boids: Query<(&GlobalTransform, &RigidBodyVelocityComponent, &Contacts)>,
+    mut faces: Query<&mut face::Face>,
+    mut seeks: Query<&mut seek::Seek>,
+    mut orbits: Query<&mut orbit::Orbit>,
+    blackboards: FlockBlackboards,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for (param, strategy, mut state, mut out) in strategies.iter_mut() {
+        let boid_entt = strategy.boid_entt();
+        let (xform, vel, contacts) = boids
+            .get(boid_entt)
+            .expect_or_log("craft not found for CraftStrategy boid_entt");
+        let quarry_pos = if let Some(contact) = contacts.get(param.quarry) {
+            contact.predicted_pos(now)
+        } else if let Some(track) = blackboards
+            .of_boid(boid_entt)
+            .and_then(|b| b.track(param.quarry))
+        {
+            track.pos
+        } else {
+            // lost track, keep at whatever we were doing
+            out.fire_weapons = false;
+            continue;
+        };
+        let pos = xform.translation;
+        let linvel = TVec3::from(vel.linvel);
+        let to_quarry = quarry_pos - pos;
+        let quarry_dir = to_quarry.normalize_or_zero();
+
+        match param.maneuver {
+            DogfightManeuver::StrafingRun { pass_offset } => {
+                let pass =
+                    state.strafing_pass(pass_offset, linvel, to_quarry, xform.up(), xform.right());
+                if let Some(mut seek) = state.linear_routine.and_then(|e| seeks.get_mut(e).ok()) {
+                    seek.target = seek::Target::Position {
+                        pos: pass.aim(quarry_pos, pass_offset),
+                    };
+                }
+            }
+            DogfightManeuver::OrbitAndShoot { .. } => {
+                if let Some(mut orbit) = state.linear_routine.and_then(|e| orbits.get_mut(e).ok()) {
+                    orbit.center = quarry_pos;
+                }
+            }
+            DogfightManeuver::FlipAndBurn | DogfightManeuver::Drift => {}
+        }
+        let face_dir = param.maneuver.face_dir(linvel, quarry_dir);
+        if let Some(mut face) = state.face_routine.and_then(|e| faces.get_mut(e).ok()) {
+            face.target = face::Target::Direction { dir: face_dir };
+        }
+
+        out.fire_weapons = to_quarry.length_squared()
+            <= param.attacking_range * param.attacking_range
+            && xform.forward().dot(quarry_dir) > param.firing_cone_cos;
+    }
+}
+
+#[test]
+fn dogfight_maneuvers_point_mass() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use trajectory::{step_rotation, PointMassModel, PointMassState};
+
+    use crate::mind::boid::{strategy::BoidStrategyOutput, BoidMindConfig};
+    use crate::mind::sensors::radar::{Contact, ScanPresence};
+
+    /// Has a point mass fly the maneuver against a quarry sitting at the
+    /// origin, running the strategy, its routines and their composition. The
+    /// engine tracks its inputs under the acceleration limits. `inspect` gets
+    /// the position, linear velocity, rotation and state after every frame.
+    fn simulate(
+        maneuver: DogfightManeuver,
+        start_pos: TVec3,
+        start_linvel: TVec3,
+        secs: TReal,
+        mut inspect: impl FnMut(TVec3, TVec3, TQuat, &DogfightState),
+    ) {
+        let config = engine::EngineConfig {
+            acceleration_limit: TVec3::new(20., 20., 30.),
+            acceleration_limit_multiplier: 1.,
+            linvel_limit: TVec3::ONE * 50.,
+            ..Default::default()
+        };
+        let angaccel_limit = TVec3::new(4., 4., 8.);
+        let angvel_limit = TVec3::ONE * 3.;
+        let dt = 1. / 60.;
+
+        let mut world = World::new();
+        world.insert_resource(Time::default());
+        let quarry = world.spawn().id();
+        let boid = world
+            .spawn()
+            .insert(GlobalTransform::from_translation(start_pos))
+            .insert(RigidBodyVelocityComponent(RigidBodyVelocity {
+                linvel: start_linvel.to_array().into(),
+                ..Default::default()
+            }))
+            .insert(CraftDimensions::from(TVec3::ONE * 4.))
+            .insert(config.clone())
+            .insert(engine::LinearEngineState::default())
+            .insert(engine::AngularEngineState::default())
+            .insert(BoidMindConfig::default())
+            .insert(CurrentSteeringRoutine::default())
+            .insert(SteeringRoutinesIndex::default())
+            .insert(Contacts {
+                contacts: [(
+                    quarry,
+                    Contact {
+                        entt: quarry,
+                        pos: TVec3::ZERO,
+                        linvel: TVec3::ZERO,
+                        faction: None,
+                        presence: ScanPresence::Boid,
+                        last_seen_secs: 0.,
+                        in_sight: true,
+                        track_quality: 1.,
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                last_sweep_secs: 0.,
+            })
+            .id();
+        let strategy = world
+            .spawn()
+            .insert_bundle(Bundle::new(
+                Dogfight::new(quarry, maneuver),
+                boid,
+                Default::default(),
+            ))
+            .id();
+
+        // in the order the mind plugin has them
+        let mut stages = [
+            SystemStage::single_threaded().with_system(butler),
+            SystemStage::single_threaded().with_system(compose::butler),
+            SystemStage::single_threaded().with_system(update),
+            SystemStage::single_threaded()
+                .with_system(behaviour::update::<cruise::Cruise>)
+                .with_system(behaviour::update::<seek::Seek>)
+                .with_system(behaviour::update::<orbit::Orbit>)
+                .with_system(behaviour::update::<face::Face>),
+            SystemStage::single_threaded().with_system(compose::update),
+            SystemStage::single_threaded().with_system(steering_output_to_engine),
+        ];
+        let mut state = PointMassState {
+            pos: start_pos,
+            linvel: start_linvel,
+            ..Default::default()
+        };
+        let (mut rotation, mut angvel) = (TQuat::IDENTITY, TVec3::ZERO);
+        for _ in 0..((secs / dt) as usize) {
+            for (ii, stage) in stages.iter_mut().enumerate() {
+                stage.run(&mut world);
+                if ii == 0 {
+                    // what the strategy output manager does
+                    let routine = world
+                        .get::<BoidStrategyOutput>(strategy)
+                        .unwrap()
+                        .steering_routine;
+                    world
+                        .get_mut::<CurrentSteeringRoutine>(boid)
+                        .unwrap()
+                        .routine = routine;
+                }
+            }
+
+            let lin_input = world.get::<engine::LinearEngineState>(boid).unwrap().input;
+            PointMassModel::new(&config, rotation).step(&mut state, rotation * lin_input, dt);
+            let ang_input = world.get::<engine::AngularEngineState>(boid).unwrap().input;
+            step_rotation(
+                &mut rotation,
+                &mut angvel,
+                ang_input.clamp(-angvel_limit, angvel_limit),
+                angaccel_limit,
+                dt,
+            );
+
+            *world.get_mut::<GlobalTransform>(boid).unwrap() = GlobalTransform {
+                translation: state.pos,
+                rotation,
+                ..Default::default()
+            };
+            world
+                .get_mut::<RigidBodyVelocityComponent>(boid)
+                .unwrap()
+                .linvel = state.linvel.to_array().into();
+            inspect(
+                state.pos,
+                state.linvel,
+                rotation,
+                world.get::<DogfightState>(strategy).unwrap(),
+            );
+        }
+    }
+    let fwd = |rotation: TQuat| rotation * -TVec3::Z;
+    let start_linvel = TVec3::new(5., 0., -40.);
+
+    // turns around to face backwards, velocity untouched
+    let (mut linvel, mut rotation) = (TVec3::ZERO, TQuat::IDENTITY);
+    simulate(
+        DogfightManeuver::FlipAndBurn,
+        TVec3::new(0., 0., -100.),
+        start_linvel,
+        10.,
+        |_, v, r, _| {
+            linvel = v;
+            rotation = r;
+        },
+    );
+    let facing = fwd(rotation).dot(-start_linvel.normalize());
+    assert!(facing > 0.99, "facing: {facing}");
+    let vel_err = linvel.distance(start_linvel);
+    assert!(vel_err < 0.5, "vel_err: {vel_err}");
+
+    // coasts by the quarry while tracking it
+    let mut pos = TVec3::ZERO;
+    simulate(
+        DogfightManeuver::Drift,
+        TVec3::new(0., 0., 500.),
+        start_linvel,
+        5.,
+        |p, v, r, _| {
+            pos = p;
+            linvel = v;
+            rotation = r;
+        },
+    );
+    let facing = fwd(rotation).dot(-pos.normalize());
+    assert!(facing > 0.99, "facing: {facing}");
+    let vel_err = linvel.distance(start_linvel);
+    assert!(vel_err < 0.5, "vel_err: {vel_err}");
+
+    // settles into circling the quarry while tracking it
+    let (mut frame, mut radius_err, mut facing): (usize, TReal, TReal) = (0, 0., 1.);
+    simulate(
+        DogfightManeuver::OrbitAndShoot {
+            radius: 200.,
+            speed: 30.,
+        },
+        TVec3::new(0., 0., 300.),
+        TVec3::new(0., 0., -20.),
+        40.,
+        |p, _, r, _| {
+            frame += 1;
+            if frame > 30 * 60 {
+                radius_err = radius_err.max((p.length() - 200.).abs());
+                facing = facing.min(fwd(r).dot(-p.normalize()));
+            }
+        },
+    );
+    assert!(radius_err < 2., "radius_err: {radius_err}");
+    assert!(facing > 0.95, "facing: {facing}");
+
+    // a side's picked once per pass and only when clear of the quarry
+    let pass_offset = 30.;
+    let (mut picks, mut side) = (0, None);
+    let (mut passes, mut closest, mut near) = (vec![], TReal::INFINITY, false);
+    simulate(
+        DogfightManeuver::StrafingRun { pass_offset },
+        TVec3::new(0., 0., 500.),
+        TVec3::ZERO,
+        60.,
+        |p, _, _, state| {
+            let dst = p.length();
+            let pass_side = state.pass.map(|pass| pass.side);
+            if pass_side != side {
+                assert!(dst > pass_offset * 2., "picked mid pass at {dst}");
+                picks += 1;
+                side = pass_side;
+            }
+            closest = closest.min(dst);
+            if dst < pass_offset * 2. {
+                near = true;
+            } else if near {
+                near = false;
+                passes.push(closest);
+                closest = TReal::INFINITY;
+            }
+        },
+    );
+    assert!(passes.len() >= 3, "passes: {passes:?}");
+    assert!(
+        picks <= passes.len() + 1,
+        "picks: {picks} passes: {passes:?}"
+    );
+    for closest in passes {
+        assert!(
+            closest > pass_offset * 0.5 && closest < pass_offset * 1.5,
+            "closest: {closest}"
+        );
+    }
+}
@@ -0,0 +1,448 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use educe::Educe;
+
+use super::{
+    attack_persue::{attack_tree, AttackCtx},
+    ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput,
+};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{
+        boid::{steering::*, targeting::*},
+        bt,
+        flock::{blackboard::FlockBlackboard, CraftFlock},
+        sensors::{radar::Contacts, spatial::CraftSpatialIndex, *},
+        tribe::Factions,
+    },
+};
+
+/// Like [`super::attack_persue::AttackPersue`] but picks its own quarries.
+#[derive(Debug, Clone, Component)]
+pub struct Engage {
+    pub attacking_range: TReal,
+    /// Only hostiles within this radius are considered.
+    pub engagement_radius: TReal,
+    /// In seconds.
+    pub retarget_period: f64,
+    /// How much better a new target's score needs to be than the current one's
+    /// before switching, as a fraction.
+    pub switch_margin: TReal,
+    pub weights: TargetScoreWeights,
+}
+
+impl Default for Engage {
+    fn default() -> Self {
+        Self {
+            attacking_range: 300.,
+            engagement_radius: 2_000.,
+            retarget_period: 1.,
+            switch_margin: 0.25,
+            weights: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Educe)]
+#[educe(Default)]
+pub struct EngageState {
+    pub quarry: Option<Entity>,
+    pub last_retarget_secs: f64,
+    pub composer_routine: Option<Entity>,
+    pub intercept_routine: Option<Entity>,
+    pub intercept_wpn_speed: Option<Entity>,
+    pub avoid_collision: Option<Entity>,
+    #[educe(Default(expression = "attack_tree()"))]
+    pub tree: bt::BehaviourTree<AttackCtx>,
+}
+
+pub type Bundle = BoidStrategyBundleExtra<Engage, EngageState>;
+
+pub fn butler(
+    mut commands: Commands,
+    mut added_strategies: Query<
+        (
+            Entity,
+            &BoidStrategy,
+            &mut EngageState,
+            &mut BoidStrategyOutput,
+        ),
+        Added<Engage>,
+    >,
+    crafts: Query<(&CraftDimensions, &SteeringRoutinesIndex)>,
+) {
+    for (entt, strategy, mut state, mut out) in added_strategies.iter_mut() {
+        let (dim, routines) = crafts
+            .get(strategy.boid_entt())
+            .expect_or_log("craft not found for BoidStrategy");
+
+        let raycast_toi_modifier = dim.max_element();
+        let cast_shape_radius = raycast_toi_modifier * 0.5;
+        let avoid_collision = reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
+                commands
+                    .spawn()
+                    .insert_bundle(avoid_collision::Bundle::new(
+                        avoid_collision::AvoidCollision::new(
+                            cast_shape_radius,
+                            raycast_toi_modifier,
+                        ),
+                        strategy.boid_entt(),
+                        Default::default(),
+                    ))
+                    .id()
+            },
+        );
+        // the intercept routines get spawned once we've got a quarry
+        let compose = commands
+            .spawn()
+            .insert_bundle(compose::Bundle::new(
+                compose::Compose {
+                    composer: compose::SteeringRoutineComposer::PriorityOverride {
+                        routines: smallvec::smallvec![avoid_collision],
+                    },
+                },
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+
+        state.avoid_collision = Some(avoid_collision);
+        state.composer_routine = Some(compose);
+
+        *out = BoidStrategyOutput {
+            steering_routine: Some(compose),
+            fire_weapons: false,
+        };
+        commands.entity(entt).insert(ActiveBoidStrategy);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update(
+    mut commands: Commands,
+    mut strategies: Query<
+        (
+            Entity,
+            &Engage,
+            &BoidStrategy,
+            &mut EngageState,
+            &mut BoidStrategyOutput,
+        ),
+        With<ActiveBoidStrategy>,
+    >,
+    boids: Query<(
+        &GlobalTransform,
+        &RigidBodyVelocityComponent,
+        &Contacts,
+        &engine::EngineConfig,
+        Option<&DamageLedger>,
+        Option<&CraftFlock>,
+    )>,
+    weapons: Query<&CraftWeaponsIndex>,
+    // contacts linger after their crafts are gone
+    alive: Query<&attire::CraftIntegrity, With<RigidBodyVelocityComponent>>,
+    mut blackboards: Query<&mut FlockBlackboard>,
+    mut composers: Query<&mut compose::Compose>,
+    mut intercepts: Query<&mut intercept::Intercept>,
+    index: Res<CraftSpatialIndex>,
+    factions: Factions,
+    time: Res<Time>,
+    mut candidates: Local<Vec<TargetCandidate>>,
+) {
+    let now = time.seconds_since_startup();
+    for (entt, param, strategy, mut state, mut out) in strategies.iter_mut() {
+        let boid_entt = strategy.boid_entt();
+        let (xform, vel, contacts, engine_config, ledger, flock) = boids
+            .get(boid_entt)
+            .expect_or_log("craft not found for CraftStrategy boid_entt");
+
+        let is_alive = |entt| {
+            alive
+                .get(entt)
+                .map_or(false, |integrity| integrity.remaining > 0.)
+        };
+        let quarry_lost = match state.quarry {
+            Some(quarry) if !is_alive(quarry) => true,
+            Some(quarry) => {
+                contacts.get(quarry).is_none()
+                    && flock
+                        .and_then(|f| blackboards.get(f.0).ok())
+                        .and_then(|b| b.track(quarry))
+                        .is_none()
+            }
+            None => true,
+        };
+
+        // look for a better quarry
+        if quarry_lost || now - state.last_retarget_secs > param.retarget_period {
+            state.last_retarget_secs = now;
+            candidates.clear();
+            perceived_hostiles(
+                boid_entt,
+                xform.translation,
+                param.engagement_radius,
+                contacts,
+                flock.and_then(|f| blackboards.get(f.0).ok()),
+                ledger,
+                &index,
+                &factions,
+                &weapons,
+                &mut candidates,
+            );
+            candidates.retain(|candidate| is_alive(candidate.entt));
+            let linvel = TVec3::from(vel.linvel);
+            let mut current_score = None;
+            let mut best: Option<(Entity, TReal)> = None;
+            for candidate in candidates.iter() {
+                let score = score_target(&param.weights, xform.translation, linvel, candidate);
+                if Some(candidate.entt) == state.quarry {
+                    current_score = Some(score);
+                }
+                if best
+                    .map(|(_, best_score)| score > best_score)
+                    .unwrap_or(true)
+                {
+                    best = Some((candidate.entt, score));
+                }
+            }
+            let new_quarry = match (best, current_score) {
+                (Some((best_entt, best_score)), Some(current_score))
+                    if best_score > current_score + (current_score.abs() * param.switch_margin) =>
+                {
+                    Some(best_entt)
+                }
+                // stick with the current one
+                (_, Some(_)) => state.quarry,
+                (best, None) => best.map(|(entt, _)| entt),
+            };
+            if new_quarry != state.quarry {
+                tracing::debug!(?boid_entt, ?new_quarry, old_quarry = ?state.quarry, "retargeting");
+                state.quarry = new_quarry;
+                if let Some(quarry) = new_quarry {
+                    let state = &mut *state;
+                    retarget(
+                        &mut commands,
+                        quarry,
+                        [&mut state.intercept_routine, &mut state.intercept_wpn_speed],
+                        entt,
+                        boid_entt,
+                        engine_config,
+                        &weapons,
+                        &mut intercepts,
+                    );
+                }
+                if let Some(mut blackboard) = flock.and_then(|f| blackboards.get_mut(f.0).ok()) {
+                    match new_quarry {
+                        Some(quarry) => blackboard.assign(boid_entt, quarry),
+                        None => blackboard.unassign(boid_entt),
+                    }
+                }
+            }
+        }
+
+        let mut ctx = AttackCtx {
+            xform: *xform,
+            has_quarry: state.quarry.is_some(),
+            // otherwise, go after where the flock thinks it is
+            quarry_pos: state.quarry.and_then(|quarry| match contacts.get(quarry) {
+                Some(contact) if contact.in_sight => Some(contact.predicted_pos(now)),
+                _ => None,
+            }),
+            attacking_range: param.attacking_range,
+            intercept_routine: state.intercept_routine,
+            intercept_wpn_speed: state.intercept_wpn_speed,
+            requests: Default::default(),
+        };
+        state.tree.tick(&mut ctx);
+
+        let mut composer = composers
+            .get_mut(state.composer_routine.unwrap_or_log())
+            .unwrap_or_log();
+        ctx.requests
+            .apply(&mut commands, boid_entt, &mut out, &mut composer, 1);
+    }
+}
+
+/// Points the intercept routines, the plain one and the one leading with the
+/// weapons' projectile speed, at the new quarry, spawning them if necessary.
+#[allow(clippy::too_many_arguments)]
+pub fn retarget(
+    commands: &mut Commands,
+    quarry: Entity,
+    [intercept_routine, intercept_wpn_speed]: [&mut Option<Entity>; 2],
+    strategy_entt: Entity,
+    boid_entt: Entity,
+    engine_config: &engine::EngineConfig,
+    weapons: &Query<&CraftWeaponsIndex>,
+    intercepts: &mut Query<&mut intercept::Intercept>,
+) {
+    let wpn_speed = weapons
+        .get(boid_entt)
+        .ok()
+        .map(|w| w.avg_projectile_speed)
+        .filter(|speed| *speed > 0.);
+    for (routine, speed) in [(intercept_routine, None), (intercept_wpn_speed, wpn_speed)] {
+        match routine.and_then(|entt| intercepts.get_mut(entt).ok()) {
+            Some(mut intercept) => {
+                intercept.target = intercept::Target::Contact(quarry);
+                intercept.speed = speed;
+            }
+            None => {
+                *routine = Some(
+                    commands
+                        .spawn()
+                        .insert_bundle(intercept::Bundle::new(
+                            intercept::Intercept {
+                                target: intercept::Target::Contact(quarry),
+                                linvel_limit: engine_config.linvel_limit,
+                                speed,
+                            },
+                            boid_entt,
+                        ))
+                        .insert(RoutineOwners::new(strategy_entt))
+                        .id(),
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn engage_retargeting() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use crate::mind::{
+        sensors::radar::{Contact, ScanPresence},
+        tribe::{Faction, FactionRelations, Relationship},
+    };
+
+    let mut world = World::new();
+    let mut time = Time::default();
+    time.update();
+    world.insert_resource(time);
+    let mut relations = FactionRelations::default();
+    relations.set(Faction(0), Faction(1), Relationship::Hostile);
+    world.insert_resource(relations);
+    world.insert_resource(CraftSpatialIndex::default());
+
+    let mut spawn_hostile = || {
+        world
+            .spawn()
+            .insert(Faction(1))
+            .insert(attire::CraftIntegrity::default())
+            .insert(RigidBodyVelocityComponent(RigidBodyVelocity::default()))
+            .id()
+    };
+    let (a, b) = (spawn_hostile(), spawn_hostile());
+    let flock = world.spawn().insert(FlockBlackboard::default()).id();
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::identity())
+        .insert(RigidBodyVelocityComponent(RigidBodyVelocity::default()))
+        .insert(Faction(0))
+        .insert(CraftFlock(flock))
+        .insert(engine::EngineConfig::default())
+        .insert(CraftDimensions::from(TVec3::ONE * 4.))
+        .insert(SteeringRoutinesIndex::default())
+        .insert(Contacts::default())
+        .id();
+    let strategy = world
+        .spawn()
+        .insert_bundle(Bundle::new(
+            Engage {
+                retarget_period: 0.,
+                ..Default::default()
+            },
+            boid,
+            Default::default(),
+        ))
+        .id();
+
+    // puts the hostiles at the given distances ahead, both in sight
+    let place = |world: &mut World, a_dst: TReal, b_dst: TReal| {
+        let placed = [(a, TVec3::Z * -a_dst), (b, TVec3::Z * -b_dst)];
+        world
+            .get_resource_mut::<CraftSpatialIndex>()
+            .unwrap()
+            .rebuild(placed);
+        world.get_mut::<Contacts>(boid).unwrap().contacts = placed
+            .into_iter()
+            .map(|(entt, pos)| {
+                (
+                    entt,
+                    Contact {
+                        entt,
+                        pos,
+                        linvel: TVec3::ZERO,
+                        faction: Some(Faction(1)),
+                        presence: ScanPresence::Boid,
+                        last_seen_secs: 0.,
+                        in_sight: true,
+                        track_quality: 1.,
+                    },
+                )
+            })
+            .collect();
+    };
+    let tick = |world: &mut World| {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        world.get_resource_mut::<Time>().unwrap().update();
+    };
+    let quarry = |world: &World| world.get::<EngageState>(strategy).unwrap().quarry;
+    let assigned = |world: &World| world.get::<FlockBlackboard>(flock).unwrap().target_of(boid);
+
+    SystemStage::single_threaded()
+        .with_system(butler)
+        .run(&mut world);
+    let mut stage = SystemStage::single_threaded().with_system(update);
+
+    // goes for the closer one and lets the flock know
+    place(&mut world, 500., 550.);
+    tick(&mut world);
+    stage.run(&mut world);
+    assert_eq!(quarry(&world), Some(a));
+    assert_eq!(assigned(&world), Some(a));
+
+    // the other one's only a little better now, not worth switching over
+    place(&mut world, 500., 450.);
+    tick(&mut world);
+    stage.run(&mut world);
+    assert_eq!(quarry(&world), Some(a));
+
+    // but now it is
+    place(&mut world, 500., 50.);
+    tick(&mut world);
+    stage.run(&mut world);
+    assert_eq!(quarry(&world), Some(b));
+    assert_eq!(assigned(&world), Some(b));
+    assert_eq!(
+        world.get::<FlockBlackboard>(flock).unwrap().saturation(a),
+        0
+    );
+
+    // the quarry dies, right back onto the other one without waiting for the
+    // next retarget
+    world
+        .get_mut::<attire::CraftIntegrity>(b)
+        .unwrap()
+        .remaining = 0.;
+    stage.run(&mut world);
+    assert_eq!(quarry(&world), Some(a));
+    assert_eq!(assigned(&world), Some(a));
+
+    // nothing left, the flock's told so
+    world
+        .get_mut::<attire::CraftIntegrity>(a)
+        .unwrap()
+        .remaining = 0.;
+    stage.run(&mut world);
+    assert_eq!(quarry(&world), None);
+    assert_eq!(assigned(&world), None);
+}
@@ -0,0 +1,418 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use educe::Educe;
+
+use super::{
+    attack_persue::{attack_tree, AttackCtx},
+    engage::retarget,
+    ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput,
+};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{
+        boid::{steering::*, targeting::*},
+        bt,
+        flock::{blackboard::FlockBlackboard, CraftFlock},
+        sensors::{radar::Contacts, spatial::CraftSpatialIndex, *},
+        tribe::Factions,
+    },
+};
+
+/// Stick by the ward, breaking off to engage hostiles that come close to it.
+#[derive(Debug, Clone, Component)]
+pub struct Escort {
+    /// Must have a rigid body.
+    pub ward: Entity,
+    /// Where to hold station, in the ward's basis.
+    pub offset: TVec3,
+    /// Hostiles within this radius of the ward get engaged.
+    pub guard_radius: TReal,
+    pub attacking_range: TReal,
+    /// In seconds.
+    pub retarget_period: f64,
+    pub weights: TargetScoreWeights,
+}
+
+impl Escort {
+    pub fn new(ward: Entity) -> Self {
+        Self {
+            ward,
+            // off the port wing
+            offset: TVec3::new(-30., 0., 0.),
+            guard_radius: 1_000.,
+            attacking_range: 300.,
+            retarget_period: 1.,
+            weights: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Educe)]
+#[educe(Default)]
+pub struct EscortState {
+    pub quarry: Option<Entity>,
+    pub last_retarget_secs: f64,
+    pub composer_routine: Option<Entity>,
+    pub avoid_collision: Option<Entity>,
+    pub offset_pursuit_routine: Option<Entity>,
+    pub intercept_routine: Option<Entity>,
+    pub intercept_wpn_speed: Option<Entity>,
+    #[educe(Default(expression = "attack_tree()"))]
+    pub tree: bt::BehaviourTree<AttackCtx>,
+}
+
+pub type Bundle = BoidStrategyBundleExtra<Escort, EscortState>;
+
+/// A quarry is chased until it's this many guard radii away from the ward.
+const LEASH_SCALE: TReal = 1.5;
+
+pub fn butler(
+    mut commands: Commands,
+    mut added_strategies: Query<
+        (
+            Entity,
+            &Escort,
+            &BoidStrategy,
+            &mut EscortState,
+            &mut BoidStrategyOutput,
+        ),
+        Added<Escort>,
+    >,
+    crafts: Query<(
+        &engine::EngineConfig,
+        &CraftDimensions,
+        &SteeringRoutinesIndex,
+    )>,
+) {
+    for (entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
+        let (engine_config, dim, routines) = crafts
+            .get(strategy.boid_entt())
+            .expect_or_log("craft not found for BoidStrategy");
+
+        let raycast_toi_modifier = dim.max_element();
+        let cast_shape_radius = raycast_toi_modifier * 0.5;
+        let avoid_collision = reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
+                commands
+                    .spawn()
+                    .insert_bundle(avoid_collision::Bundle::new(
+                        avoid_collision::AvoidCollision::new(
+                            cast_shape_radius,
+                            raycast_toi_modifier,
+                        ),
+                        strategy.boid_entt(),
+                        Default::default(),
+                    ))
+                    .id()
+            },
+        );
+        let offset_pursuit = commands
+            .spawn()
+            .insert_bundle(offset_pursuit::Bundle::new(
+                offset_pursuit::OffsetPursuit {
+                    leader: param.ward,
+                    offset: param.offset,
+                    avail_accel: engine_config.avail_lin_accel().clamp(
+                        -engine_config.actual_acceleration_limit(),
+                        engine_config.actual_acceleration_limit(),
+                    ),
+                    linvel_limit: engine_config.linvel_limit,
+                },
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+        // the intercept routines get spawned once there's something to engage
+        let compose = commands
+            .spawn()
+            .insert_bundle(compose::Bundle::new(
+                compose::Compose {
+                    composer: compose::SteeringRoutineComposer::PriorityOverride {
+                        routines: smallvec::smallvec![avoid_collision, offset_pursuit],
+                    },
+                },
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+
+        state.avoid_collision = Some(avoid_collision);
+        state.offset_pursuit_routine = Some(offset_pursuit);
+        state.composer_routine = Some(compose);
+
+        *out = BoidStrategyOutput {
+            steering_routine: Some(compose),
+            fire_weapons: false,
+        };
+        commands.entity(entt).insert(ActiveBoidStrategy);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update(
+    mut commands: Commands,
+    mut strategies: Query<
+        (
+            Entity,
+            &Escort,
+            &BoidStrategy,
+            &mut EscortState,
+            &mut BoidStrategyOutput,
+        ),
+        With<ActiveBoidStrategy>,
+    >,
+    boids: Query<(
+        &GlobalTransform,
+        &Contacts,
+        &engine::EngineConfig,
+        Option<&DamageLedger>,
+        Option<&CraftFlock>,
+    )>,
+    wards: Query<(&GlobalTransform, &RigidBodyVelocityComponent)>,
+    weapons: Query<&CraftWeaponsIndex>,
+    // contacts linger after their crafts are gone
+    alive: Query<&attire::CraftIntegrity, With<RigidBodyVelocityComponent>>,
+    blackboards: Query<&FlockBlackboard>,
+    mut composers: Query<&mut compose::Compose>,
+    mut intercepts: Query<&mut intercept::Intercept>,
+    index: Res<CraftSpatialIndex>,
+    factions: Factions,
+    time: Res<Time>,
+    mut candidates: Local<Vec<TargetCandidate>>,
+) {
+    let now = time.seconds_since_startup();
+    for (entt, param, strategy, mut state, mut out) in strategies.iter_mut() {
+        let boid_entt = strategy.boid_entt();
+        let (xform, contacts, engine_config, ledger, flock) = boids
+            .get(boid_entt)
+            .expect_or_log("craft not found for CraftStrategy boid_entt");
+        let ward = wards.get(param.ward).ok();
+        let (ward_pos, ward_linvel) = match ward {
+            Some((ward_xform, ward_vel)) => (ward_xform.translation, TVec3::from(ward_vel.linvel)),
+            // nothing left to guard but ourselves
+            None => (xform.translation, TVec3::ZERO),
+        };
+
+        let is_alive = |entt| {
+            alive
+                .get(entt)
+                .map_or(false, |integrity| integrity.remaining > 0.)
+        };
+        let quarry_lost = matches!(state.quarry, Some(quarry) if !is_alive(quarry));
+
+        // look out for anything threatening the ward
+        if quarry_lost || now - state.last_retarget_secs > param.retarget_period {
+            state.last_retarget_secs = now;
+            candidates.clear();
+            perceived_hostiles(
+                boid_entt,
+                ward_pos,
+                param.guard_radius * LEASH_SCALE,
+                contacts,
+                flock.and_then(|f| blackboards.get(f.0).ok()),
+                ledger,
+                &index,
+                &factions,
+                &weapons,
+                &mut candidates,
+            );
+            candidates.retain(|candidate| is_alive(candidate.entt));
+            let guard_radius_squared = param.guard_radius * param.guard_radius;
+            let new_quarry = if candidates.iter().any(|c| Some(c.entt) == state.quarry) {
+                // still on the leash
+                state.quarry
+            } else {
+                candidates
+                    .iter()
+                    .filter(|c| c.pos.distance_squared(ward_pos) <= guard_radius_squared)
+                    .map(|c| {
+                        (
+                            c.entt,
+                            score_target(&param.weights, ward_pos, ward_linvel, c),
+                        )
+                    })
+                    .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+                    .map(|(entt, _)| entt)
+            };
+            if new_quarry != state.quarry {
+                tracing::debug!(?boid_entt, ?new_quarry, old_quarry = ?state.quarry, "escort retargeting");
+                state.quarry = new_quarry;
+                if let Some(quarry) = new_quarry {
+                    let state = &mut *state;
+                    retarget(
+                        &mut commands,
+                        quarry,
+                        [&mut state.intercept_routine, &mut state.intercept_wpn_speed],
+                        entt,
+                        boid_entt,
+                        engine_config,
+                        &weapons,
+                        &mut intercepts,
+                    );
+                } else {
+                    // start afresh on the next engagement
+                    state.tree.reset();
+                }
+            }
+        }
+
+        let mut ctx = AttackCtx {
+            xform: *xform,
+            has_quarry: state.quarry.is_some(),
+            quarry_pos: state.quarry.and_then(|quarry| match contacts.get(quarry) {
+                Some(contact) if contact.in_sight => Some(contact.predicted_pos(now)),
+                _ => None,
+            }),
+            attacking_range: param.attacking_range,
+            intercept_routine: state.intercept_routine,
+            intercept_wpn_speed: state.intercept_wpn_speed,
+            requests: Default::default(),
+        };
+        if state.quarry.is_some() {
+            state.tree.tick(&mut ctx);
+        } else {
+            // back to the ward's side, if there's one left to go back to
+            ctx.requests.fire_weapons = Some(false);
+            ctx.requests.steering_routine = Some(bt::RoutineRequest::Switch(
+                ward.and(state.offset_pursuit_routine),
+            ));
+        }
+
+        let mut composer = composers
+            .get_mut(state.composer_routine.unwrap_or_log())
+            .unwrap_or_log();
+        ctx.requests
+            .apply(&mut commands, boid_entt, &mut out, &mut composer, 1);
+    }
+}
+
+#[test]
+fn escort_breaks_off_and_returns() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use crate::mind::{
+        boid::BoidMindDirective,
+        sensors::radar::{Contact, ScanPresence},
+        tribe::{Faction, FactionRelations, Relationship},
+    };
+
+    let tick = |world: &mut World| {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        world.get_resource_mut::<Time>().unwrap().update();
+    };
+    let mut world = World::new();
+    let mut time = Time::default();
+    time.update();
+    world.insert_resource(time);
+    let mut relations = FactionRelations::default();
+    relations.set(Faction(0), Faction(1), Relationship::Hostile);
+    world.insert_resource(relations);
+
+    let hostile_pos = TVec3::new(0., 0., -600.);
+    let hostile = world
+        .spawn()
+        .insert(Faction(1))
+        .insert(attire::CraftIntegrity::default())
+        .insert(RigidBodyVelocityComponent(RigidBodyVelocity::default()))
+        .id();
+    let mut index = CraftSpatialIndex::default();
+    index.rebuild([(hostile, hostile_pos)]);
+    world.insert_resource(index);
+
+    let ward = world
+        .spawn()
+        .insert(GlobalTransform::from_translation(TVec3::X * 30.))
+        .insert(RigidBodyVelocityComponent(RigidBodyVelocity::default()))
+        .id();
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::identity())
+        .insert(Faction(0))
+        .insert(engine::EngineConfig::default())
+        .insert(CraftDimensions::from(TVec3::ONE * 4.))
+        .insert(SteeringRoutinesIndex::default())
+        .insert(BoidMindDirective::Escort { ward })
+        .insert(Contacts {
+            contacts: [(
+                hostile,
+                Contact {
+                    entt: hostile,
+                    pos: hostile_pos,
+                    linvel: TVec3::ZERO,
+                    faction: Some(Faction(1)),
+                    presence: ScanPresence::Boid,
+                    last_seen_secs: 0.,
+                    in_sight: true,
+                    track_quality: 1.,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            last_sweep_secs: 0.,
+        })
+        .id();
+    let strategy = world
+        .spawn()
+        .insert_bundle(Bundle::new(
+            Escort {
+                retarget_period: 0.,
+                ..Escort::new(ward)
+            },
+            boid,
+            Default::default(),
+        ))
+        .id();
+
+    let mut butler_stage = SystemStage::single_threaded().with_system(butler);
+    let mut update_stage = SystemStage::single_threaded().with_system(update);
+    let composed = |world: &World| {
+        let state = world.get::<EscortState>(strategy).unwrap();
+        match &world
+            .get::<compose::Compose>(state.composer_routine.unwrap())
+            .unwrap()
+            .composer
+        {
+            compose::SteeringRoutineComposer::PriorityOverride { routines } => {
+                routines.get(1).copied()
+            }
+            composer => panic!("unexpected composer: {composer:?}"),
+        }
+    };
+    butler_stage.run(&mut world);
+
+    // breaks off to intercept the hostile closing in on the ward
+    tick(&mut world);
+    update_stage.run(&mut world);
+    let state = world.get::<EscortState>(strategy).unwrap();
+    assert_eq!(state.quarry, Some(hostile));
+    assert_eq!(composed(&world), state.intercept_routine);
+
+    // the hostile dies, back to the slot without waiting for the next retarget
+    world
+        .get_mut::<attire::CraftIntegrity>(hostile)
+        .unwrap()
+        .remaining = 0.;
+    update_stage.run(&mut world);
+    let state = world.get::<EscortState>(strategy).unwrap();
+    assert_eq!(state.quarry, None);
+    assert_eq!(composed(&world), state.offset_pursuit_routine);
+
+    // the ward's gone, nothing to hold station by and onto something else
+    world.despawn(ward);
+    tick(&mut world);
+    update_stage.run(&mut world);
+    assert_eq!(composed(&world), None);
+    SystemStage::single_threaded()
+        .with_system(crate::mind::boid::escort_ward_lost)
+        .run(&mut world);
+    assert!(matches!(
+        world.get::<BoidMindDirective>(boid).unwrap(),
+        BoidMindDirective::Engage { .. }
+    ));
+}
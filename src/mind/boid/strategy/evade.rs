@@ -0,0 +1,433 @@
+use deps::*;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+use rand::seq::SliceRandom;
+
+use super::{
+    attack_persue::AttackPersue, engage::Engage, ActiveBoidStrategy, BoidStrategy,
+    BoidStrategyBundleExtra, BoidStrategyOutput, CurrentBoidStrategy,
+};
+use crate::{
+    craft::{arms::Projectile, *},
+    math::*,
+    mind::{
+        boid::{
+            steering::{maneuver::*, *},
+            utility::UtilityMind,
+            BoidMindConfig,
+        },
+        sensors::{radar::Contacts, *},
+        tribe::Factions,
+    },
+};
+
+/// Throws off pursuers and incoming fire for a while.
+#[derive(Debug, Clone, Component)]
+pub struct Evade {
+    /// The one we're evading, if known.
+    pub threat: Option<Entity>,
+    /// Picked from at random.
+    pub maneuvers: smallvec::SmallVec<[ManeuverKind; 4]>,
+    /// How long to keep at a maneuver. In seconds.
+    pub duration_secs: f64,
+    /// See [`Maneuver::period_secs`].
+    pub maneuver_period_secs: f64,
+}
+
+impl Default for Evade {
+    fn default() -> Self {
+        Self {
+            threat: None,
+            maneuvers: smallvec::SmallVec::from_slice(&ManeuverKind::ALL),
+            duration_secs: 3.,
+            maneuver_period_secs: 0.75,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Component)]
+pub struct EvadeState {
+    pub started_secs: Option<f64>,
+    /// Set once the first maneuver's been kept at for the duration.
+    pub timed_out: bool,
+    pub composer_routine: Option<Entity>,
+    pub avoid_collision: Option<Entity>,
+    pub maneuver_routine: Option<Entity>,
+}
+
+pub type Bundle = BoidStrategyBundleExtra<Evade, EvadeState>;
+
+fn pick_maneuver(param: &Evade) -> ManeuverKind {
+    param
+        .maneuvers
+        .choose(&mut rand::thread_rng())
+        .copied()
+        .unwrap_or(ManeuverKind::BreakTurn)
+}
+
+pub fn butler(
+    mut commands: Commands,
+    mut added_strategies: Query<
+        (
+            Entity,
+            &Evade,
+            &BoidStrategy,
+            &mut EvadeState,
+            &mut BoidStrategyOutput,
+        ),
+        Added<Evade>,
+    >,
+    crafts: Query<(&CraftDimensions, &SteeringRoutinesIndex)>,
+) {
+    for (entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
+        let (dim, routines) = crafts
+            .get(strategy.boid_entt())
+            .expect_or_log("craft not found for BoidStrategy");
+
+        let raycast_toi_modifier = dim.max_element();
+        let cast_shape_radius = raycast_toi_modifier * 0.5;
+        let avoid_collision = reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
+                commands
+                    .spawn()
+                    .insert_bundle(avoid_collision::Bundle::new(
+                        avoid_collision::AvoidCollision::new(
+                            cast_shape_radius,
+                            raycast_toi_modifier,
+                        ),
+                        strategy.boid_entt(),
+                        Default::default(),
+                    ))
+                    .id()
+            },
+        );
+        let maneuver = commands
+            .spawn()
+            .insert_bundle(maneuver::Bundle::new(
+                Maneuver {
+                    kind: pick_maneuver(param),
+                    threat: param.threat,
+                    period_secs: param.maneuver_period_secs,
+                },
+                strategy.boid_entt(),
+                Default::default(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+        let compose = commands
+            .spawn()
+            .insert_bundle(compose::Bundle::new(
+                compose::Compose {
+                    composer: compose::SteeringRoutineComposer::PriorityOverride {
+                        routines: smallvec::smallvec![avoid_collision, maneuver],
+                    },
+                },
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+
+        state.avoid_collision = Some(avoid_collision);
+        state.maneuver_routine = Some(maneuver);
+        state.composer_routine = Some(compose);
+
+        *out = BoidStrategyOutput {
+            steering_routine: Some(compose),
+            fire_weapons: false,
+        };
+        commands.entity(entt).insert(ActiveBoidStrategy);
+    }
+}
+
+/// Switches maneuvers every `duration_secs`.
+pub fn update(
+    mut strategies: Query<(&Evade, &mut EvadeState), With<ActiveBoidStrategy>>,
+    mut maneuvers: Query<(&mut Maneuver, &mut ManeuverState)>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for (param, mut state) in strategies.iter_mut() {
+        let started_secs = match state.started_secs {
+            Some(secs) => secs,
+            None => {
+                state.started_secs = Some(now);
+                continue;
+            }
+        };
+        if now - started_secs < param.duration_secs {
+            continue;
+        }
+        state.timed_out = true;
+        state.started_secs = Some(now);
+
+        let (mut maneuver, mut maneuver_state) = maneuvers
+            .get_mut(state.maneuver_routine.unwrap_or_log())
+            .unwrap_or_log();
+        maneuver.kind = pick_maneuver(param);
+        *maneuver_state = Default::default();
+    }
+}
+
+/// Has boids that are being tailed or shot at interrupt their attacks to [`Evade`].
+/// The interrupted strategy is suspended until the evasion times out.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn interrupt(
+    mut commands: Commands,
+    mut boids: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &RigidBodyVelocityComponent,
+            &mut CurrentBoidStrategy,
+            &BoidMindConfig,
+            &Contacts,
+            &CraftDimensions,
+        ),
+        Without<UtilityMind>,
+    >,
+    attacking: Query<
+        (),
+        (
+            With<ActiveBoidStrategy>,
+            Or<(With<AttackPersue>, With<Engage>)>,
+        ),
+    >,
+    projectiles: Query<(&Projectile, &GlobalTransform, &RigidBodyVelocityComponent)>,
+    factions: Factions,
+    time: Res<Time>,
+    mut last_evasion: Local<HashMap<Entity, f64>>,
+) {
+    let now = time.seconds_since_startup();
+    for (boid_entt, xform, vel, mut cur_stg, config, contacts, dim) in boids.iter_mut() {
+        match cur_stg.strategy {
+            Some(strategy) if attacking.get(strategy).is_ok() => {}
+            _ => continue,
+        }
+        if let Some(last) = last_evasion.get(&boid_entt) {
+            if now - last < config.evasion_cooldown_secs as f64 {
+                continue;
+            }
+        }
+        let pos = xform.translation;
+        let fwd = xform.forward();
+
+        // someone on our six that's coming for us
+        const SIX_COS_THRESHOLD: TReal = -0.707;
+        const PURSUIT_COS_THRESHOLD: TReal = 0.9;
+        let tailed_by = contacts
+            .boids()
+            .filter(|contact| contact.in_sight && factions.is_hostile(boid_entt, contact.entt))
+            .find(|contact| {
+                let offset = contact.pos - pos;
+                let dist = offset.length();
+                dist > real::EPSILON
+                    && dist < config.evasion_tail_range
+                    && fwd.dot(offset / dist) < SIX_COS_THRESHOLD
+                    && contact.linvel.normalize_or_zero().dot(-offset / dist)
+                        > PURSUIT_COS_THRESHOLD
+            })
+            .map(|contact| contact.entt);
+
+        // or fire that's about to hit us
+        let linvel = TVec3::from(vel.linvel);
+        let hit_radius = dim.max_element() * 2.;
+        let shot_by = projectiles
+            .iter()
+            .filter(|(proj, ..)| factions.is_hostile(boid_entt, proj.source_craft))
+            .find(|(_, proj_xform, proj_vel)| {
+                let offset = proj_xform.translation - pos;
+                if offset.length_squared()
+                    > config.evasion_projectile_range * config.evasion_projectile_range
+                {
+                    return false;
+                }
+                let rel_vel = TVec3::from(proj_vel.linvel) - linvel;
+                let closing = -offset.dot(rel_vel);
+                if closing <= 0. {
+                    return false;
+                }
+                // distance at the point of closest approach
+                let toca = closing / rel_vel.length_squared();
+                (offset + (rel_vel * toca)).length() < hit_radius
+            })
+            .map(|(proj, ..)| proj.source_craft);
+
+        let threat = match (tailed_by, shot_by) {
+            (Some(threat), _) | (None, Some(threat)) => threat,
+            (None, None) => continue,
+        };
+        tracing::debug!(?boid_entt, ?threat, "evading");
+        last_evasion.insert(boid_entt, now);
+
+        let suspended = cur_stg.strategy.take().unwrap_or_log();
+        commands.entity(suspended).remove::<ActiveBoidStrategy>();
+        if let Some(old) = cur_stg.suspended.replace(suspended) {
+            commands.entity(old).despawn_recursive();
+        }
+        cur_stg.strategy = Some(
+            commands
+                .spawn()
+                .insert_bundle(Bundle::new(
+                    Evade {
+                        threat: Some(threat),
+                        ..Default::default()
+                    },
+                    boid_entt,
+                    Default::default(),
+                ))
+                .id(),
+        );
+    }
+    last_evasion.retain(|_, last| now - *last < 60.);
+}
+
+/// Gets boids back to their suspended strategy once the evasion times out.
+pub fn resume(
+    mut commands: Commands,
+    mut boids: Query<&mut CurrentBoidStrategy>,
+    evasions: Query<(&BoidStrategy, &EvadeState), Changed<EvadeState>>,
+    outputs: Query<&BoidStrategyOutput>,
+    mut composers: Query<&mut compose::Compose>,
+) {
+    for (strategy, state) in evasions.iter() {
+        if !state.timed_out {
+            continue;
+        }
+        let mut cur_stg = match boids.get_mut(strategy.boid_entt()) {
+            Ok(cur_stg) => cur_stg,
+            Err(_) => continue,
+        };
+        let suspended = match cur_stg.suspended.take() {
+            Some(suspended) => suspended,
+            // nothing to get back to, keep at it
+            None => continue,
+        };
+        if let Some(evade) = cur_stg.strategy.replace(suspended) {
+            commands.entity(evade).despawn_recursive();
+        }
+        commands.entity(suspended).insert(ActiveBoidStrategy);
+        // have the compose butler reactivate the suspended strategy's routines
+        if let Some(mut composer) = outputs
+            .get(suspended)
+            .ok()
+            .and_then(|out| out.steering_routine)
+            .and_then(|routine| composers.get_mut(routine).ok())
+        {
+            composer.set_changed();
+        }
+    }
+}
+
+#[test]
+fn evade_interrupts_and_hands_back() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use crate::mind::{
+        sensors::radar::{Contact, ScanPresence},
+        tribe::{Faction, FactionRelations, Relationship},
+    };
+
+    let mut world = World::new();
+    let mut time = Time::default();
+    time.update();
+    world.insert_resource(time);
+    let mut relations = FactionRelations::default();
+    relations.set(Faction(0), Faction(1), Relationship::Hostile);
+    world.insert_resource(relations);
+
+    // closing in on our six
+    let pursuer = world.spawn().insert(Faction(1)).id();
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::identity())
+        .insert(RigidBodyVelocityComponent(RigidBodyVelocity::default()))
+        .insert(Faction(0))
+        .insert(BoidMindConfig::default())
+        .insert(CraftDimensions::from(TVec3::ONE * 4.))
+        .insert(SteeringRoutinesIndex::default())
+        .insert(Contacts {
+            contacts: [(
+                pursuer,
+                Contact {
+                    entt: pursuer,
+                    pos: TVec3::Z * 200.,
+                    linvel: TVec3::Z * -50.,
+                    faction: Some(Faction(1)),
+                    presence: ScanPresence::Boid,
+                    last_seen_secs: 0.,
+                    in_sight: true,
+                    track_quality: 1.,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            last_sweep_secs: 0.,
+        })
+        .id();
+    let attack_composer = world
+        .spawn()
+        .insert(compose::Compose {
+            composer: compose::SteeringRoutineComposer::None,
+        })
+        .id();
+    let attack = world
+        .spawn()
+        .insert(Engage::default())
+        .insert(ActiveBoidStrategy)
+        .insert(BoidStrategyOutput {
+            steering_routine: Some(attack_composer),
+            fire_weapons: true,
+        })
+        .id();
+    world.entity_mut(boid).insert(CurrentBoidStrategy {
+        strategy: Some(attack),
+        suspended: None,
+    });
+
+    let mut stages = [
+        SystemStage::single_threaded().with_system(interrupt),
+        SystemStage::single_threaded().with_system(butler),
+        SystemStage::single_threaded().with_system(update),
+        SystemStage::single_threaded().with_system(resume),
+    ];
+    let mut run = |world: &mut World| {
+        for stage in stages.iter_mut() {
+            stage.run(world);
+        }
+    };
+
+    // the attack's put on hold for an evasion
+    run(&mut world);
+    let cur_stg = world.get::<CurrentBoidStrategy>(boid).unwrap().clone();
+    assert_eq!(cur_stg.suspended, Some(attack));
+    assert!(world.get::<ActiveBoidStrategy>(attack).is_none());
+    let evade = cur_stg.strategy.unwrap();
+    assert!(world.get::<Evade>(evade).is_some());
+    assert!(world
+        .get::<EvadeState>(evade)
+        .unwrap()
+        .maneuver_routine
+        .is_some());
+
+    // keeps at it until the maneuvers time out
+    world.get_mut::<Evade>(evade).unwrap().duration_secs = 1e-6;
+    run(&mut world);
+    assert_eq!(
+        world.get::<CurrentBoidStrategy>(boid).unwrap().strategy,
+        Some(evade)
+    );
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    world.get_resource_mut::<Time>().unwrap().update();
+
+    // and the attack picks back up
+    run(&mut world);
+    let cur_stg = world.get::<CurrentBoidStrategy>(boid).unwrap();
+    assert_eq!(cur_stg.strategy, Some(attack));
+    assert_eq!(cur_stg.suspended, None);
+    assert!(world.get::<ActiveBoidStrategy>(attack).is_some());
+    assert!(world.get_entity(evade).is_none());
+}
@@ -23,7 +23,7 @@ pub struct FormState {
     pub composer_routine: Option<Entity>,
     pub avoid_collision_routine: Option<Entity>,
     pub arrive_routine: Option<Entity>,
-    pub face_routine: Option<Entity>,
+    pub orient_routine: Option<Entity>,
 }
 
 pub type Bundle = BoidStrategyBundleExtra<Form, FormState>;
@@ -54,10 +54,11 @@ pub fn butler(
 
         let raycast_toi_modifier = dim.max_element();
         let cast_shape_radius = raycast_toi_modifier * 0.5;
-        let avoid_collision = routines
-            .kind::<avoid_collision::AvoidCollision>()
-            .map(|v| v[0])
-            .unwrap_or_else(|| {
+        let avoid_collision = reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
                 commands
                     .spawn()
                     .insert_bundle(avoid_collision::Bundle::new(
@@ -69,7 +70,8 @@ pub fn butler(
                         Default::default(),
                     ))
                     .id()
-            });
+            },
+        );
 
         let (mut formation_state, fomation_output) =
             formations.get_mut(param.formation).unwrap_or_log();
@@ -84,30 +86,30 @@ pub fn butler(
             .spawn()
             .insert_bundle(arrive::Bundle::new(
                 arrive::Arrive {
-                    target: arrive::Target::Vector {
+                    target: arrive::Target::WithLinvel {
                         at_pos: form_out.pos,
-                        // with_linvel: form_out.linvel,
                         pos_linvel: form_out.pos_linvel,
-                        with_speed: form_out.linvel.length(),
+                        with_linvel: form_out.linvel,
                     },
                     arrival_tolerance: 5.,
                     deceleration_radius: None,
                     linvel_limit: engine_config.linvel_limit,
+                    max_speed: None,
                     avail_accel: engine_config.actual_acceleration_limit(),
                 },
                 strategy.boid_entt(),
             ))
+            .insert(RoutineOwners::new(entt))
             .id();
-        let face = commands
+        let orient = commands
             .spawn()
-            .insert_bundle(face::Bundle::new(
-                face::Face {
-                    target: face::Target::Direction {
-                        dir: form_out.facing,
-                    },
-                },
+            .insert_bundle(orient::Bundle::new(
+                orient::Orient::new(orient::Target::Rotation {
+                    rot: form_out.facing,
+                }),
                 strategy.boid_entt(),
             ))
+            .insert(RoutineOwners::new(entt))
             .id();
         let compose = commands
             .spawn()
@@ -117,18 +119,19 @@ pub fn butler(
                         avoid_collision,
                         routines: smallvec::smallvec![
                             ((1., 0.).into(), arrive),
-                            ((0., 1.).into(), face),
+                            ((0., 1.).into(), orient),
                         ],
                     },
                 },
                 strategy.boid_entt(),
             ))
+            .insert(RoutineOwners::new(entt))
             .id();
 
         state.composer_routine = Some(compose);
         state.avoid_collision_routine = Some(avoid_collision);
         state.arrive_routine = Some(arrive);
-        state.face_routine = Some(face);
+        state.orient_routine = Some(orient);
 
         *out = BoidStrategyOutput {
             steering_routine: Some(compose),
@@ -143,7 +146,7 @@ pub fn update(
     strategies: Query<(&FormState, Option<&ActiveBoidStrategy>)>,
     formations: Query<(&FormationOutputs, &FormationState)>,
     mut arrive_routines: Query<&mut arrive::Arrive>,
-    mut face_routines: Query<&mut face::Face>,
+    mut orient_routines: Query<&mut orient::Orient>,
 ) {
     // return;
     for (out, formation_state) in formations.iter() {
@@ -161,17 +164,16 @@ pub fn update(
             let mut arrive_param = arrive_routines
                 .get_mut(state.arrive_routine.unwrap_or_log())
                 .unwrap_or_log();
-            arrive_param.target = arrive::Target::Vector {
+            arrive_param.target = arrive::Target::WithLinvel {
                 at_pos: form_out.pos,
-                // with_linvel: form_out.linvel,
                 pos_linvel: form_out.pos_linvel,
-                with_speed: form_out.linvel.length(),
+                with_linvel: form_out.linvel,
             };
-            let mut face_param = face_routines
-                .get_mut(state.face_routine.unwrap_or_log())
+            let mut orient_param = orient_routines
+                .get_mut(state.orient_routine.unwrap_or_log())
                 .unwrap_or_log();
-            face_param.target = face::Target::Direction {
-                dir: form_out.facing,
+            orient_param.target = orient::Target::Rotation {
+                rot: form_out.facing,
             };
         }
         // FIXME: 3 frame gap unless I divvy up the damn PreUpdate stage
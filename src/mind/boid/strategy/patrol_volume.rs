@@ -0,0 +1,223 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{boid::steering::*, sensors::*},
+};
+
+/// In world space.
+#[derive(Debug, Clone, Copy)]
+pub enum Volume {
+    Sphere {
+        center: TVec3,
+        radius: TReal,
+    },
+    /// Axis aligned.
+    Box {
+        center: TVec3,
+        half_extents: TVec3,
+    },
+}
+
+impl Volume {
+    #[inline]
+    pub fn center(&self) -> TVec3 {
+        match self {
+            Volume::Sphere { center, .. } | Volume::Box { center, .. } => *center,
+        }
+    }
+
+    /// Whether `pos` is inside the volume shrunk or grown about its center by `scale`.
+    pub fn contains_scaled(&self, pos: TVec3, scale: TReal) -> bool {
+        match self {
+            Volume::Sphere { center, radius } => {
+                center.distance_squared(pos) <= (radius * scale).powi(2)
+            }
+            Volume::Box {
+                center,
+                half_extents,
+            } => (pos - *center).abs().cmple(*half_extents * scale).all(),
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, pos: TVec3) -> bool {
+        self.contains_scaled(pos, 1.)
+    }
+}
+
+/// Roam about within the volume, heading back in whenever we stray out.
+#[derive(Debug, Clone, Component)]
+pub struct PatrolVolume {
+    pub volume: Volume,
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct PatrolVolumeState {
+    pub composer_routine: Option<Entity>,
+    pub avoid_collision_routine: Option<Entity>,
+    pub wander_routine: Option<Entity>,
+    pub return_routine: Option<Entity>,
+    pub returning: bool,
+}
+
+pub type Bundle = BoidStrategyBundleExtra<PatrolVolume, PatrolVolumeState>;
+
+pub fn butler(
+    mut commands: Commands,
+    mut added_strategies: Query<
+        (
+            Entity,
+            &PatrolVolume,
+            &BoidStrategy,
+            &mut PatrolVolumeState,
+            &mut BoidStrategyOutput,
+        ),
+        Added<PatrolVolume>,
+    >,
+    crafts: Query<(&SteeringRoutinesIndex, &CraftDimensions)>,
+) {
+    for (entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
+        let (routines, dim) = crafts
+            .get(strategy.boid_entt())
+            .expect_or_log("craft not found for BoidStrategy");
+
+        let raycast_toi_modifier = dim.max_element();
+        let cast_shape_radius = raycast_toi_modifier * 0.5;
+        let avoid_collision = reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
+                commands
+                    .spawn()
+                    .insert_bundle(avoid_collision::Bundle::new(
+                        avoid_collision::AvoidCollision::new(
+                            cast_shape_radius,
+                            raycast_toi_modifier,
+                        ),
+                        strategy.boid_entt(),
+                        Default::default(),
+                    ))
+                    .id()
+            },
+        );
+        // seeded by the craft so that each one wanders its own way
+        let wander = commands
+            .spawn()
+            .insert_bundle(wander::Bundle::new(
+                wander::Wander::new(strategy.boid_entt().to_bits()),
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+        let return_routine = commands
+            .spawn()
+            .insert_bundle(seek::Bundle::new(
+                seek::Seek {
+                    target: seek::Target::Position {
+                        pos: param.volume.center(),
+                    },
+                },
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+        let compose = commands
+            .spawn()
+            .insert_bundle(compose::Bundle::new(
+                compose::Compose {
+                    composer: compose::SteeringRoutineComposer::PriorityOverride {
+                        routines: smallvec::smallvec![avoid_collision, wander],
+                    },
+                },
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+
+        state.avoid_collision_routine = Some(avoid_collision);
+        state.wander_routine = Some(wander);
+        state.return_routine = Some(return_routine);
+        state.composer_routine = Some(compose);
+        *out = BoidStrategyOutput {
+            steering_routine: Some(compose),
+            fire_weapons: false,
+        };
+
+        commands.entity(entt).insert(ActiveBoidStrategy);
+    }
+}
+
+/// Only head back in once well inside so that we don't jitter at the border.
+const RETURN_UNTIL_SCALE: TReal = 0.75;
+
+pub fn update(
+    mut strategies: Query<
+        (&PatrolVolume, &BoidStrategy, &mut PatrolVolumeState),
+        With<ActiveBoidStrategy>,
+    >,
+    crafts: Query<&GlobalTransform>,
+    mut composers: Query<&mut compose::Compose>,
+    mut wander_states: Query<&mut wander::WanderState>,
+) {
+    for (param, strategy, mut state) in strategies.iter_mut() {
+        let pos = crafts
+            .get(strategy.boid_entt())
+            .expect_or_log("craft xform not found for BoidStrategy boid_entt")
+            .translation;
+        let returning = if state.returning {
+            !param.volume.contains_scaled(pos, RETURN_UNTIL_SCALE)
+        } else {
+            !param.volume.contains(pos)
+        };
+        if returning == state.returning {
+            continue;
+        }
+        state.returning = returning;
+
+        let routine = if returning {
+            state.return_routine.unwrap_or_log()
+        } else {
+            let wander = state.wander_routine.unwrap_or_log();
+            // pick up from wherever the return trip left us headed
+            if let Ok(mut wander_state) = wander_states.get_mut(wander) {
+                wander_state.reset_heading();
+            }
+            wander
+        };
+        let mut composer = composers
+            .get_mut(state.composer_routine.unwrap_or_log())
+            .unwrap_or_log();
+        match &mut composer.composer {
+            compose::SteeringRoutineComposer::PriorityOverride { routines } => {
+                routines[1] = routine;
+            }
+            composer => {
+                tracing::error!(?composer, "unexpected composer for PatrolVolume");
+            }
+        }
+    }
+}
+
+#[test]
+fn volume_containment() {
+    let sphere = Volume::Sphere {
+        center: TVec3::X * 100.,
+        radius: 10.,
+    };
+    assert!(sphere.contains(TVec3::X * 95.));
+    assert!(!sphere.contains_scaled(TVec3::X * 95., 0.25));
+    assert!(!sphere.contains(TVec3::ZERO));
+
+    let cuboid = Volume::Box {
+        center: TVec3::ZERO,
+        half_extents: TVec3::new(10., 1., 10.),
+    };
+    assert!(cuboid.contains(TVec3::new(9., 0.5, -9.)));
+    assert!(!cuboid.contains(TVec3::new(9., 2., -9.)));
+}
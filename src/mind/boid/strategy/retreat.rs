@@ -0,0 +1,201 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{boid::steering::*, flock::formation::FormationCenterPivot, sensors::*},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum RallyPoint {
+    /// In world space.
+    Position(TVec3),
+    /// Falls in behind the formation's center pivot.
+    Formation(Entity),
+}
+
+/// Disengage and make for the rally point, holding fire.
+#[derive(Debug, Clone, Component)]
+pub struct Retreat {
+    pub rally: RallyPoint,
+    /// Ran from when closer than the `panic_distance`.
+    pub threat: Option<Entity>,
+    /// In meters.
+    pub panic_distance: TReal,
+    /// Caps the speed, e.g. to keep withdrawing flocks together. In m/s.
+    pub pace: Option<TReal>,
+}
+
+impl Retreat {
+    pub fn new(rally: RallyPoint) -> Self {
+        Self {
+            rally,
+            threat: None,
+            panic_distance: 500.,
+            pace: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct RetreatState {
+    pub composer_routine: Option<Entity>,
+    pub avoid_collision_routine: Option<Entity>,
+    pub flee_routine: Option<Entity>,
+    pub arrive_routine: Option<Entity>,
+}
+
+pub type Bundle = BoidStrategyBundleExtra<Retreat, RetreatState>;
+
+/// How far behind a formation's pivot to rally. In meters.
+const FORMATION_TRAIL_DISTANCE: TReal = 50.;
+
+pub fn butler(
+    mut commands: Commands,
+    mut added_strategies: Query<
+        (
+            Entity,
+            &Retreat,
+            &BoidStrategy,
+            &mut RetreatState,
+            &mut BoidStrategyOutput,
+        ),
+        Added<Retreat>,
+    >,
+    crafts: Query<(
+        &GlobalTransform,
+        &engine::EngineConfig,
+        &CraftDimensions,
+        &SteeringRoutinesIndex,
+    )>,
+) {
+    for (entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
+        let (xform, engine_config, dim, routines) = crafts
+            .get(strategy.boid_entt())
+            .expect_or_log("craft not found for BoidStrategy");
+
+        let raycast_toi_modifier = dim.max_element();
+        let cast_shape_radius = raycast_toi_modifier * 0.5;
+        let avoid_collision = reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
+                commands
+                    .spawn()
+                    .insert_bundle(avoid_collision::Bundle::new(
+                        avoid_collision::AvoidCollision::new(
+                            cast_shape_radius,
+                            raycast_toi_modifier,
+                        ),
+                        strategy.boid_entt(),
+                        Default::default(),
+                    ))
+                    .id()
+            },
+        );
+        let flee = param.threat.map(|threat| {
+            commands
+                .spawn()
+                .insert_bundle(flee::Bundle::new(
+                    flee::Flee {
+                        target: seek::Target::Object { entt: threat },
+                        panic_distance: param.panic_distance,
+                    },
+                    strategy.boid_entt(),
+                ))
+                .insert(RoutineOwners::new(entt))
+                .id()
+        });
+        // aimed at the rally point on update
+        let arrive = commands
+            .spawn()
+            .insert_bundle(arrive::Bundle::new(
+                arrive::Arrive {
+                    target: arrive::Target::WithLinvel {
+                        at_pos: xform.translation,
+                        pos_linvel: TVec3::ZERO,
+                        with_linvel: TVec3::ZERO,
+                    },
+                    arrival_tolerance: 5.,
+                    deceleration_radius: None,
+                    linvel_limit: engine_config.linvel_limit,
+                    max_speed: param.pace,
+                    avail_accel: engine_config.avail_lin_accel().clamp(
+                        -engine_config.actual_acceleration_limit(),
+                        engine_config.actual_acceleration_limit(),
+                    ),
+                },
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+        let compose = commands
+            .spawn()
+            .insert_bundle(compose::Bundle::new(
+                compose::Compose {
+                    composer: compose::SteeringRoutineComposer::PriorityOverride {
+                        routines: [Some(avoid_collision), flee, Some(arrive)]
+                            .into_iter()
+                            .flatten()
+                            .collect(),
+                    },
+                },
+                strategy.boid_entt(),
+            ))
+            .insert(RoutineOwners::new(entt))
+            .id();
+
+        state.avoid_collision_routine = Some(avoid_collision);
+        state.flee_routine = flee;
+        state.arrive_routine = Some(arrive);
+        state.composer_routine = Some(compose);
+        // ignore any targets
+        *out = BoidStrategyOutput {
+            steering_routine: Some(compose),
+            fire_weapons: false,
+        };
+
+        commands.entity(entt).insert(ActiveBoidStrategy);
+    }
+}
+
+pub fn update(
+    strategies: Query<(&Retreat, &RetreatState), With<ActiveBoidStrategy>>,
+    formations: Query<&FormationCenterPivot>,
+    crafts: Query<(&GlobalTransform, &RigidBodyVelocityComponent)>,
+    mut arrive_routines: Query<&mut arrive::Arrive>,
+) {
+    for (param, state) in strategies.iter() {
+        let (at_pos, pos_linvel) = match param.rally {
+            RallyPoint::Position(pos) => (pos, TVec3::ZERO),
+            RallyPoint::Formation(formation) => {
+                match formations
+                    .get(formation)
+                    .ok()
+                    .and_then(|pivot| crafts.get(pivot.boid_entt()).ok())
+                {
+                    Some((xform, vel)) => (
+                        xform.translation
+                            + (xform.rotation * (TVec3::Z * FORMATION_TRAIL_DISTANCE)),
+                        TVec3::from(vel.linvel),
+                    ),
+                    // keep heading for where it was last
+                    None => continue,
+                }
+            }
+        };
+        let mut arrive = arrive_routines
+            .get_mut(state.arrive_routine.unwrap_or_log())
+            .unwrap_or_log();
+        arrive.target = arrive::Target::WithLinvel {
+            at_pos,
+            pos_linvel,
+            with_linvel: pos_linvel,
+        };
+    }
+}
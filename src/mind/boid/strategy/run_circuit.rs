@@ -25,6 +25,7 @@ pub struct RunCircuitState {
     pub composer_routine: Option<Entity>,
     pub arrive_routine: Option<Entity>,
     pub avoid_collision_routine: Option<Entity>,
+    pub avoid_obstacles_routine: Option<Entity>,
 }
 
 pub type Bundle = BoidStrategyBundleExtra<RunCircuit, RunCircuitState>;
@@ -57,10 +58,11 @@ pub fn butler(
 
         let raycast_toi_modifier = dim.max_element();
         let cast_shape_radius = raycast_toi_modifier * 0.5;
-        let avoid_collision = routines
-            .kind::<avoid_collision::AvoidCollision>()
-            .map(|v| v[0])
-            .unwrap_or_else(|| {
+        let avoid_collision = steering::reuse_or_spawn::<avoid_collision::AvoidCollision>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
                 commands
                     .spawn()
                     .insert_bundle(avoid_collision::Bundle::new(
@@ -72,7 +74,28 @@ pub fn butler(
                         Default::default(),
                     ))
                     .id()
-            });
+            },
+        );
+        // circuits tend to be cluttered, search around for a way through
+        // whatever avoid_collision's not already dodging
+        let avoid_obstacles = steering::reuse_or_spawn::<avoid_obstacles::AvoidObstacles>(
+            &mut commands,
+            routines,
+            entt,
+            |commands| {
+                commands
+                    .spawn()
+                    .insert_bundle(avoid_obstacles::Bundle::new(
+                        avoid_obstacles::AvoidObstacles::new(
+                            cast_shape_radius,
+                            raycast_toi_modifier,
+                        ),
+                        strategy.boid_entt(),
+                        Default::default(),
+                    ))
+                    .id()
+            },
+        );
         let arrive = commands
             .spawn()
             .insert_bundle(arrive::Bundle::new(
@@ -89,6 +112,7 @@ pub fn butler(
                     arrival_tolerance: 5.,
                     deceleration_radius: None,
                     linvel_limit: engine_config.linvel_limit,
+                    max_speed: None,
                     avail_accel: engine_config.avail_lin_accel().clamp(
                         -engine_config.actual_acceleration_limit(),
                         engine_config.actual_acceleration_limit(),
@@ -96,21 +120,24 @@ pub fn butler(
                 },
                 strategy.boid_entt(),
             ))
+            .insert(steering::RoutineOwners::new(entt))
             .id();
         let compose = commands
             .spawn()
             .insert_bundle(compose::Bundle::new(
                 compose::Compose {
                     composer: compose::SteeringRoutineComposer::PriorityOverride {
-                        routines: smallvec::smallvec![avoid_collision, arrive],
+                        routines: smallvec::smallvec![avoid_collision, avoid_obstacles, arrive],
                     },
                 },
                 strategy.boid_entt(),
             ))
+            .insert(steering::RoutineOwners::new(entt))
             .id();
 
         state.arrive_routine = Some(arrive);
         state.avoid_collision_routine = Some(avoid_collision);
+        state.avoid_obstacles_routine = Some(avoid_obstacles);
         state.composer_routine = Some(compose);
         *out = BoidStrategyOutput {
             steering_routine: Some(compose),
@@ -0,0 +1,242 @@
+use deps::*;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    craft::attire::ProjectileDamageEvent,
+    math::*,
+    mind::{
+        flock::blackboard::FlockBlackboard,
+        sensors::{
+            radar::{Contacts, ScanPresence},
+            spatial::CraftSpatialIndex,
+            CraftWeaponsIndex,
+        },
+        tribe::Factions,
+    },
+};
+
+/// Who's been shooting at the craft and how much it hurt.
+/// Craft mind component
+#[derive(Debug, Clone, Default, Component)]
+pub struct DamageLedger {
+    pub dealt_by: HashMap<Entity, TReal>,
+}
+
+impl DamageLedger {
+    /// Recorded damage halves every this many seconds.
+    pub const HALF_LIFE_SECS: TReal = 10.;
+
+    #[inline]
+    pub fn damage_from(&self, attacker: Entity) -> TReal {
+        self.dealt_by.get(&attacker).copied().unwrap_or_default()
+    }
+
+    pub fn record(&mut self, attacker: Entity, damage: TReal) {
+        *self.dealt_by.entry(attacker).or_default() += damage;
+    }
+
+    pub fn decay(&mut self, delta_secs: TReal) {
+        let factor = (-delta_secs * real::consts::LN_2 / Self::HALF_LIFE_SECS).exp();
+        self.dealt_by.retain(|_, damage| {
+            *damage *= factor;
+            *damage > 1.
+        });
+    }
+}
+
+pub fn record_damage(
+    mut damage_events: EventReader<ProjectileDamageEvent>,
+    attires: Query<&ColliderParentComponent>,
+    mut ledgers: Query<&mut DamageLedger>,
+) {
+    for event in damage_events.iter() {
+        let victim = match attires.get(event.attire_entt) {
+            Ok(parent) => parent.handle.entity(),
+            Err(_) => continue,
+        };
+        if let Ok(mut ledger) = ledgers.get_mut(victim) {
+            let projectile = &event.ixn_event.projectile;
+            ledger.record(projectile.source_craft, projectile.damage.value);
+        }
+    }
+}
+
+pub fn decay_damage_ledgers(time: Res<Time>, mut ledgers: Query<&mut DamageLedger>) {
+    let delta = time.delta_seconds();
+    for mut ledger in ledgers.iter_mut() {
+        if !ledger.dealt_by.is_empty() {
+            ledger.decay(delta);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TargetScoreWeights {
+    pub distance: TReal,
+    pub closing_speed: TReal,
+    pub threat: TReal,
+    pub damage_dealt: TReal,
+    /// Applied per flock member already assigned to the target.
+    pub saturation: TReal,
+}
+
+impl Default for TargetScoreWeights {
+    fn default() -> Self {
+        Self {
+            distance: 1.,
+            closing_speed: 0.5,
+            threat: 1.,
+            damage_dealt: 2.,
+            saturation: 0.75,
+        }
+    }
+}
+
+/// What's known about a potential target.
+#[derive(Debug, Clone)]
+pub struct TargetCandidate {
+    pub entt: Entity,
+    pub pos: TVec3,
+    pub linvel: TVec3,
+    /// See [`weapon_threat`].
+    pub threat: TReal,
+    /// Recent damage dealt to us, from the [`DamageLedger`].
+    pub damage_dealt: TReal,
+    /// Number of other flock members engaging the target.
+    pub saturation: usize,
+}
+
+/// Rough estimate of how dangerous the armaments of a craft are at the given distance.
+pub fn weapon_threat(weapons: &CraftWeaponsIndex, distance: TReal) -> TReal {
+    weapons
+        .entt_to_desc
+        .values()
+        .map(|desc| if distance <= desc.range { 1. } else { 0.25 })
+        .sum()
+}
+
+/// Higher is juicier.
+pub fn score_target(
+    weights: &TargetScoreWeights,
+    pos: TVec3,
+    linvel: TVec3,
+    candidate: &TargetCandidate,
+) -> TReal {
+    const DISTANCE_FALLOFF: TReal = 1_000.;
+    const SPEED_NORMALIZER: TReal = 100.;
+    const DAMAGE_NORMALIZER: TReal = 1_000.;
+
+    let offset = candidate.pos - pos;
+    let distance = offset.length();
+    let closing_speed = if distance > real::EPSILON {
+        (linvel - candidate.linvel).dot(offset / distance)
+    } else {
+        0.
+    };
+    (weights.distance / (1. + (distance / DISTANCE_FALLOFF)))
+        + (weights.closing_speed * (closing_speed / SPEED_NORMALIZER).clamp(-1., 1.))
+        + (weights.threat * candidate.threat)
+        + (weights.damage_dealt * (candidate.damage_dealt / DAMAGE_NORMALIZER))
+        - (weights.saturation * candidate.saturation as TReal)
+}
+
+/// Gathers the hostiles perceived by the boid, either through its own [`Contacts`] or
+/// its flock's [`FlockBlackboard`], within `radius`.
+#[allow(clippy::too_many_arguments)]
+pub fn perceived_hostiles(
+    boid_entt: Entity,
+    pos: TVec3,
+    radius: TReal,
+    contacts: &Contacts,
+    blackboard: Option<&FlockBlackboard>,
+    ledger: Option<&DamageLedger>,
+    index: &CraftSpatialIndex,
+    factions: &Factions,
+    weapons: &Query<&CraftWeaponsIndex>,
+    out: &mut Vec<TargetCandidate>,
+) {
+    index.for_each_within_radius(pos, radius, |entt, _| {
+        if entt == boid_entt || !factions.is_hostile(boid_entt, entt) {
+            return;
+        }
+        let (target_pos, target_linvel) = match contacts.get(entt) {
+            Some(contact) if contact.presence == ScanPresence::Boid => {
+                (contact.pos, contact.linvel)
+            }
+            _ => match blackboard.and_then(|b| b.track(entt)) {
+                Some(track) => (track.pos, track.linvel),
+                // can't shoot what you can't see
+                None => return,
+            },
+        };
+        let saturation = blackboard
+            .map(|b| {
+                let assigned = b.saturation(entt);
+                // don't count ourselves
+                if b.target_of(boid_entt) == Some(entt) {
+                    assigned - 1
+                } else {
+                    assigned
+                }
+            })
+            .unwrap_or_default();
+        out.push(TargetCandidate {
+            entt,
+            pos: target_pos,
+            linvel: target_linvel,
+            threat: weapons
+                .get(entt)
+                .map(|w| weapon_threat(w, target_pos.distance(pos)))
+                .unwrap_or_default(),
+            damage_dealt: ledger.map(|l| l.damage_from(entt)).unwrap_or_default(),
+            saturation,
+        });
+    });
+}
+
+#[test]
+fn target_scoring() {
+    let weights = TargetScoreWeights::default();
+    let candidate = |pos: TVec3| TargetCandidate {
+        entt: Entity::from_raw(1),
+        pos,
+        linvel: TVec3::ZERO,
+        threat: 0.,
+        damage_dealt: 0.,
+        saturation: 0,
+    };
+    let near = candidate(TVec3::Z * 100.);
+    let far = candidate(TVec3::Z * 2_000.);
+    assert!(
+        score_target(&weights, TVec3::ZERO, TVec3::ZERO, &near)
+            > score_target(&weights, TVec3::ZERO, TVec3::ZERO, &far)
+    );
+
+    // the one shooting at us should win out even if further away
+    let shooter = TargetCandidate {
+        damage_dealt: 2_000.,
+        ..far.clone()
+    };
+    assert!(
+        score_target(&weights, TVec3::ZERO, TVec3::ZERO, &shooter)
+            > score_target(&weights, TVec3::ZERO, TVec3::ZERO, &near)
+    );
+
+    // crowded targets are less attractive
+    let crowded = TargetCandidate {
+        saturation: 3,
+        ..near.clone()
+    };
+    assert!(
+        score_target(&weights, TVec3::ZERO, TVec3::ZERO, &crowded)
+            < score_target(&weights, TVec3::ZERO, TVec3::ZERO, &near)
+    );
+
+    // closing in is better than running away
+    assert!(
+        score_target(&weights, TVec3::ZERO, TVec3::Z * 50., &near)
+            > score_target(&weights, TVec3::ZERO, TVec3::Z * -50., &near)
+    );
+}
@@ -0,0 +1,484 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use big_brain::prelude::*;
+
+use super::{
+    spawn_strategy, strategy::*, targeting::DamageLedger, BoidMindConfig, BoidMindDirective,
+};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{
+        flock::{blackboard::FlockBlackboard, CraftFlock, CurrentFlockFormation, FlockMembers},
+        sensors::{radar::Contacts, spatial::CraftSpatialIndex, *},
+        tribe::Factions,
+    },
+};
+
+/// Opts the boid into having its strategies picked by a utility AI [`Thinker`]
+/// instead of straight from its [`BoidMindDirective`]. The directive is still
+/// used as the high-level goal. Insert along with [`thinker`].
+/// Craft mind component
+#[derive(Debug, Clone, Inspectable, Component)]
+pub struct UtilityMind {
+    /// Hostiles within this radius are worth attacking. In meters.
+    pub engagement_radius: TReal,
+    /// Members farther than this from the center of their flock start looking to regroup.
+    /// In meters.
+    pub cohesion_radius: TReal,
+    /// Recent damage, as kept by the [`DamageLedger`], at which the urge to evade maxes out.
+    pub evasion_damage: TReal,
+    /// How far to run from hostiles when there's no station to flee to. In meters.
+    pub flee_distance: TReal,
+}
+
+impl Default for UtilityMind {
+    fn default() -> Self {
+        Self {
+            engagement_radius: 2_000.,
+            cohesion_radius: 300.,
+            evasion_damage: 500.,
+            flee_distance: 1_500.,
+        }
+    }
+}
+
+/// The [`Thinker`] to go along with the [`UtilityMind`].
+pub fn thinker() -> ThinkerBuilder {
+    Thinker::build()
+        .picker(Highest)
+        .when(UtilityScorer::LowIntegrity, UtilityAction::Flee)
+        .when(UtilityScorer::LowAmmo, UtilityAction::Flee)
+        .when(UtilityScorer::UnderFire, UtilityAction::Evade)
+        .when(UtilityScorer::HostileNearby, UtilityAction::Attack)
+        .when(UtilityScorer::Straggling, UtilityAction::Regroup)
+        .otherwise(UtilityAction::Hold)
+}
+
+/// Big brain scorer component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub enum UtilityScorer {
+    /// Maxes out once integrity drops below the [`BoidMindConfig::repair_request_threshold`].
+    LowIntegrity,
+    /// Fraction of the craft's weapons that are out of ammo.
+    LowAmmo,
+    /// Recent damage taken.
+    UnderFire,
+    /// Closeness of the nearest perceived hostile.
+    HostileNearby,
+    /// Distance from the center of the craft's flock.
+    Straggling,
+}
+
+impl ScorerBuilder for UtilityScorer {
+    fn build(&self, cmd: &mut Commands, scorer: Entity, _actor: Entity) {
+        cmd.entity(scorer).insert(*self);
+    }
+}
+
+/// Big brain action component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub enum UtilityAction {
+    /// Go after hostiles, as the directive says if it's an attacking one.
+    Attack,
+    /// Throw off whoever's shooting at us.
+    Evade,
+    /// Get back to the flock's formation.
+    Regroup,
+    /// Carry on with the directive, holding position if there's none.
+    Hold,
+    /// Head to the nearest [`repair::ResupplyStation`] or away from hostiles.
+    Flee,
+}
+
+impl ActionBuilder for UtilityAction {
+    fn build(&self, cmd: &mut Commands, action: Entity, _actor: Entity) {
+        cmd.entity(action).insert(*self);
+    }
+}
+
+/// The closest hostile the boid knows about, either through its own [`Contacts`]
+/// or its flock's [`FlockBlackboard`].
+fn nearest_hostile(
+    boid_entt: Entity,
+    pos: TVec3,
+    contacts: &Contacts,
+    blackboard: Option<&FlockBlackboard>,
+    factions: &Factions,
+) -> Option<(Entity, TVec3)> {
+    contacts
+        .boids()
+        .map(|contact| (contact.entt, contact.pos))
+        .chain(
+            blackboard
+                .into_iter()
+                .flat_map(|b| b.tracks.values().map(|track| (track.entt, track.pos))),
+        )
+        .filter(|(entt, _)| factions.is_hostile(boid_entt, *entt))
+        .min_by(|(_, a), (_, b)| {
+            a.distance_squared(pos)
+                .partial_cmp(&b.distance_squared(pos))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+fn flock_center(members: &FlockMembers, index: &CraftSpatialIndex) -> Option<TVec3> {
+    let (sum, count) = members
+        .iter()
+        .filter_map(|entt| index.position(*entt))
+        .fold((TVec3::ZERO, 0), |(sum, count), pos| (sum + pos, count + 1));
+    if count > 0 {
+        Some(sum / count as TReal)
+    } else {
+        None
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn score(
+    mut scorers: Query<(&Actor, &UtilityScorer, &mut Score)>,
+    boids: Query<(
+        &GlobalTransform,
+        &UtilityMind,
+        &BoidMindConfig,
+        &attire::CraftIntegrity,
+        &Contacts,
+        &CraftWeaponsIndex,
+        Option<&DamageLedger>,
+        Option<&CraftFlock>,
+    )>,
+    ammo: Query<&arms::Ammunition>,
+    flocks: Query<(&FlockMembers, &FlockBlackboard)>,
+    index: Res<CraftSpatialIndex>,
+    factions: Factions,
+) {
+    for (Actor(actor), scorer, mut score) in scorers.iter_mut() {
+        let (xform, mind, config, integrity, contacts, weapons, ledger, flock) =
+            match boids.get(*actor) {
+                Ok(boid) => boid,
+                Err(err) => {
+                    tracing::error!(?err, "boid not found for UtilityScorer");
+                    continue;
+                }
+            };
+        let flock = flock.and_then(|f| flocks.get(f.0).ok());
+        let value = match scorer {
+            UtilityScorer::LowIntegrity => {
+                if integrity.is_below(config.repair_request_threshold) {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            UtilityScorer::LowAmmo => {
+                let (empty, count) = weapons
+                    .entt_to_desc
+                    .keys()
+                    .filter_map(|wpn| ammo.get(*wpn).ok())
+                    .fold((0, 0), |(empty, count), ammo| {
+                        (empty + ammo.is_empty() as usize, count + 1)
+                    });
+                if count > 0 {
+                    empty as TReal / count as TReal
+                } else {
+                    0.
+                }
+            }
+            UtilityScorer::UnderFire => {
+                let damage: TReal = ledger
+                    .map(|l| l.dealt_by.values().sum())
+                    .unwrap_or_default();
+                damage / mind.evasion_damage
+            }
+            UtilityScorer::HostileNearby => {
+                match nearest_hostile(
+                    *actor,
+                    xform.translation,
+                    contacts,
+                    flock.map(|(_, b)| b),
+                    &factions,
+                ) {
+                    // anything in range's worth at least half
+                    Some((_, pos)) => {
+                        let dist = pos.distance(xform.translation);
+                        if dist <= mind.engagement_radius {
+                            1. - (0.5 * dist / mind.engagement_radius)
+                        } else {
+                            0.
+                        }
+                    }
+                    None => 0.,
+                }
+            }
+            UtilityScorer::Straggling => match flock
+                .and_then(|(members, _)| flock_center(members, &index))
+            {
+                Some(center) => (center.distance(xform.translation) / mind.cohesion_radius) - 1.,
+                None => 0.,
+            },
+        };
+        score.set(value.clamp(0., 1.));
+    }
+}
+
+/// What the boid should be doing to carry out the action.
+#[allow(clippy::too_many_arguments)]
+fn action_directive(
+    action: UtilityAction,
+    boid_entt: Entity,
+    pos: TVec3,
+    goal: &BoidMindDirective,
+    mind: &UtilityMind,
+    contacts: &Contacts,
+    ledger: Option<&DamageLedger>,
+    flock: Option<(Entity, &FlockBlackboard)>,
+    formations: &Query<&CurrentFlockFormation>,
+    stations: &Query<(Entity, &GlobalTransform), With<repair::ResupplyStation>>,
+    index: &CraftSpatialIndex,
+    factions: &Factions,
+) -> BoidMindDirective {
+    let hold_here = BoidMindDirective::HoldPosition { pos };
+    // whoever's been hurting us the most, or the closest hostile otherwise
+    let threat = || {
+        ledger
+            .and_then(|l| {
+                l.dealt_by
+                    .iter()
+                    .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            })
+            .and_then(|(attacker, _)| index.position(*attacker).map(|pos| (*attacker, pos)))
+            .or_else(|| nearest_hostile(boid_entt, pos, contacts, flock.map(|(_, b)| b), factions))
+    };
+    match action {
+        UtilityAction::Attack => match goal {
+            BoidMindDirective::AttackPresue { .. } | BoidMindDirective::Engage { .. } => {
+                goal.clone()
+            }
+            _ => BoidMindDirective::Engage {
+                param: engage::Engage {
+                    engagement_radius: mind.engagement_radius,
+                    ..Default::default()
+                },
+            },
+        },
+        UtilityAction::Evade => BoidMindDirective::Evade {
+            param: evade::Evade {
+                threat: threat().map(|(entt, _)| entt),
+                ..Default::default()
+            },
+        },
+        UtilityAction::Regroup => match flock.and_then(|(flock, _)| formations.get(flock).ok()) {
+            Some(formation) => BoidMindDirective::JoinFomation {
+                formation: formation.formation,
+            },
+            None => hold_here,
+        },
+        UtilityAction::Hold => match goal {
+            BoidMindDirective::None => hold_here,
+            goal => goal.clone(),
+        },
+        UtilityAction::Flee => {
+            let closest = stations.iter().min_by(|(_, a), (_, b)| {
+                a.translation
+                    .distance_squared(pos)
+                    .partial_cmp(&b.translation.distance_squared(pos))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            match (closest, threat().map(|(_, pos)| pos)) {
+                (Some((station, _)), _) => BoidMindDirective::Resupply {
+                    station,
+                    resume: None,
+                },
+                (None, Some(threat_pos)) => BoidMindDirective::HoldPosition {
+                    pos: pos + ((pos - threat_pos).normalize_or_zero() * mind.flee_distance),
+                },
+                (None, None) => hold_here,
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn act(
+    mut commands: Commands,
+    mut actions: Query<(&Actor, &UtilityAction, &mut ActionState)>,
+    mut boids: Query<(
+        &BoidMindDirective,
+        ChangeTrackers<BoidMindDirective>,
+        &mut CurrentBoidStrategy,
+        &UtilityMind,
+        &Contacts,
+        &engine::EngineConfig,
+        &CraftDimensions,
+        Option<&DamageLedger>,
+        Option<&CraftFlock>,
+    )>,
+    objects: Query<&GlobalTransform>,
+    blackboards: Query<&FlockBlackboard>,
+    formations: Query<&CurrentFlockFormation>,
+    stations: Query<(Entity, &GlobalTransform), With<repair::ResupplyStation>>,
+    index: Res<CraftSpatialIndex>,
+    factions: Factions,
+) {
+    for (Actor(actor), action, mut state) in actions.iter_mut() {
+        let (goal, goal_tracker, mut cur_stg, mind, contacts, engine_config, dim, ledger, flock) =
+            match boids.get_mut(*actor) {
+                Ok(boid) => boid,
+                Err(err) => {
+                    tracing::error!(?err, "boid not found for UtilityAction");
+                    continue;
+                }
+            };
+        match *state {
+            ActionState::Requested => {}
+            // the goal changed underneath us, carry out the new one
+            ActionState::Executing if goal_tracker.is_changed() => {}
+            ActionState::Cancelled => {
+                // the next action will replace the strategy
+                *state = ActionState::Failure;
+                continue;
+            }
+            _ => continue,
+        }
+        let pos = objects
+            .get(*actor)
+            .expect_or_log("GlobalTransform not found on boid")
+            .translation;
+        let flock = flock.and_then(|f| blackboards.get(f.0).ok().map(|b| (f.0, b)));
+        let directive = action_directive(
+            *action,
+            *actor,
+            pos,
+            goal,
+            mind,
+            contacts,
+            ledger,
+            flock,
+            &formations,
+            &stations,
+            &index,
+            &factions,
+        );
+        tracing::debug!(boid_entt = ?actor, ?action, ?directive, "utility action");
+        for old in [cur_stg.strategy.take(), cur_stg.suspended.take()]
+            .into_iter()
+            .flatten()
+        {
+            commands.entity(old).despawn_recursive();
+        }
+        cur_stg.strategy = spawn_strategy(
+            &mut commands,
+            *actor,
+            &directive,
+            engine_config,
+            dim,
+            &objects,
+        );
+        *state = ActionState::Executing;
+    }
+}
+
+#[test]
+fn score_boid_state() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    let mut world = World::new();
+    world.insert_resource(CraftSpatialIndex::default());
+    world.insert_resource(crate::mind::tribe::FactionRelations::default());
+
+    let mind = UtilityMind::default();
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::identity())
+        .insert(mind.clone())
+        .insert(BoidMindConfig::default())
+        .insert(attire::CraftIntegrity {
+            remaining: 0.01,
+            factory: 1.,
+        })
+        .insert(Contacts::default())
+        .insert(CraftWeaponsIndex::default())
+        .insert(DamageLedger {
+            dealt_by: [(Entity::from_raw(1_000), mind.evasion_damage * 0.5)]
+                .into_iter()
+                .collect(),
+        })
+        .id();
+    let scorers = [
+        (UtilityScorer::LowIntegrity, 1.),
+        (UtilityScorer::LowAmmo, 0.),
+        (UtilityScorer::UnderFire, 0.5),
+        (UtilityScorer::HostileNearby, 0.),
+        (UtilityScorer::Straggling, 0.),
+    ]
+    .map(|(scorer, expected)| {
+        let entt = world
+            .spawn()
+            .insert(Actor(boid))
+            .insert(scorer)
+            .insert(Score::default())
+            .id();
+        (entt, scorer, expected)
+    });
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(score);
+    stage.run(&mut world);
+
+    for (entt, scorer, expected) in scorers {
+        let value = world.get::<Score>(entt).unwrap().get();
+        assert!((value - expected).abs() < 1e-5, "{scorer:?} {value}");
+    }
+}
+
+#[test]
+fn act_flees_to_station() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    let mut world = World::new();
+    world.insert_resource(CraftSpatialIndex::default());
+    world.insert_resource(crate::mind::tribe::FactionRelations::default());
+
+    world
+        .spawn()
+        .insert(repair::ResupplyStation::default())
+        .insert(GlobalTransform::from_translation(TVec3::X * 1_000.));
+    let old_strategy = world.spawn().id();
+    let boid = world
+        .spawn()
+        .insert(GlobalTransform::identity())
+        .insert(BoidMindDirective::HoldPosition { pos: TVec3::ZERO })
+        .insert(CurrentBoidStrategy {
+            strategy: Some(old_strategy),
+            suspended: None,
+        })
+        .insert(UtilityMind::default())
+        .insert(Contacts::default())
+        .insert(engine::EngineConfig::default())
+        .insert(CraftDimensions(TVec3::ONE * 8.))
+        .id();
+    let action = world
+        .spawn()
+        .insert(Actor(boid))
+        .insert(UtilityAction::Flee)
+        .insert(ActionState::Requested)
+        .id();
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(act);
+    stage.run(&mut world);
+
+    assert_eq!(
+        *world.get::<ActionState>(action).unwrap(),
+        ActionState::Executing
+    );
+    assert!(world.get_entity(old_strategy).is_none());
+    let strategy = world
+        .get::<CurrentBoidStrategy>(boid)
+        .unwrap()
+        .strategy
+        .expect("no strategy for Flee");
+    assert!(world.get_entity(strategy).is_some());
+}
@@ -0,0 +1,534 @@
+//! Small behaviour trees for the micro decisions of strategies.
+//!
+//! Trees are plain data owned by whoever ticks them, usually the state component
+//! of a strategy. Leaves only get to see a context value put together by the
+//! ticking system, which means they can't touch the world directly: actions
+//! write down what they want done in the context and the system carries it out
+//! after the tick.
+
+use deps::*;
+
+use bevy::prelude::*;
+use std::sync::Arc;
+
+use crate::mind::boid::{
+    steering::{compose, RoutineOwners},
+    strategy::*,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+impl From<bool> for Status {
+    #[inline]
+    fn from(success: bool) -> Self {
+        if success {
+            Status::Success
+        } else {
+            Status::Failure
+        }
+    }
+}
+
+pub type ConditionFn<C> = dyn Fn(&C) -> bool + Send + Sync;
+pub type ActionFn<C> = dyn Fn(&mut C) -> Status + Send + Sync;
+
+pub enum Node<C> {
+    /// Ticks its children in order until one fails. Picks up from the running
+    /// child on the next tick.
+    Sequence {
+        children: Vec<Node<C>>,
+        cursor: usize,
+    },
+    /// Ticks its children in order until one succeeds. Picks up from the running
+    /// child on the next tick.
+    Selector {
+        children: Vec<Node<C>>,
+        cursor: usize,
+    },
+    /// Ticks all its children every tick. Succeeds once `success_threshold` of them
+    /// succeed and fails once that's no longer possible.
+    Parallel {
+        children: Vec<Node<C>>,
+        success_threshold: usize,
+    },
+    Invert(Box<Node<C>>),
+    /// Succeeds once the child is done, whatever it returns.
+    Succeed(Box<Node<C>>),
+    /// Runs the child to success `times` times, one run per tick.
+    Repeat {
+        child: Box<Node<C>>,
+        times: u32,
+        count: u32,
+    },
+    /// Keeps running the child until it fails and then succeeds.
+    UntilFailure(Box<Node<C>>),
+    Condition(Arc<ConditionFn<C>>),
+    Action(Arc<ActionFn<C>>),
+}
+
+impl<C> Node<C> {
+    pub fn sequence(children: impl IntoIterator<Item = Node<C>>) -> Self {
+        Node::Sequence {
+            children: children.into_iter().collect(),
+            cursor: 0,
+        }
+    }
+
+    pub fn selector(children: impl IntoIterator<Item = Node<C>>) -> Self {
+        Node::Selector {
+            children: children.into_iter().collect(),
+            cursor: 0,
+        }
+    }
+
+    pub fn parallel(success_threshold: usize, children: impl IntoIterator<Item = Node<C>>) -> Self {
+        Node::Parallel {
+            children: children.into_iter().collect(),
+            success_threshold,
+        }
+    }
+
+    pub fn invert(child: Node<C>) -> Self {
+        Node::Invert(Box::new(child))
+    }
+
+    pub fn succeed(child: Node<C>) -> Self {
+        Node::Succeed(Box::new(child))
+    }
+
+    pub fn repeat(times: u32, child: Node<C>) -> Self {
+        Node::Repeat {
+            child: Box::new(child),
+            times,
+            count: 0,
+        }
+    }
+
+    pub fn until_failure(child: Node<C>) -> Self {
+        Node::UntilFailure(Box::new(child))
+    }
+
+    pub fn condition(condition: impl Fn(&C) -> bool + Send + Sync + 'static) -> Self {
+        Node::Condition(Arc::new(condition))
+    }
+
+    pub fn action(action: impl Fn(&mut C) -> Status + Send + Sync + 'static) -> Self {
+        Node::Action(Arc::new(action))
+    }
+
+    pub fn tick(&mut self, ctx: &mut C) -> Status {
+        match self {
+            Node::Sequence { children, cursor } => {
+                while *cursor < children.len() {
+                    match children[*cursor].tick(ctx) {
+                        Status::Success => *cursor += 1,
+                        Status::Running => return Status::Running,
+                        Status::Failure => {
+                            self.reset();
+                            return Status::Failure;
+                        }
+                    }
+                }
+                self.reset();
+                Status::Success
+            }
+            Node::Selector { children, cursor } => {
+                while *cursor < children.len() {
+                    match children[*cursor].tick(ctx) {
+                        Status::Failure => *cursor += 1,
+                        Status::Running => return Status::Running,
+                        Status::Success => {
+                            self.reset();
+                            return Status::Success;
+                        }
+                    }
+                }
+                self.reset();
+                Status::Failure
+            }
+            Node::Parallel {
+                children,
+                success_threshold,
+            } => {
+                let (mut successes, mut failures) = (0, 0);
+                for child in children.iter_mut() {
+                    match child.tick(ctx) {
+                        Status::Success => successes += 1,
+                        Status::Failure => failures += 1,
+                        Status::Running => {}
+                    }
+                }
+                let status = if successes >= *success_threshold {
+                    Status::Success
+                } else if children.len() - failures < *success_threshold {
+                    Status::Failure
+                } else {
+                    Status::Running
+                };
+                if status != Status::Running {
+                    self.reset();
+                }
+                status
+            }
+            Node::Invert(child) => match child.tick(ctx) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Succeed(child) => match child.tick(ctx) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            Node::Repeat {
+                child,
+                times,
+                count,
+            } => match child.tick(ctx) {
+                Status::Success => {
+                    *count += 1;
+                    if *count >= *times {
+                        *count = 0;
+                        Status::Success
+                    } else {
+                        Status::Running
+                    }
+                }
+                Status::Failure => {
+                    *count = 0;
+                    Status::Failure
+                }
+                Status::Running => Status::Running,
+            },
+            Node::UntilFailure(child) => match child.tick(ctx) {
+                Status::Failure => Status::Success,
+                _ => Status::Running,
+            },
+            Node::Condition(condition) => condition(ctx).into(),
+            Node::Action(action) => action(ctx),
+        }
+    }
+
+    /// Forgets about any running children.
+    pub fn reset(&mut self) {
+        match self {
+            Node::Sequence { children, cursor } | Node::Selector { children, cursor } => {
+                *cursor = 0;
+                for child in children.iter_mut() {
+                    child.reset();
+                }
+            }
+            Node::Parallel { children, .. } => {
+                for child in children.iter_mut() {
+                    child.reset();
+                }
+            }
+            Node::Repeat { child, count, .. } => {
+                *count = 0;
+                child.reset();
+            }
+            Node::Invert(child) | Node::Succeed(child) | Node::UntilFailure(child) => child.reset(),
+            Node::Condition(_) | Node::Action(_) => {}
+        }
+    }
+}
+
+impl<C> Clone for Node<C> {
+    fn clone(&self) -> Self {
+        match self {
+            Node::Sequence { children, cursor } => Node::Sequence {
+                children: children.clone(),
+                cursor: *cursor,
+            },
+            Node::Selector { children, cursor } => Node::Selector {
+                children: children.clone(),
+                cursor: *cursor,
+            },
+            Node::Parallel {
+                children,
+                success_threshold,
+            } => Node::Parallel {
+                children: children.clone(),
+                success_threshold: *success_threshold,
+            },
+            Node::Invert(child) => Node::Invert(child.clone()),
+            Node::Succeed(child) => Node::Succeed(child.clone()),
+            Node::Repeat {
+                child,
+                times,
+                count,
+            } => Node::Repeat {
+                child: child.clone(),
+                times: *times,
+                count: *count,
+            },
+            Node::UntilFailure(child) => Node::UntilFailure(child.clone()),
+            Node::Condition(condition) => Node::Condition(condition.clone()),
+            Node::Action(action) => Node::Action(action.clone()),
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for Node<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Node::Sequence { children, cursor } => f
+                .debug_struct("Sequence")
+                .field("cursor", cursor)
+                .field("children", children)
+                .finish(),
+            Node::Selector { children, cursor } => f
+                .debug_struct("Selector")
+                .field("cursor", cursor)
+                .field("children", children)
+                .finish(),
+            Node::Parallel {
+                children,
+                success_threshold,
+            } => f
+                .debug_struct("Parallel")
+                .field("success_threshold", success_threshold)
+                .field("children", children)
+                .finish(),
+            Node::Invert(child) => f.debug_tuple("Invert").field(child).finish(),
+            Node::Succeed(child) => f.debug_tuple("Succeed").field(child).finish(),
+            Node::Repeat {
+                child,
+                times,
+                count,
+            } => f
+                .debug_struct("Repeat")
+                .field("times", times)
+                .field("count", count)
+                .field("child", child)
+                .finish(),
+            Node::UntilFailure(child) => f.debug_tuple("UntilFailure").field(child).finish(),
+            Node::Condition(_) => f.write_str("Condition"),
+            Node::Action(_) => f.write_str("Action"),
+        }
+    }
+}
+
+pub struct BehaviourTree<C> {
+    pub root: Node<C>,
+}
+
+impl<C> Clone for BehaviourTree<C> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for BehaviourTree<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BehaviourTree")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+impl<C> BehaviourTree<C> {
+    pub fn new(root: Node<C>) -> Self {
+        Self { root }
+    }
+
+    #[inline]
+    pub fn tick(&mut self, ctx: &mut C) -> Status {
+        self.root.tick(ctx)
+    }
+
+    #[inline]
+    pub fn reset(&mut self) {
+        self.root.reset()
+    }
+}
+
+pub type RoutineSpawnerFn = dyn Fn(&mut Commands, Entity) -> Entity + Send + Sync;
+
+#[derive(Clone)]
+pub enum RoutineRequest {
+    /// `None` drops the routine.
+    Switch(Option<Entity>),
+    /// Spawn a new routine for the boid using the given spawner and switch to it.
+    Spawn(Arc<RoutineSpawnerFn>),
+}
+
+/// What the actions of a tree ticked on behalf of a boid strategy want done.
+#[derive(Clone, Default)]
+pub struct StrategyRequests {
+    pub fire_weapons: Option<bool>,
+    pub steering_routine: Option<RoutineRequest>,
+}
+
+impl StrategyRequests {
+    /// Carries out the requests. The requested routine goes into the `slot` of the
+    /// strategy's composer. Returns the routine if one was spawned, which the
+    /// strategy's expected to keep track of.
+    pub fn apply(
+        &mut self,
+        commands: &mut Commands,
+        boid_entt: Entity,
+        out: &mut BoidStrategyOutput,
+        composer: &mut Mut<compose::Compose>,
+        slot: usize,
+    ) -> Option<Entity> {
+        if let Some(fire_weapons) = self.fire_weapons.take() {
+            out.fire_weapons = fire_weapons;
+        }
+        let (routine, spawned) = match self.steering_routine.take() {
+            Some(RoutineRequest::Switch(routine)) => (routine, None),
+            Some(RoutineRequest::Spawn(spawner)) => {
+                let routine = spawner(commands, boid_entt);
+                // kept alive for as long as it's composed
+                commands.entity(routine).insert(RoutineOwners::default());
+                (Some(routine), Some(routine))
+            }
+            None => return None,
+        };
+        // avoid tripping change detection if nothing's changed
+        let changed = match (&composer.composer, routine) {
+            (compose::SteeringRoutineComposer::PriorityOverride { routines }, Some(routine)) => {
+                routines.get(slot) != Some(&routine)
+            }
+            (compose::SteeringRoutineComposer::PriorityOverride { routines }, None) => {
+                routines.len() > slot
+            }
+            (compose::SteeringRoutineComposer::Single { entt }, Some(routine)) => *entt != routine,
+            _ => true,
+        };
+        if changed {
+            match (&mut composer.composer, routine) {
+                (
+                    compose::SteeringRoutineComposer::PriorityOverride { routines },
+                    Some(routine),
+                ) => {
+                    if routines.len() > slot {
+                        routines[slot] = routine;
+                    } else {
+                        routines.push(routine);
+                    }
+                }
+                (compose::SteeringRoutineComposer::PriorityOverride { routines }, None) => {
+                    routines.truncate(slot);
+                }
+                (compose::SteeringRoutineComposer::Single { entt }, Some(routine)) => {
+                    *entt = routine;
+                }
+                (composer, routine) => {
+                    tracing::error!(?composer, ?routine, "unsupported routine request");
+                }
+            }
+        }
+        spawned
+    }
+}
+
+/// Contexts of trees ticked on behalf of boid strategies.
+pub trait StrategyTreeCtx {
+    fn requests(&mut self) -> &mut StrategyRequests;
+}
+
+/// Action leaf that sets whether to fire the weapons.
+pub fn fire_weapons<C: StrategyTreeCtx>(fire: bool) -> Node<C> {
+    Node::action(move |ctx: &mut C| {
+        ctx.requests().fire_weapons = Some(fire);
+        Status::Success
+    })
+}
+
+/// Action leaf that switches to the routine picked out of the context, failing if
+/// there's none.
+pub fn switch_routine<C: StrategyTreeCtx>(
+    pick: impl Fn(&C) -> Option<Entity> + Send + Sync + 'static,
+) -> Node<C> {
+    Node::action(move |ctx: &mut C| match pick(ctx) {
+        Some(routine) => {
+            ctx.requests().steering_routine = Some(RoutineRequest::Switch(Some(routine)));
+            Status::Success
+        }
+        None => Status::Failure,
+    })
+}
+
+/// Action leaf that drops the current routine.
+pub fn clear_routine<C: StrategyTreeCtx>() -> Node<C> {
+    Node::action(|ctx: &mut C| {
+        ctx.requests().steering_routine = Some(RoutineRequest::Switch(None));
+        Status::Success
+    })
+}
+
+/// Action leaf that has a new routine spawned and switched to.
+pub fn spawn_routine<C: StrategyTreeCtx>(
+    spawner: impl Fn(&mut Commands, Entity) -> Entity + Send + Sync + 'static,
+) -> Node<C> {
+    let spawner: Arc<RoutineSpawnerFn> = Arc::new(spawner);
+    Node::action(move |ctx: &mut C| {
+        ctx.requests().steering_routine = Some(RoutineRequest::Spawn(spawner.clone()));
+        Status::Success
+    })
+}
+
+#[test]
+fn behaviour_tree_composites() {
+    // (ticks, log)
+    type Ctx = (u32, Vec<&'static str>);
+    let log = |name: &'static str, status: Status| {
+        Node::action(move |ctx: &mut Ctx| {
+            ctx.1.push(name);
+            status
+        })
+    };
+    // takes two ticks
+    let slow = || {
+        Node::action(|ctx: &mut Ctx| {
+            ctx.0 += 1;
+            if ctx.0 % 2 == 0 {
+                Status::Success
+            } else {
+                Status::Running
+            }
+        })
+    };
+
+    // sequences resume from the running child
+    let mut tree = BehaviourTree::new(Node::sequence([
+        log("a", Status::Success),
+        slow(),
+        log("b", Status::Success),
+    ]));
+    let mut ctx: Ctx = Default::default();
+    assert_eq!(tree.tick(&mut ctx), Status::Running);
+    assert_eq!(tree.tick(&mut ctx), Status::Success);
+    assert_eq!(ctx.1, ["a", "b"]);
+
+    // selectors stop at the first success
+    let mut tree = BehaviourTree::new(Node::selector([
+        Node::condition(|_: &Ctx| false),
+        log("a", Status::Success),
+        log("b", Status::Success),
+    ]));
+    let mut ctx: Ctx = Default::default();
+    assert_eq!(tree.tick(&mut ctx), Status::Success);
+    assert_eq!(ctx.1, ["a"]);
+
+    let mut tree = BehaviourTree::new(Node::parallel(
+        2,
+        [log("a", Status::Success), log("b", Status::Failure), slow()],
+    ));
+    let mut ctx: Ctx = Default::default();
+    assert_eq!(tree.tick(&mut ctx), Status::Running);
+    assert_eq!(tree.tick(&mut ctx), Status::Success);
+
+    let mut tree = BehaviourTree::new(Node::invert(Node::repeat(2, log("a", Status::Success))));
+    let mut ctx: Ctx = Default::default();
+    assert_eq!(tree.tick(&mut ctx), Status::Running);
+    assert_eq!(tree.tick(&mut ctx), Status::Failure);
+    assert_eq!(ctx.1, ["a", "a"]);
+}
@@ -4,10 +4,11 @@ use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
 use educe::Educe;
 
-use crate::mind::*;
+use crate::{math::*, mind::*};
 
 pub mod strategy;
 use strategy::*;
+pub mod blackboard;
 pub mod formation;
 
 #[derive(Bundle)]
@@ -18,6 +19,7 @@ pub struct FlockMindBundle {
     pub active_strategy: CurrentFlockStrategy,
     pub active_formation: CurrentFlockFormation,
     pub directive: FlockMindDirective,
+    pub blackboard: blackboard::FlockBlackboard,
 }
 
 impl FlockMindBundle {
@@ -30,6 +32,7 @@ impl FlockMindBundle {
             },
             directive: Default::default(),
             change_events: Default::default(),
+            blackboard: Default::default(),
         }
     }
 }
@@ -89,6 +92,10 @@ pub enum FlockMindDirective {
     JoinFomation {
         formation: Entity,
     },
+    /// Pull back to the rally point together.
+    Withdraw {
+        rally: TVec3,
+    },
 }
 
 pub fn flock_mind(
@@ -119,12 +126,23 @@ pub fn flock_mind(
             FlockMindDirective::JoinFomation { .. } => {
                 todo!()
             }
+            FlockMindDirective::Withdraw { rally } => Some(
+                commands
+                    .spawn()
+                    .insert_bundle(strategy::withdraw::Bundle::new(
+                        strategy::withdraw::Withdraw { rally: *rally },
+                        flock_entt,
+                    ))
+                    .id(),
+            ),
         }
     }
 }
-/*
+
+/// The flock a craft's a member of.
+/// Craft mind component
 #[derive(Debug, Clone, Copy, Component)]
-pub struct CraftFlock(pub Entity); */
+pub struct CraftFlock(pub Entity);
 /*
 #[derive(Debug, Default, Component)]
 pub struct FlockChangeEvents {
@@ -151,13 +169,33 @@ pub struct FlockChangeEvents(bevy::ecs::event::Events<FlockChangeEvent>);
 #[educe(Deref, DerefMut)]
 pub struct FlockChangeEventsReader(bevy::ecs::event::ManualEventReader<FlockChangeEvent>);
 
+/// Removes the craft's [`CraftFlock`] if it's still a member of `flock`.
+pub struct LeaveFlock {
+    pub craft: Entity,
+    pub flock: Entity,
+}
+
+impl bevy::ecs::system::Command for LeaveFlock {
+    fn write(self, world: &mut World) {
+        let mut craft = match world.get_entity_mut(self.craft) {
+            Some(craft) => craft,
+            // despawned crafts leave on their own
+            None => return,
+        };
+        if matches!(craft.get::<CraftFlock>(), Some(CraftFlock(flock)) if *flock == self.flock) {
+            craft.remove::<CraftFlock>();
+        }
+    }
+}
+
 pub fn flock_members_change_listener(
+    mut commands: Commands,
     // new: Query<(Entity, &FlockMembers), Added<FlockMembers>>,
     mut queries: QuerySet<(
         // all
         QueryState<(&mut FlockChangeEvents,)>,
         // changed
-        QueryState<(&mut FlockMembers, &mut FlockChangeEvents), Changed<FlockMembers>>,
+        QueryState<(Entity, &mut FlockMembers, &mut FlockChangeEvents), Changed<FlockMembers>>,
     )>,
     // mut crafts: Query<(&mut boid::BoidMindDirective,)>,
     // mut cross_ref_index: ResMut<FlockCrossRefIndex>,
@@ -167,11 +205,17 @@ pub fn flock_members_change_listener(
         // add them to the global index
         cross_ref_index.insert(entt, members.clone());
     } */
-    for (mut members, mut events) in queries.q1().iter_mut() {
+    for (flock_entt, mut members, mut events) in queries.q1().iter_mut() {
         for removed in members.removed() {
+            // it might have joined another flock since
+            commands.add(LeaveFlock {
+                craft: removed,
+                flock: flock_entt,
+            });
             events.send(FlockChangeEvent::MemberRemoved { entt: removed });
         }
         for added in members.added() {
+            commands.entity(added).insert(CraftFlock(flock_entt));
             events.send(FlockChangeEvent::MemberAdded { entt: added });
         }
     }
@@ -182,3 +226,48 @@ pub fn flock_members_change_listener(
         cross_ref_index.remove(&entt);
     } */
 }
+
+#[test]
+fn switching_flocks_keeps_the_new_one() {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    let mut world = World::new();
+    let craft = world.spawn().id();
+    let spawn_flock = |world: &mut World| {
+        world
+            .spawn()
+            .insert(FlockMembers::default())
+            .insert(FlockChangeEvents::default())
+            .id()
+    };
+    // the new one gets processed first
+    let (new_flock, old_flock) = (spawn_flock(&mut world), spawn_flock(&mut world));
+    let mut stage = SystemStage::single_threaded().with_system(flock_members_change_listener);
+
+    world
+        .get_mut::<FlockMembers>(old_flock)
+        .unwrap()
+        .push(craft);
+    stage.run(&mut world);
+    assert_eq!(world.get::<CraftFlock>(craft).unwrap().0, old_flock);
+
+    // moved over in the same frame
+    world
+        .get_mut::<FlockMembers>(new_flock)
+        .unwrap()
+        .push(craft);
+    world
+        .get_mut::<FlockMembers>(old_flock)
+        .unwrap()
+        .remove(craft);
+    stage.run(&mut world);
+    assert_eq!(world.get::<CraftFlock>(craft).unwrap().0, new_flock);
+
+    // and leaving for good
+    world
+        .get_mut::<FlockMembers>(new_flock)
+        .unwrap()
+        .remove(craft);
+    stage.run(&mut world);
+    assert!(world.get::<CraftFlock>(craft).is_none());
+}
@@ -0,0 +1,215 @@
+use deps::*;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+
+use super::{CraftFlock, FlockMembers};
+use crate::{
+    math::*,
+    mind::{
+        sensors::radar::{Contacts, ScanPresence},
+        tribe::Faction,
+    },
+};
+
+/// A contact as seen by the flock as a whole.
+#[derive(Debug, Clone)]
+pub struct FusedTrack {
+    pub entt: Entity,
+    /// Estimated current position in world space.
+    pub pos: TVec3,
+    /// Estimated linear velocity in world space.
+    pub linvel: TVec3,
+    pub faction: Option<Faction>,
+    pub last_seen_secs: f64,
+    /// Likelihood of the track being accurate. In [0, 1].
+    pub confidence: TReal,
+    /// The members whose contacts contributed to this track.
+    pub spotters: smallvec::SmallVec<[Entity; 4]>,
+}
+
+/// Shared knowledge of the flock, aggregated from its members' [`Contacts`].
+/// Flock component.
+#[derive(Debug, Clone, Default, Component)]
+pub struct FlockBlackboard {
+    pub tracks: HashMap<Entity, FusedTrack>,
+    member_to_target: HashMap<Entity, Entity>,
+    target_to_members: HashMap<Entity, smallvec::SmallVec<[Entity; 4]>>,
+}
+
+impl FlockBlackboard {
+    #[inline]
+    pub fn track(&self, entt: Entity) -> Option<&FusedTrack> {
+        self.tracks.get(&entt)
+    }
+
+    #[inline]
+    pub fn target_of(&self, member: Entity) -> Option<Entity> {
+        self.member_to_target.get(&member).copied()
+    }
+
+    /// Number of members assigned to the target.
+    #[inline]
+    pub fn saturation(&self, target: Entity) -> usize {
+        self.target_to_members
+            .get(&target)
+            .map(|v| v.len())
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn assigned_to(&self, target: Entity) -> &[Entity] {
+        self.target_to_members
+            .get(&target)
+            .map(|v| &v[..])
+            .unwrap_or_default()
+    }
+
+    pub fn assign(&mut self, member: Entity, target: Entity) {
+        self.unassign(member);
+        self.member_to_target.insert(member, target);
+        self.target_to_members
+            .entry(target)
+            .or_default()
+            .push(member);
+    }
+
+    pub fn unassign(&mut self, member: Entity) {
+        if let Some(target) = self.member_to_target.remove(&member) {
+            if let Some(members) = self.target_to_members.get_mut(&target) {
+                for (ii, entt) in members.iter().enumerate() {
+                    if *entt == member {
+                        members.swap_remove(ii);
+                        break;
+                    }
+                }
+                if members.is_empty() {
+                    self.target_to_members.remove(&target);
+                }
+            }
+        }
+    }
+
+    /// Assigns the member to whichever of the candidates has the least members
+    /// assigned, favoring the earlier candidates on ties.
+    pub fn assign_least_saturated(
+        &mut self,
+        member: Entity,
+        candidates: impl IntoIterator<Item = Entity>,
+    ) -> Option<Entity> {
+        // don't count the member against its own current target
+        self.unassign(member);
+        let target = candidates
+            .into_iter()
+            .enumerate()
+            .min_by_key(|(ii, entt)| (self.saturation(*entt), *ii))
+            .map(|(_, entt)| entt)?;
+        self.assign(member, target);
+        Some(target)
+    }
+}
+
+/// Lets systems get at the [`FlockBlackboard`] of a boid's flock.
+#[derive(SystemParam)]
+pub struct FlockBlackboards<'w, 's> {
+    crafts: Query<'w, 's, &'static CraftFlock>,
+    blackboards: Query<'w, 's, &'static FlockBlackboard>,
+}
+
+impl<'w, 's> FlockBlackboards<'w, 's> {
+    pub fn of_boid(&self, boid_entt: Entity) -> Option<&FlockBlackboard> {
+        self.crafts
+            .get(boid_entt)
+            .ok()
+            .and_then(|flock| self.blackboards.get(flock.0).ok())
+    }
+}
+
+pub fn fuse_contacts(
+    time: Res<Time>,
+    mut flocks: Query<(&FlockMembers, &mut FlockBlackboard)>,
+    crafts: Query<&Contacts>,
+    mut weight_sums: Local<HashMap<Entity, TReal>>,
+) {
+    let now = time.seconds_since_startup();
+    for (members, mut board) in flocks.iter_mut() {
+        let board = &mut *board;
+        board.tracks.clear();
+        weight_sums.clear();
+        for member in members.iter() {
+            let contacts = match crafts.get(*member) {
+                Ok(contacts) => contacts,
+                Err(_) => continue,
+            };
+            for contact in contacts.iter() {
+                // no need to track ourselves
+                if contact.presence != ScanPresence::Boid || members.contains(&contact.entt) {
+                    continue;
+                }
+                let weight = contact.track_quality.max(real::EPSILON);
+                let pos = contact.predicted_pos(now);
+                let track = board
+                    .tracks
+                    .entry(contact.entt)
+                    .or_insert_with(|| FusedTrack {
+                        entt: contact.entt,
+                        pos: TVec3::ZERO,
+                        linvel: TVec3::ZERO,
+                        faction: contact.faction,
+                        last_seen_secs: contact.last_seen_secs,
+                        // accumulates the odds of all the contacts being wrong first
+                        confidence: 1.,
+                        spotters: Default::default(),
+                    });
+                *weight_sums.entry(contact.entt).or_default() += weight;
+                track.pos += pos * weight;
+                track.linvel += contact.linvel * weight;
+                track.confidence *= 1. - contact.track_quality;
+                track.last_seen_secs = track.last_seen_secs.max(contact.last_seen_secs);
+                track.spotters.push(*member);
+            }
+        }
+        for track in board.tracks.values_mut() {
+            let weight_sum = weight_sums[&track.entt];
+            track.pos /= weight_sum;
+            track.linvel /= weight_sum;
+            track.confidence = 1. - track.confidence;
+        }
+
+        // drop assignments to targets that are no longer tracked or by members who left
+        let stale: smallvec::SmallVec<[Entity; 4]> = board
+            .member_to_target
+            .iter()
+            .filter(|(member, target)| {
+                !members.contains(*member) || !board.tracks.contains_key(*target)
+            })
+            .map(|(member, _)| *member)
+            .collect();
+        for member in stale {
+            board.unassign(member);
+        }
+    }
+}
+
+#[test]
+fn target_assignment_saturation() {
+    let mut board = FlockBlackboard::default();
+    let (a, b, c) = (
+        Entity::from_raw(0),
+        Entity::from_raw(1),
+        Entity::from_raw(2),
+    );
+    let (t0, t1) = (Entity::from_raw(10), Entity::from_raw(11));
+
+    assert_eq!(board.assign_least_saturated(a, [t0, t1]), Some(t0));
+    assert_eq!(board.assign_least_saturated(b, [t0, t1]), Some(t1));
+    assert_eq!(board.assign_least_saturated(c, [t0, t1]), Some(t0));
+    assert_eq!(board.saturation(t0), 2);
+
+    // reassigning shouldn't count against the old target
+    assert_eq!(board.assign_least_saturated(c, [t0, t1]), Some(t0));
+    assert_eq!(board.saturation(t0), 2);
+
+    board.unassign(a);
+    assert_eq!(board.target_of(a), None);
+    assert_eq!(board.assigned_to(t0), &[c]);
+}
@@ -150,7 +150,8 @@ pub struct FormationOutput {
     pub pos: TVec3,
     pub linvel: TVec3,
     pub pos_linvel: TVec3,
-    pub facing: TVec3,
+    /// Full orientation in world basis, forward and up.
+    pub facing: TQuat,
 }
 
 #[derive(Debug, Default, Component)]
@@ -238,6 +239,7 @@ pub fn butler(
                 *member,
                 FormationOutput {
                     pos: xform.translation,
+                    facing: xform.rotation,
                     ..Default::default()
                 },
             );
@@ -277,6 +279,7 @@ pub fn butler(
                 added,
                 FormationOutput {
                     pos: xform.translation,
+                    facing: xform.rotation,
                     ..Default::default()
                 },
             );
@@ -310,6 +313,7 @@ pub fn butler(
                         entt,
                         FormationOutput {
                             pos: xform.translation,
+                            facing: xform.rotation,
                             ..Default::default()
                         },
                     );
@@ -353,7 +357,7 @@ pub fn update(
         let (anchor_state,) = anchors
             .get(state.shadow_leader_anchor.unwrap_or_log())
             .unwrap_or_log();
-        let facing = anchor_state.rot * -TVec3::Z;
+        let facing = anchor_state.rot;
         match &pattern {
             FormationPattern::Sphere { radius } => {
                 // TODO: LRU cache the rays
@@ -5,6 +5,7 @@ use bevy_inspector_egui::Inspectable;
 
 pub mod cas;
 pub mod form_up;
+pub mod withdraw;
 
 #[derive(Debug, Clone, Default, Component, Reflect, Inspectable)]
 pub struct CurrentFlockStrategy {
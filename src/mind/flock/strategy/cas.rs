@@ -19,7 +19,6 @@ pub struct CASState {
     pub avg_vel: TVec3,
     pub center_sum: TVec3,
     pub center: TVec3,
-    pub craft_positions: Vec<TVec3>,
     pub member_count: usize,
 }
 
@@ -86,12 +85,10 @@ pub fn update(
         let members = flocks
             .get(strategy.flock_entt)
             .expect_or_log("unable to find FlockMind for new strategy");
-        state.craft_positions.clear();
         state.vel_sum = TVec3::ZERO;
         state.center_sum = TVec3::ZERO;
         for craft in members.iter() {
             if let Ok((xform, vel)) = crafts.get(*craft) {
-                state.craft_positions.push(xform.translation);
                 state.vel_sum += TVec3::from(vel.linvel);
                 state.center_sum += xform.translation;
             } else {
@@ -103,3 +100,102 @@ pub fn update(
         state.center = state.center_sum / members.len() as TReal;
     }
 }
+
+/// Ticks a large flock headlessly: the [`CAS`] strategy and the `fly_with_flock`
+/// and `avoid_crafts` routines of every member, all querying the spatial index.
+/// Sensors and targeting aren't covered.
+/// Run with `cargo test --release -- --ignored --nocapture flock_bench`.
+#[test]
+#[ignore]
+fn flock_bench() {
+    use crate::craft::{engine, CraftDimensions};
+    use crate::mind::{
+        boid::steering::{avoid_crafts, fly_with_flock, ActiveSteeringRoutine},
+        flock::CraftFlock,
+        sensors::spatial,
+        CraftMindSystems,
+    };
+    use rand::prelude::*;
+
+    const BOID_COUNT: usize = 600;
+    const TICKS: u32 = 100;
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<spatial::CraftSpatialIndex>()
+        .add_system(spatial::rebuild.label(CraftMindSystems::SpatialIndex))
+        .add_system(
+            update
+                .label(CraftMindSystems::FlockStrategy)
+                .after(CraftMindSystems::SpatialIndex),
+        )
+        .add_system(fly_with_flock::update.after(CraftMindSystems::FlockStrategy))
+        .add_system(avoid_crafts::update.after(CraftMindSystems::SpatialIndex));
+
+    let flock_entt = app.world.spawn().id();
+    let strategy_entt = app
+        .world
+        .spawn()
+        .insert(FlockStrategy::new(
+            flock_entt,
+            super::FlockStrategyKind::of::<CAS>(),
+        ))
+        .insert(CASState::default())
+        .insert(ActiveFlockStrategy)
+        .id();
+    let mut members = FlockMembers::default();
+    let mut rng = StdRng::seed_from_u64(420);
+    for _ in 0..BOID_COUNT {
+        let pos = TVec3::new(
+            rng.gen_range(-1_500.0..1_500.),
+            rng.gen_range(-1_500.0..1_500.),
+            rng.gen_range(-1_500.0..1_500.),
+        );
+        let boid_entt = app
+            .world
+            .spawn()
+            .insert(GlobalTransform::from_translation(pos))
+            .insert(RigidBodyVelocityComponent(Default::default()))
+            .insert(CraftDimensions(TVec3::ONE * 8.))
+            .insert(CraftFlock(flock_entt))
+            .insert(engine::LinearEngineState::default())
+            .insert(engine::EngineConfig::default())
+            .id();
+        app.world
+            .spawn()
+            .insert_bundle(fly_with_flock::Bundle::new(
+                fly_with_flock::FlyWithFlock {
+                    flock_strategy_entt: strategy_entt,
+                },
+                boid_entt,
+            ))
+            .insert(ActiveSteeringRoutine);
+        app.world
+            .spawn()
+            .insert_bundle(avoid_crafts::Bundle::new(
+                Default::default(),
+                boid_entt,
+                Default::default(),
+            ))
+            .insert(ActiveSteeringRoutine);
+        members.push(boid_entt);
+    }
+    app.world.entity_mut(flock_entt).insert(members);
+
+    // warm up the allocations
+    app.update();
+    let start = std::time::Instant::now();
+    for _ in 0..TICKS {
+        app.update();
+    }
+    let tick = start.elapsed() / TICKS;
+
+    assert_eq!(
+        app.world
+            .get::<CASState>(strategy_entt)
+            .unwrap()
+            .member_count,
+        BOID_COUNT
+    );
+    println!("{BOID_COUNT} boid flock | {tick:?}/tick");
+}
@@ -0,0 +1,75 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{super::FlockMembers, ActiveFlockStrategy, FlockStrategy, FlockStrategyBundle};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{boid::strategy::retreat, *},
+};
+
+/// Pulls the whole flock back to the rally point together, the members keeping
+/// their places relative to each other at the pace of the slowest one.
+#[derive(Debug, Clone, Component)]
+pub struct Withdraw {
+    /// In world space.
+    pub rally: TVec3,
+}
+
+pub type Bundle = FlockStrategyBundle<Withdraw>;
+
+/// Members are kept at most this far from the rally point. In meters.
+const MAX_SPREAD: TReal = 300.;
+
+pub fn butler(
+    mut commands: Commands,
+    strategies: Query<(Entity, &Withdraw, &FlockStrategy), Added<Withdraw>>,
+    flocks: Query<&FlockMembers>,
+    mut crafts: Query<(
+        &GlobalTransform,
+        &engine::EngineConfig,
+        &mut boid::BoidMindDirective,
+    )>,
+) {
+    for (strategy_entt, param, strategy) in strategies.iter() {
+        let members = flocks
+            .get(strategy.flock_entt)
+            .expect_or_log("unable to find Flock for new strategy");
+        let (pos_sum, pace, count) = members
+            .iter()
+            .filter_map(|entt| crafts.get(*entt).ok())
+            .fold(
+                (TVec3::ZERO, TReal::INFINITY, 0),
+                |(sum, pace, count), (xform, engine_config, _)| {
+                    (
+                        sum + xform.translation,
+                        pace.min(engine_config.linvel_limit.z),
+                        count + 1,
+                    )
+                },
+            );
+        if count == 0 {
+            tracing::error!(flock_entt = ?strategy.flock_entt, "no members found to withdraw");
+            continue;
+        }
+        let center = pos_sum / count as TReal;
+        for boid_entt in members.iter() {
+            // members that aren't boids were left out of the pace too
+            let (xform, _, mut directive) = match crafts.get_mut(*boid_entt) {
+                Ok(craft) => craft,
+                Err(_) => continue,
+            };
+            // keep the arrangement the flock's in
+            let slot = (xform.translation - center).clamp_length_max(MAX_SPREAD);
+            *directive = boid::BoidMindDirective::Retreat {
+                param: retreat::Retreat {
+                    pace: Some(pace),
+                    ..retreat::Retreat::new(retreat::RallyPoint::Position(param.rally + slot))
+                },
+                resume: None,
+            };
+        }
+        commands.entity(strategy_entt).insert(ActiveFlockStrategy);
+    }
+}
@@ -0,0 +1,404 @@
+//! Pathfinding around large static obstacles.
+//!
+//! Obstacles are reduced to their bounding spheres and rasterized into a
+//! sparse voxel grid that's searched with A*. The resulting paths are pulled
+//! taut with line of sight checks against the spheres.
+
+use deps::*;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::craft::attire::ColliderGroups;
+use crate::math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NavObstacle {
+    pub center: TVec3,
+    pub radius: TReal,
+}
+
+/// Sparse voxel grid of the space taken up by `SOLID` colliders.
+/// Resource.
+#[derive(Debug, Clone)]
+pub struct NavGrid {
+    /// Edge length of the grid's cells. In meters.
+    pub cell_size: TReal,
+    /// Obstacles are inflated by this to leave room for the crafts.
+    pub clearance: TReal,
+    /// Bumped every rebuild so that paths know when they're stale.
+    pub version: u64,
+    /// Obstacles that moved less than this are considered still. In meters.
+    pub movement_tolerance: TReal,
+    /// Give up on A* searches that take more than these many expansions.
+    pub max_expansions: usize,
+    obstacles: Vec<NavObstacle>,
+    blocked: HashSet<IVec3>,
+    min_cell: IVec3,
+    max_cell: IVec3,
+}
+
+impl Default for NavGrid {
+    fn default() -> Self {
+        Self::new(25., 15.)
+    }
+}
+
+impl NavGrid {
+    pub fn new(cell_size: TReal, clearance: TReal) -> Self {
+        Self {
+            cell_size,
+            clearance,
+            version: 0,
+            movement_tolerance: 5.,
+            max_expansions: 20_000,
+            obstacles: Default::default(),
+            blocked: Default::default(),
+            min_cell: IVec3::ZERO,
+            max_cell: IVec3::ZERO,
+        }
+    }
+
+    #[inline]
+    pub fn obstacles(&self) -> &[NavObstacle] {
+        &self.obstacles[..]
+    }
+
+    #[inline]
+    fn cell_of(&self, pos: TVec3) -> IVec3 {
+        (pos / self.cell_size).floor().as_ivec3()
+    }
+
+    #[inline]
+    fn cell_center(&self, cell: IVec3) -> TVec3 {
+        (cell.as_vec3() + TVec3::splat(0.5)) * self.cell_size
+    }
+
+    #[inline]
+    pub fn is_blocked(&self, pos: TVec3) -> bool {
+        self.blocked.contains(&self.cell_of(pos))
+    }
+
+    /// Whether the obstacles differ enough from the current ones to warrant a
+    /// rebuild. The order they come in doesn't matter.
+    pub fn differs(&self, obstacles: &[NavObstacle]) -> bool {
+        if obstacles.len() != self.obstacles.len() {
+            return true;
+        }
+        let mut obstacles = obstacles.to_vec();
+        sort_obstacles(&mut obstacles[..]);
+        obstacles.iter().zip(self.obstacles.iter()).any(|(a, b)| {
+            a.center.distance(b.center) > self.movement_tolerance
+                || (a.radius - b.radius).abs() > self.movement_tolerance
+        })
+    }
+
+    pub fn rebuild(&mut self, mut obstacles: Vec<NavObstacle>) {
+        sort_obstacles(&mut obstacles[..]);
+        self.blocked.clear();
+        self.min_cell = IVec3::splat(i32::MAX);
+        self.max_cell = IVec3::splat(i32::MIN);
+        // a cell's blocked if any part of it might be in an obstacle
+        let half_diagonal = self.cell_size * 0.5 * TReal::sqrt(3.);
+        for obstacle in &obstacles {
+            let reach = obstacle.radius + self.clearance;
+            let min = self.cell_of(obstacle.center - TVec3::splat(reach));
+            let max = self.cell_of(obstacle.center + TVec3::splat(reach));
+            self.min_cell = self.min_cell.min(min);
+            self.max_cell = self.max_cell.max(max);
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    for z in min.z..=max.z {
+                        let cell = IVec3::new(x, y, z);
+                        if self.cell_center(cell).distance(obstacle.center) <= reach + half_diagonal
+                        {
+                            self.blocked.insert(cell);
+                        }
+                    }
+                }
+            }
+        }
+        self.obstacles = obstacles;
+        self.version += 1;
+    }
+
+    /// Whether a straight line from `from` to `to` stays clear of the inflated
+    /// obstacles.
+    pub fn segment_clear(&self, from: TVec3, to: TVec3) -> bool {
+        let seg = to - from;
+        let seg_len_squared = seg.length_squared();
+        self.obstacles.iter().all(|obstacle| {
+            let t = if seg_len_squared > TReal::EPSILON {
+                ((obstacle.center - from).dot(seg) / seg_len_squared).clamp(0., 1.)
+            } else {
+                0.
+            };
+            let reach = obstacle.radius + self.clearance;
+            (from + (seg * t)).distance_squared(obstacle.center) > reach * reach
+        })
+    }
+
+    /// Waypoints from `start` to `goal`, both included, that steer clear of the
+    /// obstacles. `None` if there's no way through.
+    pub fn find_path(&self, start: TVec3, goal: TVec3) -> Option<Vec<TVec3>> {
+        if self.segment_clear(start, goal) {
+            return Some(vec![start, goal]);
+        }
+        let start_cell = self.cell_of(start);
+        let goal_cell = self.cell_of(goal);
+        // leave some room to go around the obstacles on the edges
+        let min = self.min_cell.min(start_cell).min(goal_cell) - IVec3::ONE;
+        let max = self.max_cell.max(start_cell).max(goal_cell) + IVec3::ONE;
+        let passable = |cell: IVec3| {
+            cell == start_cell
+                || cell == goal_cell
+                || (cell.cmpge(min).all() && cell.cmple(max).all() && !self.blocked.contains(&cell))
+        };
+        let heuristic = |cell: IVec3| (goal_cell - cell).as_vec3().length();
+
+        let mut open = std::collections::BinaryHeap::new();
+        let mut came_from: HashMap<IVec3, IVec3> = Default::default();
+        let mut cost_so_far: HashMap<IVec3, TReal> = Default::default();
+        let mut closed: HashSet<IVec3> = Default::default();
+        open.push(OpenNode {
+            priority: heuristic(start_cell),
+            cost: 0.,
+            cell: start_cell,
+        });
+        cost_so_far.insert(start_cell, 0.);
+        let mut expansions = 0;
+        let mut found = false;
+        while let Some(OpenNode { cell, cost, .. }) = open.pop() {
+            if cell == goal_cell {
+                found = true;
+                break;
+            }
+            // entries left behind by cheaper ways to the cell
+            if cost > cost_so_far[&cell] || !closed.insert(cell) {
+                continue;
+            }
+            expansions += 1;
+            if expansions > self.max_expansions {
+                tracing::warn!(?start, ?goal, "nav search ran out of expansions");
+                return None;
+            }
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let offset = IVec3::new(x, y, z);
+                        if offset == IVec3::ZERO {
+                            continue;
+                        }
+                        let next = cell + offset;
+                        if closed.contains(&next) || !passable(next) {
+                            continue;
+                        }
+                        let next_cost = cost + offset.as_vec3().length();
+                        if cost_so_far
+                            .get(&next)
+                            .map(|old| next_cost < *old)
+                            .unwrap_or(true)
+                        {
+                            cost_so_far.insert(next, next_cost);
+                            came_from.insert(next, cell);
+                            open.push(OpenNode {
+                                priority: next_cost + heuristic(next),
+                                cost: next_cost,
+                                cell: next,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        if !found {
+            return None;
+        }
+
+        let mut cells = vec![goal_cell];
+        let mut cur = goal_cell;
+        while let Some(prev) = came_from.get(&cur) {
+            cells.push(*prev);
+            cur = *prev;
+        }
+        cells.reverse();
+        let mut raw = Vec::with_capacity(cells.len());
+        raw.push(start);
+        // the start and goal cells are stood in by the actual points
+        raw.extend(
+            cells[1..cells.len().saturating_sub(1)]
+                .iter()
+                .map(|cell| self.cell_center(*cell)),
+        );
+        raw.push(goal);
+        Some(self.pull_taut(&raw[..]))
+    }
+
+    /// Skips over waypoints that can be seen past.
+    fn pull_taut(&self, raw: &[TVec3]) -> Vec<TVec3> {
+        let mut path = vec![raw[0]];
+        let mut anchor = 0;
+        while anchor < raw.len() - 1 {
+            let mut next = anchor + 1;
+            for candidate in (anchor + 2..raw.len()).rev() {
+                if self.segment_clear(raw[anchor], raw[candidate]) {
+                    next = candidate;
+                    break;
+                }
+            }
+            path.push(raw[next]);
+            anchor = next;
+        }
+        path
+    }
+}
+
+/// Puts the obstacles in a canonical order for [`NavGrid::differs`].
+fn sort_obstacles(obstacles: &mut [NavObstacle]) {
+    obstacles.sort_by(|a, b| {
+        (a.center.to_array(), a.radius)
+            .partial_cmp(&(b.center.to_array(), b.radius))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OpenNode {
+    priority: TReal,
+    /// Of getting to the cell when it was pushed.
+    cost: TReal,
+    cell: IVec3,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // reversed for a min heap
+        other
+            .priority
+            .partial_cmp(&self.priority)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    }
+}
+
+/// Rebuilds the [`NavGrid`] whenever the `SOLID` colliders that aren't crafts
+/// get added, removed or moved.
+pub fn rebuild(
+    mut grid: ResMut<NavGrid>,
+    // the entities behind the current obstacles, to tell their removals apart
+    mut obstacle_entts: Local<HashSet<Entity>>,
+    colliders: Query<(
+        Entity,
+        &ColliderShapeComponent,
+        &ColliderPositionComponent,
+        &ColliderFlagsComponent,
+    )>,
+    changed: Query<
+        &ColliderFlagsComponent,
+        Or<(
+            Added<ColliderShapeComponent>,
+            Changed<ColliderPositionComponent>,
+        )>,
+    >,
+    removed: RemovedComponents<ColliderShapeComponent>,
+) {
+    // crafts and projectiles move every frame so only look at obstacles
+    let is_obstacle = |flags: &ColliderFlagsComponent| {
+        flags.collision_groups.memberships == ColliderGroups::SOLID.bits()
+    };
+    if !changed.iter().any(is_obstacle)
+        && !removed.iter().any(|entt| obstacle_entts.contains(&entt))
+    {
+        return;
+    }
+    obstacle_entts.clear();
+    let obstacles = colliders
+        .iter()
+        .filter(|(_, _, _, flags)| is_obstacle(flags))
+        .map(|(entt, shape, pos, _)| {
+            obstacle_entts.insert(entt);
+            let sphere = shape.compute_bounding_sphere(pos);
+            NavObstacle {
+                center: (*sphere.center()).into(),
+                radius: sphere.radius(),
+            }
+        })
+        .collect::<Vec<_>>();
+    if grid.differs(&obstacles[..]) {
+        grid.rebuild(obstacles);
+        tracing::debug!(version = grid.version, "nav grid rebuilt");
+    }
+}
+
+#[test]
+fn nav_grid_paths_around_obstacles() {
+    let mut grid = NavGrid::new(10., 5.);
+    grid.rebuild(vec![
+        NavObstacle {
+            center: TVec3::ZERO,
+            radius: 50.,
+        },
+        NavObstacle {
+            center: TVec3::new(0., 0., -150.),
+            radius: 30.,
+        },
+    ]);
+
+    // nothing in the way
+    let start = TVec3::new(100., 0., 100.);
+    let path = grid.find_path(start, TVec3::new(100., 0., -100.)).unwrap();
+    assert_eq!(path.len(), 2);
+
+    // straight through the big one
+    let (start, goal) = (TVec3::new(-120., 0., 0.), TVec3::new(120., 0., 0.));
+    let path = grid.find_path(start, goal).unwrap();
+    assert!(path.len() > 2, "{path:?}");
+    assert_eq!(*path.first().unwrap(), start);
+    assert_eq!(*path.last().unwrap(), goal);
+    let mut length = 0.;
+    for pair in path.windows(2) {
+        assert!(grid.segment_clear(pair[0], pair[1]), "{path:?}");
+        length += pair[0].distance(pair[1]);
+    }
+    assert!(length > start.distance(goal));
+    // and not some wild detour
+    assert!(length < start.distance(goal) * 1.6, "{length}");
+
+    // the same obstacles in a different order are no different
+    let mut shuffled = grid.obstacles().to_vec();
+    shuffled.reverse();
+    assert!(!grid.differs(&shuffled[..]));
+
+    // moving an obstacle bumps the version
+    let version = grid.version;
+    let moved = vec![
+        NavObstacle {
+            center: TVec3::new(0., 200., 0.),
+            radius: 50.,
+        },
+        NavObstacle {
+            center: TVec3::new(0., 0., -150.),
+            radius: 30.,
+        },
+    ];
+    assert!(grid.differs(&moved[..]));
+    grid.rebuild(moved);
+    assert!(grid.version > version);
+    assert_eq!(grid.find_path(start, goal).unwrap().len(), 2);
+}
@@ -509,6 +509,20 @@ pub fn setup_markers(
         .insert(VelocityDirMarker);
 }
 
+/// Only the [`CurrentCraft`]'s predicted path gets drawn.
+pub fn show_current_craft_prediction(
+    cur_craft: Res<CurrentCraft>,
+    mut crafts: Query<(Entity, &mut trajectory::PredictedTrajectory)>,
+) {
+    for (entt, mut prediction) in crafts.iter_mut() {
+        let draw = Some(entt) == cur_craft.entt;
+        // avoid tripping change detection
+        if prediction.draw != draw {
+            prediction.draw = draw;
+        }
+    }
+}
+
 pub fn update_ui_markers(
     mut query: QuerySet<(
         QueryState<(&mut Style, &mut Visibility, &CalculatedSize), With<CraftFwdMarker>>,
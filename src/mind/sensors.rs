@@ -11,6 +11,9 @@ use crate::{
     mind::boid::{steering::*, strategy::*},
 };
 
+pub mod radar;
+pub mod spatial;
+
 /// Used to store entity data for [`RemovedComponents`] usage.
 #[derive(Debug, Component)]
 pub struct CrossReferenceIndex<P> {
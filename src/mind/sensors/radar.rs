@@ -0,0 +1,414 @@
+use deps::*;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::{
+    craft::{attire::*, signature::CraftSignature, *},
+    math::*,
+    mind::{
+        sensors::spatial::CraftSpatialIndex,
+        tribe::{Faction, Factions},
+    },
+};
+
+/// Periodically sweeps the surroundings of a craft for objects to put into
+/// its [`Contacts`].
+/// Craft mind component
+#[derive(Debug, Clone, Component, Reflect, Inspectable)]
+pub struct Radar {
+    /// In meters.
+    pub range: TReal,
+    /// Half angle of the detection cone around the craft's forward.
+    /// Anything at or above PI makes it omnidirectional. In radians.
+    pub half_fov: TReal,
+    /// In seconds.
+    pub sweep_period: TReal,
+    /// A craft with this signature at max range has even odds of getting detected
+    /// on any single sweep. Odds improve with the inverse square of distance.
+    pub reference_signature: TReal,
+    /// How long lost contacts are remembered for. In seconds.
+    pub memory_secs: TReal,
+}
+
+impl Default for Radar {
+    fn default() -> Self {
+        Self {
+            range: 2_000.,
+            half_fov: real::consts::PI,
+            sweep_period: 0.5,
+            reference_signature: 64.,
+            memory_secs: 10.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanPresence {
+    Obstacle,
+    Boid,
+}
+
+#[derive(Debug, Clone)]
+pub struct Contact {
+    pub entt: Entity,
+    /// Last known position in world space.
+    pub pos: TVec3,
+    /// Last known linear velocity in world space.
+    pub linvel: TVec3,
+    pub faction: Option<Faction>,
+    pub presence: ScanPresence,
+    pub last_seen_secs: f64,
+    /// Whether the contact was picked up in the latest sweep.
+    pub in_sight: bool,
+    /// How solid a lock the radar has on the contact. In [0, 1].
+    pub track_quality: TReal,
+}
+
+impl Contact {
+    /// Seconds since the contact was last seen.
+    #[inline]
+    pub fn age(&self, now_secs: f64) -> TReal {
+        (now_secs - self.last_seen_secs) as TReal
+    }
+
+    /// Dead reckoned position.
+    #[inline]
+    pub fn predicted_pos(&self, now_secs: f64) -> TVec3 {
+        self.pos + (self.linvel * self.age(now_secs))
+    }
+}
+
+/// Everything the craft's [`Radar`] knows about.
+/// Craft mind component
+#[derive(Debug, Clone, Component, Default)]
+pub struct Contacts {
+    pub contacts: HashMap<Entity, Contact>,
+    pub last_sweep_secs: f64,
+}
+
+impl Contacts {
+    #[inline]
+    pub fn get(&self, entt: Entity) -> Option<&Contact> {
+        self.contacts.get(&entt)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts.values()
+    }
+
+    pub fn boids(&self) -> impl Iterator<Item = &Contact> {
+        self.iter().filter(|c| c.presence == ScanPresence::Boid)
+    }
+}
+
+/// Chance of a single sweep detecting a signature at the given distance.
+pub fn detection_probability(radar: &Radar, signature: TReal, distance: TReal) -> TReal {
+    if distance <= real::EPSILON {
+        return 1.;
+    }
+    let range_ratio = radar.range / distance;
+    let snr = (signature / radar.reference_signature) * range_ratio * range_ratio;
+    1. - (-snr * real::consts::LN_2).exp()
+}
+
+/// Quality lost by tracks on every missed sweep.
+const TRACK_QUALITY_DECAY: TReal = 0.5;
+
+/// Tracks at least this good ride out failed detection rolls. A solid one lasts
+/// about three sweeps.
+const TRACK_HOLD_QUALITY: TReal = 0.2;
+
+/// The track quality of an object that's in view after a sweep, `None` if it
+/// wasn't picked up. `roll` is uniform in [0, 1).
+fn sighting(track_quality: Option<TReal>, detection_p: TReal, roll: TReal) -> Option<TReal> {
+    match track_quality {
+        _ if roll < detection_p => {
+            Some(track_quality.map_or(detection_p, |q| q + ((1. - q) * detection_p)))
+        }
+        Some(q) if q >= TRACK_HOLD_QUALITY => Some(q * TRACK_QUALITY_DECAY),
+        _ => None,
+    }
+}
+
+/// Drives the detection rolls of [`sweep`]. Seeded so that runs are reproducible.
+pub struct RadarRng(pub rand::rngs::StdRng);
+
+impl Default for RadarRng {
+    fn default() -> Self {
+        use rand::SeedableRng;
+        Self(rand::rngs::StdRng::seed_from_u64(420))
+    }
+}
+
+pub fn sweep(
+    time: Res<Time>,
+    mut radars: Query<(
+        Entity,
+        &Radar,
+        &mut Contacts,
+        &GlobalTransform,
+        &RigidBodyCollidersComponent,
+    )>,
+    objects: Query<(
+        &GlobalTransform,
+        Option<&RigidBodyVelocityComponent>,
+        Option<&CraftDimensions>,
+        Option<&CraftSignature>,
+    )>,
+    parents: Query<&ColliderParentComponent>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    factions: Factions,
+    index: Res<CraftSpatialIndex>,
+    mut rng: ResMut<RadarRng>,
+) {
+    let now = time.seconds_since_startup();
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    let mut sweep_ctr = 0usize;
+    let mut detected = HashSet::default();
+    for (boid_entt, radar, mut contacts, xform, own_colliders) in radars.iter_mut() {
+        if now - contacts.last_sweep_secs < radar.sweep_period as f64 {
+            continue;
+        }
+        sweep_ctr += 1;
+        contacts.last_sweep_secs = now;
+        let own_colliders = &own_colliders.0 .0;
+
+        detected.clear();
+        index.for_each_within_radius(xform.translation, radar.range, |entt, _| {
+            if entt != boid_entt {
+                detected.insert(entt);
+            }
+        });
+        // crafts come from the index, obstacles from the physics engine
+        query_pipeline.intersections_with_shape(
+            &collider_set,
+            &Isometry::translation(
+                xform.translation.x,
+                xform.translation.y,
+                xform.translation.z,
+            ),
+            &Ball::new(radar.range),
+            InteractionGroups::new(ColliderGroups::SENSOR.bits(), ColliderGroups::SOLID.bits()),
+            Some(&|handle| !own_colliders.contains(&handle)),
+            |handle| {
+                // colliders not attached to a rigid body are static objects
+                let entt = parents
+                    .get(handle.entity())
+                    .map(|p| p.handle.entity())
+                    .unwrap_or_else(|_| handle.entity());
+                if entt != boid_entt {
+                    detected.insert(entt);
+                }
+                true
+            },
+        );
+
+        for contact in contacts.contacts.values_mut() {
+            contact.in_sight = false;
+        }
+        for entt in detected.iter().copied() {
+            let (obj_xform, obj_vel, obj_dim, obj_sig) = match objects.get(entt) {
+                Ok(obj) => obj,
+                Err(_) => continue,
+            };
+            let offset = obj_xform.translation - xform.translation;
+            let distance = offset.length();
+
+            // outside the cone
+            if radar.half_fov < real::consts::PI
+                && distance > real::EPSILON
+                && xform.forward().angle_between(offset) > radar.half_fov
+            {
+                continue;
+            }
+
+            let presence = if obj_dim.is_some() {
+                ScanPresence::Boid
+            } else {
+                ScanPresence::Obstacle
+            };
+
+            // crafts without signatures and obstacles are always picked up
+            let detection_p = obj_sig
+                .map(|sig| {
+                    // as seen from the radar
+                    let signature = match obj_dim {
+                        Some(dim) => sig.total_from(dim, obj_xform.rotation.inverse() * -offset),
+                        None => sig.total(),
+                    };
+                    detection_probability(radar, signature, distance)
+                })
+                .unwrap_or(1.);
+            let track_quality = match sighting(
+                contacts.get(entt).map(|c| c.track_quality),
+                detection_p,
+                rng.0.gen::<TReal>(),
+            ) {
+                Some(track_quality) => track_quality,
+                None => continue,
+            };
+
+            // line of sight blocked by some obstacle
+            if distance > real::EPSILON {
+                let origin: Vector<Real> = xform.translation.into();
+                let ray = Ray::new(origin.into(), (offset / distance).into());
+                if let Some((hit_handle, toi)) = query_pipeline.cast_ray(
+                    &collider_set,
+                    &ray,
+                    distance,
+                    true,
+                    InteractionGroups::new(
+                        ColliderGroups::SENSOR.bits(),
+                        ColliderGroups::SOLID.bits(),
+                    ),
+                    Some(&|handle| {
+                        !own_colliders.contains(&handle)
+                            && parents
+                                .get(handle.entity())
+                                .map(|p| p.handle.entity() != entt)
+                                .unwrap_or(handle.entity() != entt)
+                    }),
+                ) {
+                    tracing::trace!(?boid_entt, ?entt, ?hit_handle, ?toi, "contact occluded");
+                    continue;
+                }
+            }
+
+            contacts.contacts.insert(
+                entt,
+                Contact {
+                    entt,
+                    pos: obj_xform.translation,
+                    linvel: obj_vel.map(|v| v.linvel.into()).unwrap_or_default(),
+                    faction: factions.of(entt),
+                    presence,
+                    last_seen_secs: now,
+                    in_sight: true,
+                    track_quality,
+                },
+            );
+        }
+        for contact in contacts.contacts.values_mut() {
+            if !contact.in_sight {
+                contact.track_quality *= TRACK_QUALITY_DECAY;
+            }
+        }
+
+        // forget about the stale ones
+        let memory_secs = radar.memory_secs;
+        contacts
+            .contacts
+            .retain(|_, contact| contact.age(now) <= memory_secs);
+    }
+    tracing::trace!(sweep_ctr);
+}
+
+#[test]
+fn detection_falls_off_with_distance() {
+    let radar = Radar::default();
+    let at_range = detection_probability(&radar, radar.reference_signature, radar.range);
+    assert!((at_range - 0.5).abs() < 1e-4);
+
+    let closer = detection_probability(&radar, radar.reference_signature, radar.range * 0.5);
+    assert!(closer > at_range);
+
+    // cutting thrust shrinks the signature
+    let cold = detection_probability(&radar, radar.reference_signature * 0.5, radar.range);
+    assert!(cold < at_range);
+}
+
+#[test]
+fn tracks_ride_out_missed_sweeps() {
+    // a long shot that keeps missing
+    let (detection_p, miss) = (0.1, 0.5);
+    assert_eq!(sighting(None, detection_p, miss), None);
+
+    // a solid track holds for a few sweeps before getting dropped
+    let mut track_quality = Some(1.);
+    let mut held = 0;
+    while let Some(quality) = sighting(track_quality, detection_p, miss) {
+        assert!(quality < track_quality.unwrap());
+        track_quality = Some(quality);
+        held += 1;
+    }
+    assert_eq!(held, 3);
+
+    // a hit firms it back up
+    let firmed = sighting(Some(0.3), detection_p, 0.).unwrap();
+    assert!(firmed > 0.3);
+}
+
+#[test]
+fn sweep_cone_range_and_occlusion() {
+    use crate::mind::{sensors::spatial, tribe::FactionRelations, CraftMindSystems};
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+        .insert_resource(RapierConfiguration {
+            gravity: [0.0, 0.0, 0.0].into(),
+            ..Default::default()
+        })
+        .init_resource::<FactionRelations>()
+        .init_resource::<CraftSpatialIndex>()
+        .init_resource::<RadarRng>()
+        .add_system(
+            spatial::rebuild
+                .label(CraftMindSystems::SpatialIndex)
+                .before(CraftMindSystems::Sensors),
+        )
+        .add_system(sweep.label(CraftMindSystems::Sensors));
+
+    let mut spawn_craft = |pos: TVec3| {
+        app.world
+            .spawn()
+            .insert(GlobalTransform::from_translation(pos))
+            .insert(CraftDimensions(TVec3::ONE))
+            .insert_bundle(RigidBodyBundle {
+                position: pos.into(),
+                ..Default::default()
+            })
+            .id()
+    };
+    // looking down -Z
+    let radar_craft = spawn_craft(TVec3::ZERO);
+    let ahead = spawn_craft(TVec3::new(0., 0., -100.));
+    let behind = spawn_craft(TVec3::new(0., 0., 100.));
+    let out_of_range = spawn_craft(TVec3::new(0., 0., -1_000.));
+    let occluded = spawn_craft(TVec3::new(100., 0., -200.));
+    app.world
+        .entity_mut(radar_craft)
+        .insert(Radar {
+            range: 500.,
+            half_fov: real::consts::FRAC_PI_4,
+            sweep_period: 0.,
+            ..Default::default()
+        })
+        .insert(Contacts::default());
+    // right between the radar and `occluded`
+    app.world.spawn().insert_bundle(ColliderBundle {
+        shape: ColliderShape::ball(10.).into(),
+        position: TVec3::new(50., 0., -100.).into(),
+        ..Default::default()
+    });
+
+    // give the query pipeline a chance to pick up the obstacle
+    for _ in 0..3 {
+        app.update();
+    }
+
+    let contacts = app.world.get::<Contacts>(radar_craft).unwrap();
+    let in_sight = |entt| contacts.get(entt).map_or(false, |c| c.in_sight);
+    assert!(in_sight(ahead));
+    assert!(!in_sight(behind));
+    assert!(!in_sight(out_of_range));
+    assert!(!in_sight(occluded));
+    assert!(!in_sight(radar_craft));
+}
@@ -0,0 +1,245 @@
+use deps::*;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{craft::CraftDimensions, math::*};
+
+/// Uniform grid over the positions of all crafts, rebuilt every tick.
+/// Resource.
+#[derive(Debug, Clone)]
+pub struct CraftSpatialIndex {
+    /// Edge length of the grid's cells. In meters.
+    pub cell_size: TReal,
+    cells: HashMap<IVec3, smallvec::SmallVec<[(Entity, TVec3); 8]>>,
+    positions: HashMap<Entity, TVec3>,
+    min_cell: IVec3,
+    max_cell: IVec3,
+}
+
+impl Default for CraftSpatialIndex {
+    fn default() -> Self {
+        Self::new(100.)
+    }
+}
+
+impl CraftSpatialIndex {
+    pub fn new(cell_size: TReal) -> Self {
+        Self {
+            cell_size,
+            cells: Default::default(),
+            positions: Default::default(),
+            min_cell: IVec3::ZERO,
+            max_cell: IVec3::ZERO,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    #[inline]
+    pub fn position(&self, entt: Entity) -> Option<TVec3> {
+        self.positions.get(&entt).copied()
+    }
+
+    #[inline]
+    fn cell_of(&self, pos: TVec3) -> IVec3 {
+        (pos / self.cell_size).floor().as_ivec3()
+    }
+
+    pub fn rebuild(&mut self, items: impl IntoIterator<Item = (Entity, TVec3)>) {
+        // keep the allocations around
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.positions.clear();
+        self.min_cell = IVec3::splat(i32::MAX);
+        self.max_cell = IVec3::splat(i32::MIN);
+        for (entt, pos) in items {
+            let cell = self.cell_of(pos);
+            self.min_cell = self.min_cell.min(cell);
+            self.max_cell = self.max_cell.max(cell);
+            self.cells.entry(cell).or_default().push((entt, pos));
+            self.positions.insert(entt, pos);
+        }
+        // don't let cells from long ago pile up
+        if self.cells.len() > self.positions.len() * 4 {
+            self.cells.retain(|_, cell| !cell.is_empty());
+        }
+    }
+
+    /// Calls `visitor` with every item within `radius` of `pos`.
+    pub fn for_each_within_radius(
+        &self,
+        pos: TVec3,
+        radius: TReal,
+        mut visitor: impl FnMut(Entity, TVec3),
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let radius_squared = radius * radius;
+        let min = self.cell_of(pos - TVec3::splat(radius)).max(self.min_cell);
+        let max = self.cell_of(pos + TVec3::splat(radius)).min(self.max_cell);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    if let Some(cell) = self.cells.get(&IVec3::new(x, y, z)) {
+                        for (entt, item_pos) in cell.iter() {
+                            if item_pos.distance_squared(pos) <= radius_squared {
+                                visitor(*entt, *item_pos);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn within_radius(&self, pos: TVec3, radius: TReal) -> Vec<(Entity, TVec3)> {
+        let mut out = Vec::new();
+        self.for_each_within_radius(pos, radius, |entt, pos| out.push((entt, pos)));
+        out
+    }
+
+    /// The `k` items closest to `pos` no farther than `max_radius`, sorted
+    /// by distance. `exclude` is useful for leaving out the querying craft.
+    #[inline]
+    pub fn k_nearest(
+        &self,
+        pos: TVec3,
+        k: usize,
+        max_radius: TReal,
+        exclude: Option<Entity>,
+    ) -> smallvec::SmallVec<[(Entity, TVec3); 8]> {
+        self.k_nearest_where(pos, k, max_radius, |entt| Some(entt) != exclude)
+    }
+
+    /// Like [`Self::k_nearest`] but only considers items that pass the `filter`.
+    pub fn k_nearest_where(
+        &self,
+        pos: TVec3,
+        k: usize,
+        max_radius: TReal,
+        filter: impl Fn(Entity) -> bool,
+    ) -> smallvec::SmallVec<[(Entity, TVec3); 8]> {
+        // (distance squared, entt, pos)
+        let mut best: smallvec::SmallVec<[(TReal, Entity, TVec3); 8]> = Default::default();
+        if k == 0 || self.is_empty() {
+            return Default::default();
+        }
+        let max_radius_squared = max_radius * max_radius;
+        let center = self.cell_of(pos);
+        // search shells of cells around the center until nothing closer can be found
+        let max_shell = (center - self.min_cell)
+            .abs()
+            .max((self.max_cell - center).abs())
+            .max_element();
+        for shell in 0..=max_shell {
+            // anything in this shell or beyond is at least this far away
+            let shell_min_dist = ((shell - 1).max(0)) as TReal * self.cell_size;
+            if shell_min_dist * shell_min_dist > max_radius_squared
+                || (best.len() == k && shell_min_dist * shell_min_dist > best[k - 1].0)
+            {
+                break;
+            }
+            for x in -shell..=shell {
+                for y in -shell..=shell {
+                    for z in -shell..=shell {
+                        // only the surface of the shell
+                        if x.abs().max(y.abs()).max(z.abs()) != shell {
+                            continue;
+                        }
+                        let cell = match self.cells.get(&(center + IVec3::new(x, y, z))) {
+                            Some(cell) => cell,
+                            None => continue,
+                        };
+                        for (entt, item_pos) in cell.iter() {
+                            if !filter(*entt) {
+                                continue;
+                            }
+                            let dist_squared = item_pos.distance_squared(pos);
+                            if dist_squared > max_radius_squared
+                                || (best.len() == k && dist_squared >= best[k - 1].0)
+                            {
+                                continue;
+                            }
+                            let idx = best.partition_point(|(d, ..)| *d <= dist_squared);
+                            best.insert(idx, (dist_squared, *entt, *item_pos));
+                            best.truncate(k);
+                        }
+                    }
+                }
+            }
+        }
+        best.into_iter().map(|(_, entt, pos)| (entt, pos)).collect()
+    }
+}
+
+pub fn rebuild(
+    mut index: ResMut<CraftSpatialIndex>,
+    crafts: Query<(Entity, &GlobalTransform), With<CraftDimensions>>,
+) {
+    index.rebuild(crafts.iter().map(|(entt, xform)| (entt, xform.translation)));
+}
+
+#[cfg(test)]
+fn random_items(count: usize, extent: TReal) -> Vec<(Entity, TVec3)> {
+    use rand::prelude::*;
+    let mut rng = StdRng::seed_from_u64(420);
+    (0..count)
+        .map(|ii| {
+            (
+                Entity::from_raw(ii as u32),
+                TVec3::new(
+                    rng.gen_range(-extent..extent),
+                    rng.gen_range(-extent..extent),
+                    rng.gen_range(-extent..extent),
+                ),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn spatial_index_matches_brute_force() {
+    let items = random_items(300, 1_000.);
+    let mut index = CraftSpatialIndex::new(75.);
+    index.rebuild(items.iter().copied());
+
+    for (query_entt, query_pos) in items.iter().take(20) {
+        let mut brute = items
+            .iter()
+            .filter(|(entt, _)| entt != query_entt)
+            .map(|(entt, pos)| (pos.distance_squared(*query_pos), *entt))
+            .collect::<Vec<_>>();
+        brute.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let nearest = index.k_nearest(*query_pos, 5, TReal::INFINITY, Some(*query_entt));
+        assert_eq!(
+            nearest.iter().map(|(e, _)| *e).collect::<Vec<_>>(),
+            brute.iter().take(5).map(|(_, e)| *e).collect::<Vec<_>>()
+        );
+
+        let mut in_radius = index
+            .within_radius(*query_pos, 200.)
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        in_radius.sort();
+        let mut brute_radius = brute
+            .iter()
+            .filter(|(d, _)| *d <= 200. * 200.)
+            .map(|(_, e)| *e)
+            .chain(std::iter::once(*query_entt))
+            .collect::<Vec<_>>();
+        brute_radius.sort();
+        assert_eq!(in_radius, brute_radius);
+    }
+}
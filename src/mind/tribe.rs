@@ -0,0 +1,124 @@
+use deps::*;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_inspector_egui::Inspectable;
+
+/// Which side an entity's on.
+/// Craft component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Reflect, Inspectable)]
+pub struct Faction(pub u16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relationship {
+    Hostile,
+    Neutral,
+    Allied,
+}
+
+impl Default for Relationship {
+    fn default() -> Self {
+        Self::Neutral
+    }
+}
+
+/// Whether allies get to damage each other.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FriendlyFire {
+    pub projectiles: bool,
+    pub collisions: bool,
+}
+
+/// The relationship matrix between all the factions.
+/// Relationships are symmetric and a faction's always allied with itself.
+#[derive(Debug, Clone, Default)]
+pub struct FactionRelations {
+    matrix: HashMap<(Faction, Faction), Relationship>,
+    /// Used for faction pairs not found in the matrix.
+    pub default_relationship: Relationship,
+    pub friendly_fire: FriendlyFire,
+}
+
+impl FactionRelations {
+    pub fn set(&mut self, a: Faction, b: Faction, relationship: Relationship) {
+        if a == b {
+            tracing::warn!(?a, "a faction's relationship with itself can't be changed");
+            return;
+        }
+        self.matrix.insert((a, b), relationship);
+        self.matrix.insert((b, a), relationship);
+    }
+
+    pub fn get(&self, a: Faction, b: Faction) -> Relationship {
+        if a == b {
+            Relationship::Allied
+        } else {
+            self.matrix
+                .get(&(a, b))
+                .copied()
+                .unwrap_or(self.default_relationship)
+        }
+    }
+}
+
+/// Faction aware queries for use by systems.
+#[derive(SystemParam)]
+pub struct Factions<'w, 's> {
+    relations: Res<'w, FactionRelations>,
+    factions: Query<'w, 's, &'static Faction>,
+}
+
+impl<'w, 's> Factions<'w, 's> {
+    #[inline]
+    pub fn of(&self, entt: Entity) -> Option<Faction> {
+        self.factions.get(entt).ok().copied()
+    }
+
+    #[inline]
+    pub fn relations(&self) -> &FactionRelations {
+        &self.relations
+    }
+
+    /// Entities without a [`Faction`] are considered neutral to everyone.
+    pub fn relationship(&self, a: Entity, b: Entity) -> Relationship {
+        match (self.of(a), self.of(b)) {
+            (Some(a), Some(b)) => self.relations.get(a, b),
+            _ => Relationship::Neutral,
+        }
+    }
+
+    #[inline]
+    pub fn is_hostile(&self, a: Entity, b: Entity) -> bool {
+        self.relationship(a, b) == Relationship::Hostile
+    }
+
+    #[inline]
+    pub fn is_allied(&self, a: Entity, b: Entity) -> bool {
+        self.relationship(a, b) == Relationship::Allied
+    }
+
+    /// Whether projectiles fired by `attacker` should damage `victim`.
+    pub fn allows_projectile_damage(&self, attacker: Entity, victim: Entity) -> bool {
+        self.relations.friendly_fire.projectiles || !self.is_allied(attacker, victim)
+    }
+
+    /// Whether the two entities should damage each other on collision.
+    pub fn allows_collision_damage(&self, a: Entity, b: Entity) -> bool {
+        self.relations.friendly_fire.collisions || !self.is_allied(a, b)
+    }
+}
+
+#[test]
+fn faction_relations() {
+    let (a, b, c) = (Faction(0), Faction(1), Faction(2));
+    let mut relations = FactionRelations::default();
+    relations.set(a, b, Relationship::Hostile);
+    relations.set(c, a, Relationship::Allied);
+
+    assert_eq!(relations.get(a, a), Relationship::Allied);
+    assert_eq!(relations.get(b, a), Relationship::Hostile);
+    assert_eq!(relations.get(a, c), Relationship::Allied);
+    assert_eq!(relations.get(b, c), Relationship::Neutral);
+
+    relations.default_relationship = Relationship::Hostile;
+    assert_eq!(relations.get(c, b), Relationship::Hostile);
+}
@@ -6,7 +6,7 @@ use crate::math::*;
 
 #[derive(Debug, Clone)]
 pub struct PIDControllerVec3 {
-    last_state: Vec3,
+    last_state: Option<Vec3>,
     integrat_err: Vec3,
     pub proportional_gain: Vec3,
     pub integrat_gain: Vec3,
@@ -40,15 +40,22 @@ impl PIDControllerVec3 {
         self.integrat_err =
             (self.integrat_err + (err * delta_time)).clamp(self.integrat_min, self.integrat_max);
 
+        // no rate on the first update, it'd be measured against zero
+        let state_rate = match self.last_state {
+            Some(last_state) if delta_time > TReal::EPSILON => (state - last_state) / delta_time,
+            _ => Vec3::ZERO,
+        };
+
         let drive_v =
             // calculate the proportional term
             self.proportional_gain * err
             // caclulate the integral term
             + self.integrat_gain * self.integrat_err
             // caclulate the differntal term
-            + self.differntial_gain * ((state - self.last_state) * delta_time);
+            // on the measurement and not the error so that setpoint jumps don't kick
+            - self.differntial_gain * state_rate;
 
-        self.last_state = state;
+        self.last_state = Some(state);
 
         drive_v
     }